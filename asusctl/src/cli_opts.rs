@@ -1,5 +1,7 @@
 use gumdrop::Options;
+use rog_platform::asus_armoury::ChargeMode;
 use rog_platform::platform::PlatformProfile;
+use rog_platform::profile_rules::{RuleAction, RuleCondition};
 
 use crate::anime_cli::AnimeCommand;
 use crate::aura_cli::{LedBrightness, LedPowerCommand1, LedPowerCommand2, SetAuraBuiltin};
@@ -41,6 +43,10 @@ pub enum CliCommand {
     Profile(ProfileCommand),
     #[options(help = "Set, select, or modify fan curves if supported")]
     FanCurve(FanCurveCommand),
+    #[options(help = "Switch the platform profile and other settings by rules")]
+    Rules(RulesCommand),
+    #[options(help = "Show or change how the battery is charged")]
+    Battery(BatteryCommand),
     #[options(help = "Set the graphics mode (obsoleted by supergfxctl)")]
     Graphics(GraphicsCommand),
     #[options(name = "anime", help = "Manage AniMe Matrix")]
@@ -56,6 +62,12 @@ pub enum CliCommand {
     Armoury(ArmouryCommand),
     #[options(name = "backlight", help = "Set screen backlight levels")]
     Backlight(BacklightCommand),
+    #[options(
+        name = "snapshot",
+        help = "Record the sysfs attributes asusd uses to a file for bug reports, or extract a \
+                recording for replay. Does not require asusd to be running"
+    )]
+    Snapshot(SnapshotCommand),
 }
 
 #[derive(Debug, Clone, Options)]
@@ -76,6 +88,79 @@ pub struct ProfileCommand {
     pub profile_set: Option<PlatformProfile>,
 }
 
+#[derive(Options)]
+pub struct RulesCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+
+    #[options(help = "list the rules and the active rule")]
+    pub list: bool,
+
+    #[options(meta = "", help = "add or replace the rule <name>, requires --then")]
+    pub add: Option<String>,
+
+    #[options(
+        meta = "",
+        help = "priority of the added rule, the highest of the rules matching is active"
+    )]
+    pub priority: u32,
+
+    #[options(
+        meta = "",
+        help = "a condition of the added rule, repeat for more: power=<ac|battery>, battery<N, \
+                battery>N, time=HH:MM-HH:MM, lid=<open|closed>, display=<external|internal>, \
+                process=<name>"
+    )]
+    pub when: Vec<RuleCondition>,
+
+    #[options(
+        meta = "",
+        help = "an action of the added rule, repeat for more: profile=<profile>, epp=<epp>, \
+                ppt=<on|off>, fan-curves=<on|off>, kbd-brightness=<0-3>, charge-limit=<20-100>"
+    )]
+    pub then: Vec<RuleAction>,
+
+    #[options(help = "add the rule disabled")]
+    pub disabled: bool,
+
+    #[options(meta = "", help = "remove the rule <name>")]
+    pub remove: Option<String>,
+}
+
+#[derive(Options)]
+pub struct BatteryCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+
+    #[options(
+        help = "show the charge limit, charge mode, adaptive charging and the learned unplug times"
+    )]
+    pub show: bool,
+
+    #[options(
+        meta = "",
+        help = "learn when the laptop is usually unplugged and charge past the limit before then \
+                <true|false>"
+    )]
+    pub adaptive: Option<bool>,
+
+    #[options(meta = "", help = "set the charge mode <normal|bypass|ac-only>")]
+    pub charge_mode: Option<ChargeMode>,
+
+    #[options(
+        meta = "",
+        help = "set the charge mode to use on AC <normal|bypass|ac-only>, e.g bypass to run from \
+                AC without wearing the battery while gaming"
+    )]
+    pub charge_mode_on_ac: Option<ChargeMode>,
+
+    #[options(
+        meta = "",
+        help = "set the charge mode on AC/battery change <true|false>"
+    )]
+    pub change_charge_mode_on_ac: Option<bool>,
+}
+
 #[derive(Options)]
 pub struct LedModeCommand {
     #[options(help = "print help message")]
@@ -98,6 +183,23 @@ pub struct GraphicsCommand {
 pub struct ArmouryCommand {
     #[options(help = "print help message")]
     pub help: bool,
+    #[options(
+        help = "apply the power limits given as one set, checked against each other and the \
+                limits of each attribute before any is written"
+    )]
+    pub limits: bool,
+    #[options(help = "list the power limit presets of the current profile and power state")]
+    pub list_presets: bool,
+    #[options(
+        meta = "",
+        help = "save the power limits given, or the current ones if none are given, as a preset \
+                of the current profile and power state"
+    )]
+    pub save_preset: Option<String>,
+    #[options(meta = "", help = "apply a power limit preset")]
+    pub preset: Option<String>,
+    #[options(meta = "", help = "remove a power limit preset")]
+    pub remove_preset: Option<String>,
     #[options(
         free,
         help = "append each value name followed by the value to set. `-1` sets to default"
@@ -122,3 +224,18 @@ pub struct BacklightCommand {
     )]
     pub sync_screenpad_brightness: Option<bool>,
 }
+
+#[derive(Options)]
+pub struct SnapshotCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(meta = "", help = "write a snapshot of this laptop to <file>")]
+    pub capture: Option<String>,
+    #[options(meta = "", help = "extract the snapshot <file>, requires --dir")]
+    pub extract: Option<String>,
+    #[options(
+        meta = "",
+        help = "directory to extract to, asusd can then be run with ROG_SYSFS_ROOT=<dir>"
+    )]
+    pub dir: Option<String>,
+}
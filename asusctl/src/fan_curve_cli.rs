@@ -1,5 +1,6 @@
 use gumdrop::Options;
 use rog_platform::platform::PlatformProfile;
+use rog_profiles::curve_points::{CurvePoints, CurvePreset};
 use rog_profiles::fan_curve_set::CurveData;
 use rog_profiles::FanCurvePU;
 
@@ -36,7 +37,7 @@ pub struct FanCurveCommand {
 
     #[options(
         meta = "",
-        help = "select fan <cpu/gpu/mid> to modify. `--mod_profile` required"
+        help = "select fan <cpu/gpu/mid> to show or modify. `--mod_profile` required"
     )]
     pub fan: Option<FanCurvePU>,
 
@@ -46,4 +47,61 @@ pub struct FanCurveCommand {
                 `--mod-profile` required. If '%' is omitted the fan range is 0-255"
     )]
     pub data: Option<CurveData>,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "like `--data` with any number of points, which are smoothed to the 8 the firmware \
+                takes. `--mod-profile` required"
+    )]
+    pub points: Option<CurvePoints>,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "set the curve to a <silent/balanced/aggressive> preset for this model. \
+                `--mod-profile` required"
+    )]
+    pub preset: Option<CurvePreset>,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "export fan curves to <file>. Only one profile if `--mod-profile` and one fan if \
+                `--fan` are given"
+    )]
+    pub export: Option<String>,
+
+    #[options(no_short, meta = "", help = "import fan curves from <file>")]
+    pub import: Option<String>,
+
+    #[options(
+        no_short,
+        help = "with `--import`, import curves exported from a different model"
+    )]
+    pub force: bool,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "use separate <true/false> fan curves on battery, starting from a copy of the AC \
+                curves"
+    )]
+    pub battery_curves: Option<bool>,
+
+    #[options(
+        no_short,
+        help = "with `--mod-profile`, show or set `--data` for the battery curves rather than AC"
+    )]
+    pub battery: bool,
+
+    #[options(
+        no_short,
+        help = "measure the RPM of each fan, or one if `--fan` is given, across its duty range. \
+                Takes around a minute and a half per fan"
+    )]
+    pub calibrate: bool,
+
+    #[options(no_short, help = "show the measured RPM of each fan")]
+    pub calibration: bool,
 }
@@ -6,7 +6,6 @@ use std::thread::sleep;
 
 use anime_cli::{AnimeActions, AnimeCommand};
 use aura_cli::{LedPowerCommand1, LedPowerCommand2};
-use dmi_id::DMIID;
 use fan_curve_cli::FanCurveCommand;
 use gumdrop::{Opt, Options};
 use log::{error, info};
@@ -22,9 +21,17 @@ use rog_dbus::zbus_aura::AuraProxyBlocking;
 use rog_dbus::zbus_backlight::BacklightProxyBlocking;
 use rog_dbus::zbus_fan_curves::FanCurvesProxyBlocking;
 use rog_dbus::zbus_platform::PlatformProxyBlocking;
+use rog_dbus::zbus_profile_rules::ProfileRulesProxyBlocking;
 use rog_dbus::zbus_slash::SlashProxyBlocking;
+use rog_platform::asus_armoury::{FirmwareAttribute, PowerLimits};
 use rog_platform::platform::{PlatformProfile, Properties};
+use rog_platform::profile_rules::ProfileRule;
+use rog_platform::snapshot::SysfsSnapshot;
+use rog_platform::sysfs::SysfsRoot;
+use rog_profiles::calibration::FanCalibration;
 use rog_profiles::error::ProfileError;
+use rog_profiles::fan_curve_set::CurveData;
+use rog_profiles::FanCurvePU;
 use rog_scsi::AuraMode;
 use rog_slash::SlashMode;
 use ron::ser::PrettyConfig;
@@ -69,6 +76,14 @@ fn main() {
         }
     };
 
+    // Snapshots are for bug reports so must work even if asusd is broken
+    if let Some(CliCommand::Snapshot(cmd)) = &parsed.command {
+        if let Err(err) = handle_snapshot(cmd) {
+            println!("Error: {err}");
+        }
+        return;
+    }
+
     let conn = Connection::system().unwrap();
     if let Ok(platform_proxy) = PlatformProxyBlocking::new(&conn).map_err(|e| {
         check_service("asusd");
@@ -135,7 +150,7 @@ fn print_error_help(
 }
 
 fn print_info() {
-    let dmi = DMIID::new().unwrap_or_default();
+    let dmi = SysfsRoot::get().dmi_id().unwrap_or_default();
     let board_name = dmi.board_name;
     let prod_family = dmi.product_family;
     println!("asusctl version: {}", env!("CARGO_PKG_VERSION"));
@@ -214,12 +229,16 @@ fn do_parsed(
         Some(CliCommand::FanCurve(cmd)) => {
             handle_fan_curve(&conn, cmd)?;
         }
+        Some(CliCommand::Rules(cmd)) => handle_rules(&conn, cmd)?,
+        Some(CliCommand::Battery(cmd)) => handle_battery(&conn, cmd)?,
         Some(CliCommand::Graphics(_)) => do_gfx(),
         Some(CliCommand::Anime(cmd)) => handle_anime(cmd)?,
         Some(CliCommand::Slash(cmd)) => handle_slash(cmd)?,
         Some(CliCommand::Scsi(cmd)) => handle_scsi(cmd)?,
-        Some(CliCommand::Armoury(cmd)) => handle_armoury_command(cmd)?,
+        Some(CliCommand::Armoury(cmd)) => handle_armoury_command(&conn, cmd)?,
         Some(CliCommand::Backlight(cmd)) => handle_backlight(cmd)?,
+        // Dispatched in main() before connecting to asusd
+        Some(CliCommand::Snapshot(_)) => {}
         None => {
             if (!parsed.show_supported
                 && parsed.kbd_bright.is_none()
@@ -429,6 +448,40 @@ fn handle_backlight(cmd: &BacklightCommand) -> Result<(), Box<dyn std::error::Er
     Ok(())
 }
 
+fn handle_snapshot(cmd: &SnapshotCommand) -> Result<(), Box<dyn std::error::Error>> {
+    if (cmd.capture.is_none() && cmd.extract.is_none()) || cmd.help {
+        println!("Missing arg or command\n\n{}", cmd.self_usage());
+        return Ok(());
+    }
+
+    if let Some(file) = &cmd.capture {
+        let snapshot = SysfsSnapshot::capture(&SysfsRoot::get());
+        let ron = ron::ser::to_string_pretty(&snapshot, PrettyConfig::new().depth_limit(4))?;
+        std::fs::write(file, ron)?;
+        println!(
+            "Captured {} attributes from {} to {file}",
+            snapshot.files.len(),
+            snapshot.dmi.board_name.trim()
+        );
+    }
+
+    if let Some(file) = &cmd.extract {
+        let Some(dir) = &cmd.dir else {
+            return Err("--extract requires --dir".into());
+        };
+        let snapshot: SysfsSnapshot = ron::from_str(&std::fs::read_to_string(file)?)?;
+        snapshot.extract(Path::new(dir))?;
+        println!(
+            "Extracted {} attributes from {} to {dir}",
+            snapshot.files.len(),
+            snapshot.dmi.board_name.trim()
+        );
+        println!("Replay with: ROG_SYSFS_ROOT={dir} asusd");
+    }
+
+    Ok(())
+}
+
 fn handle_anime(cmd: &AnimeCommand) -> Result<(), Box<dyn std::error::Error>> {
     if (cmd.command.is_none()
         && cmd.enable_display.is_none()
@@ -1014,6 +1067,130 @@ fn handle_throttle_profile(
     Ok(())
 }
 
+fn handle_rules(conn: &Connection, cmd: &RulesCommand) -> Result<(), Box<dyn std::error::Error>> {
+    if !cmd.list && cmd.add.is_none() && cmd.remove.is_none() {
+        if !cmd.help {
+            println!("Missing arg or command\n");
+        }
+        println!("{}", RulesCommand::usage());
+        return Ok(());
+    }
+
+    let proxy = ProfileRulesProxyBlocking::new(conn)?;
+    if let Some(name) = &cmd.remove {
+        proxy.remove_rule(name)?;
+    }
+    if let Some(name) = &cmd.add {
+        if cmd.then.is_empty() {
+            println!("A rule needs at least one --then action");
+            return Ok(());
+        }
+        proxy.add_rule(&ProfileRule {
+            name: name.clone(),
+            priority: cmd.priority,
+            enabled: !cmd.disabled,
+            conditions: cmd.when.clone(),
+            actions: cmd.then.clone(),
+        })?;
+    }
+
+    if cmd.list {
+        let active = proxy.active_rule()?;
+        for rule in proxy.rules()? {
+            let conditions: Vec<String> = rule.conditions.iter().map(|c| c.to_string()).collect();
+            let actions: Vec<String> = rule.actions.iter().map(|a| a.to_string()).collect();
+            println!(
+                "{}{} (priority {}{}): when {} then {}",
+                if rule.name == active { "* " } else { "  " },
+                rule.name,
+                rule.priority,
+                if rule.enabled { "" } else { ", disabled" },
+                if conditions.is_empty() {
+                    "always".to_owned()
+                } else {
+                    conditions.join(", ")
+                },
+                actions.join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_battery(
+    conn: &Connection,
+    cmd: &BatteryCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !cmd.show
+        && cmd.adaptive.is_none()
+        && cmd.charge_mode.is_none()
+        && cmd.charge_mode_on_ac.is_none()
+        && cmd.change_charge_mode_on_ac.is_none()
+    {
+        if !cmd.help {
+            println!("Missing arg or command\n");
+        }
+        println!("{}", BatteryCommand::usage());
+        return Ok(());
+    }
+
+    let proxy = PlatformProxyBlocking::new(conn)?;
+    if let Some(enabled) = cmd.adaptive {
+        proxy.set_adaptive_charging(enabled)?;
+    }
+    if let Some(mode) = cmd.charge_mode {
+        proxy.set_charge_mode(mode)?;
+    }
+    if let Some(mode) = cmd.charge_mode_on_ac {
+        proxy.set_charge_mode_on_ac(mode)?;
+    }
+    if let Some(change) = cmd.change_charge_mode_on_ac {
+        proxy.set_change_charge_mode_on_ac(change)?;
+    }
+
+    if cmd.show {
+        println!("Charge limit: {}%", proxy.charge_control_end_threshold()?);
+        if let Ok(mode) = proxy.charge_mode() {
+            let choices: Vec<String> = proxy
+                .charge_mode_choices()?
+                .iter()
+                .map(|m| m.to_string())
+                .collect();
+            println!("Charge mode: {mode} (supported: {})", choices.join(", "));
+            println!(
+                "Charge mode on AC: {}{}",
+                proxy.charge_mode_on_ac()?,
+                if proxy.change_charge_mode_on_ac()? {
+                    ""
+                } else {
+                    " (not changed on AC)"
+                }
+            );
+        }
+        println!(
+            "Adaptive charging: {}",
+            if proxy.adaptive_charging()? {
+                "on"
+            } else {
+                "off"
+            }
+        );
+        let learned = proxy.learned_charge_targets()?;
+        if learned.is_empty() {
+            println!("Usual unplug times: none learned yet");
+        } else {
+            println!("Usual unplug times: {}", learned.join(", "));
+        }
+        let next = proxy.next_charge_action()?;
+        if !next.is_empty() {
+            println!("Next: {next}");
+        }
+    }
+
+    Ok(())
+}
+
 fn handle_fan_curve(
     conn: &Connection,
     cmd: &FanCurveCommand,
@@ -1024,7 +1201,15 @@ fn handle_fan_curve(
         return Err(ProfileError::NotSupported.into());
     };
 
-    if !cmd.get_enabled && !cmd.default && cmd.mod_profile.is_none() {
+    if !cmd.get_enabled
+        && !cmd.default
+        && cmd.mod_profile.is_none()
+        && cmd.export.is_none()
+        && cmd.import.is_none()
+        && !cmd.calibrate
+        && !cmd.calibration
+        && cmd.battery_curves.is_none()
+    {
         if !cmd.help {
             println!("Missing arg or command\n");
         }
@@ -1036,16 +1221,68 @@ fn handle_fan_curve(
         return Ok(());
     }
 
-    if (cmd.enable_fan_curves.is_some() || cmd.fan.is_some() || cmd.data.is_some())
+    if (cmd.enable_fan_curves.is_some()
+        || cmd.fan.is_some()
+        || cmd.data.is_some()
+        || cmd.points.is_some()
+        || cmd.preset.is_some())
         && cmd.mod_profile.is_none()
+        && cmd.export.is_none()
+        && !cmd.calibrate
     {
         println!(
-            "--enable-fan-curves, --enable-fan-curve, --fan, and --data options require \
-             --mod-profile"
+            "--enable-fan-curves, --enable-fan-curve, --fan, --data, --points and --preset \
+             options require --mod-profile"
         );
         return Ok(());
     }
 
+    if let Some(file) = &cmd.export {
+        let profiles: Vec<PlatformProfile> = cmd.mod_profile.into_iter().collect();
+        let fans: Vec<FanCurvePU> = cmd.fan.into_iter().collect();
+        let document = fan_proxy.export_fan_curves(&profiles, &fans)?;
+        std::fs::write(file, document)?;
+        println!("Exported fan curves to {file}");
+        return Ok(());
+    }
+
+    if let Some(file) = &cmd.import {
+        let document = std::fs::read_to_string(file)?;
+        for warning in fan_proxy.import_fan_curves(&document, cmd.force)? {
+            println!("Warning: {warning}");
+        }
+        println!("Imported fan curves from {file}");
+        return Ok(());
+    }
+
+    if cmd.calibrate {
+        let fans: Vec<FanCurvePU> = cmd.fan.into_iter().collect();
+        fan_proxy.calibrate_fans(&fans)?;
+        println!("Calibrating, the fans will speed up and slow down");
+        while fan_proxy.calibrating()? {
+            sleep(std::time::Duration::from_secs(1));
+        }
+    }
+
+    if cmd.calibrate || cmd.calibration {
+        let calibration = fan_proxy.fan_calibration()?;
+        if calibration.is_empty() {
+            println!("No fans have been calibrated");
+        }
+        for fan in &calibration {
+            print_fan_calibration(fan);
+        }
+        return Ok(());
+    }
+
+    if let Some(enabled) = cmd.battery_curves {
+        fan_proxy.set_battery_fan_curves(enabled)?;
+    }
+    if cmd.battery && !fan_proxy.battery_fan_curves()? {
+        println!("Battery uses the AC fan curves, use `--battery-curves true` to give it its own");
+        return Ok(());
+    }
+
     let plat_proxy = PlatformProxyBlocking::new(conn)?;
     if cmd.get_enabled {
         let profile = plat_proxy.platform_profile()?;
@@ -1061,10 +1298,22 @@ fn handle_fan_curve(
     }
 
     if let Some(profile) = cmd.mod_profile {
-        if cmd.enable_fan_curves.is_none() && cmd.data.is_none() {
-            let data = fan_proxy.fan_curve_data(profile)?;
+        if cmd.enable_fan_curves.is_none()
+            && cmd.data.is_none()
+            && cmd.points.is_none()
+            && cmd.preset.is_none()
+        {
+            let mut data = if cmd.battery {
+                fan_proxy.fan_curve_data_for_power(profile, false)?
+            } else {
+                fan_proxy.fan_curve_data(profile)?
+            };
+            if let Some(fan) = cmd.fan {
+                data.retain(|curve| curve.fan == fan);
+            }
             let ron = ron::ser::to_string_pretty(&data, PrettyConfig::new().depth_limit(4))?;
-            println!("\nFan curves for {:?}\n\n{}", profile, ron);
+            let power = if cmd.battery { " on battery" } else { "" };
+            println!("\nFan curves for {:?}{power}\n\n{}", profile, ron);
         }
 
         if let Some(enabled) = cmd.enable_fan_curves {
@@ -1082,16 +1331,79 @@ fn handle_fan_curve(
             }
         }
 
+        let fan = cmd.fan.unwrap_or_default();
         if let Some(mut curve) = cmd.data.clone() {
-            let fan = cmd.fan.unwrap_or_default();
             curve.set_fan(fan);
-            fan_proxy.set_fan_curve(profile, curve)?;
+            if cmd.battery {
+                fan_proxy.set_fan_curve_for_power(profile, false, curve.clone())?;
+            } else {
+                fan_proxy.set_fan_curve(profile, curve.clone())?;
+            }
+            print_dead_zone_warnings(&fan_proxy, &curve);
+        }
+
+        let points = match cmd.preset {
+            Some(preset) => Some(fan_proxy.curve_preset(preset, fan)?),
+            None => cmd.points.clone(),
+        };
+        if let Some(mut points) = points {
+            let read_curves = || {
+                if cmd.battery {
+                    fan_proxy.fan_curve_data_for_power(profile, false)
+                } else {
+                    fan_proxy.fan_curve_data(profile)
+                }
+            };
+            // Keep the curve enabled or disabled as it was
+            points.fan = fan;
+            points.enabled = read_curves()?.iter().any(|c| c.fan == fan && c.enabled);
+            if cmd.battery {
+                fan_proxy.set_fan_curve_points_for_power(profile, false, points)?;
+            } else {
+                fan_proxy.set_fan_curve_points(profile, points)?;
+            }
+            if let Some(curve) = read_curves()?.iter().find(|c| c.fan == fan) {
+                println!("{}", String::from(curve));
+                print_dead_zone_warnings(&fan_proxy, curve);
+            }
         }
     }
 
     Ok(())
 }
 
+/// Warn about each point of the curve too low for the fan to spin, if the fan
+/// has been calibrated
+fn print_dead_zone_warnings(fan_proxy: &FanCurvesProxyBlocking, curve: &CurveData) {
+    let calibration = fan_proxy.fan_calibration().unwrap_or_default();
+    let Some(calibration) = calibration.iter().find(|c| c.fan == curve.fan) else {
+        return;
+    };
+    for index in calibration.dead_zone_points(curve) {
+        println!(
+            "Warning: point {} at {}% is below the {}% the {:?} fan needs to spin",
+            index + 1,
+            curve.pwm[index] as u32 * 100 / 255,
+            calibration.spin_up_pwm().unwrap_or_default() as u32 * 100 / 255,
+            curve.fan
+        );
+    }
+}
+
+fn print_fan_calibration(calibration: &FanCalibration) {
+    match calibration.spin_up_pwm() {
+        Some(pwm) => println!(
+            "{:?} fan, spins from {}%:",
+            calibration.fan,
+            pwm as u32 * 100 / 255
+        ),
+        None => println!("{:?} fan, did not spin:", calibration.fan),
+    }
+    for (pwm, rpm) in &calibration.points {
+        println!("  {:>3}%: {rpm} RPM", *pwm as u32 * 100 / 255);
+    }
+}
+
 fn check_systemd_unit_active(name: &str) -> bool {
     if let Ok(out) = Command::new("systemctl")
         .arg("is-active")
@@ -1169,7 +1481,67 @@ fn print_firmware_attr(attr: &AsusArmouryProxyBlocking) -> Result<(), Box<dyn st
     Ok(())
 }
 
-fn handle_armoury_command(cmd: &ArmouryCommand) -> Result<(), Box<dyn std::error::Error>> {
+fn handle_power_limits(
+    conn: &Connection,
+    cmd: &ArmouryCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut limits = PowerLimits::default();
+    for pair in cmd.free.chunks(2) {
+        let [name, value] = pair else {
+            return Err("Each power limit must be paired with a value".into());
+        };
+        limits.set(FirmwareAttribute::from(name.as_str()), value.parse()?)?;
+    }
+
+    let proxy = PlatformProxyBlocking::new(conn)?;
+    if let Some(name) = &cmd.remove_preset {
+        proxy.remove_power_limit_preset(name)?;
+    }
+    if let Some(name) = &cmd.save_preset {
+        if limits.is_empty() {
+            limits = proxy.power_limits()?;
+        }
+        proxy.save_power_limit_preset(name, limits)?;
+    } else if cmd.limits {
+        proxy.apply_power_limits(limits)?;
+    }
+    if let Some(name) = &cmd.preset {
+        proxy.apply_power_limit_preset(name)?;
+    }
+
+    if cmd.list_presets {
+        for name in proxy.power_limit_presets()? {
+            let limits: Vec<String> = proxy
+                .power_limit_preset(&name)?
+                .iter()
+                .map(|(attr, value)| format!("{} {value}", <&str>::from(attr)))
+                .collect();
+            println!("{name}: {}", limits.join(", "));
+        }
+    } else {
+        let limits: Vec<String> = proxy
+            .power_limits()?
+            .iter()
+            .map(|(attr, value)| format!("{} {value}", <&str>::from(attr)))
+            .collect();
+        println!("Power limits: {}", limits.join(", "));
+    }
+    Ok(())
+}
+
+fn handle_armoury_command(
+    conn: &Connection,
+    cmd: &ArmouryCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !cmd.help
+        && (cmd.limits
+            || cmd.list_presets
+            || cmd.save_preset.is_some()
+            || cmd.preset.is_some()
+            || cmd.remove_preset.is_some())
+    {
+        return handle_power_limits(conn, cmd);
+    }
     {
         if cmd.free.is_empty() || cmd.free.len() % 2 != 0 || cmd.help {
             const USAGE: &str = "Usage: asusctl platform panel_overdrive 1 nv_dynamic_boost 5";
@@ -0,0 +1,200 @@
+//! Ask `asusd` to switch the platform profile, PPT tuning and fan curves
+//! while a configured application is running, and put the previous settings
+//! back once it exits.
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use log::{info, warn};
+use rog_dbus::zbus_fan_curves::FanCurvesProxyBlocking;
+use rog_dbus::zbus_platform::PlatformProxyBlocking;
+use rog_platform::platform::PlatformProfile;
+use rog_platform::profile_rules::{process_matches, running_processes};
+use rog_profiles::fan_curve_set::CurveData;
+use rog_profiles::FanCurvePU;
+
+use crate::config::AppProfile;
+
+/// How often the running processes are checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The first profile whose executable is running
+pub fn find_app_profile<'a>(
+    profiles: &'a [AppProfile],
+    processes: &[String],
+) -> Option<&'a AppProfile> {
+    profiles.iter().find(|app| {
+        !app.exe.is_empty() && processes.iter().any(|comm| process_matches(comm, &app.exe))
+    })
+}
+
+/// If each fan's curve is enabled
+fn fan_curves_enabled(curves: &[CurveData]) -> Vec<(FanCurvePU, bool)> {
+    curves
+        .iter()
+        .map(|curve| (curve.fan, curve.enabled))
+        .collect()
+}
+
+/// The settings from before an application profile was applied
+#[derive(Debug, Default)]
+struct Saved {
+    platform_profile: Option<PlatformProfile>,
+    ppt_group: Option<bool>,
+    /// The platform profile whose fan curves were changed, and if each fan's
+    /// curve was enabled
+    fan_curves: Option<(PlatformProfile, Vec<(FanCurvePU, bool)>)>,
+}
+
+pub struct AppWatcher {
+    profiles: Vec<AppProfile>,
+    platform: PlatformProxyBlocking<'static>,
+    /// `None` if the laptop has no fan curve support
+    fan_curves: Option<FanCurvesProxyBlocking<'static>>,
+    active: Option<(AppProfile, Saved)>,
+}
+
+impl AppWatcher {
+    pub fn new(
+        profiles: Vec<AppProfile>,
+        platform: PlatformProxyBlocking<'static>,
+        fan_curves: Option<FanCurvesProxyBlocking<'static>>,
+    ) -> Self {
+        Self {
+            profiles,
+            platform,
+            fan_curves,
+            active: None,
+        }
+    }
+
+    /// Each setting is saved before it is changed, so whatever was applied
+    /// before an error is still restored
+    fn apply(&self, app: &AppProfile, saved: &mut Saved) -> zbus::Result<()> {
+        if let Some(profile) = app.platform_profile {
+            saved.platform_profile = Some(self.platform.platform_profile()?);
+            self.platform.set_platform_profile(profile)?;
+        }
+        if let Some(enable) = app.ppt_group {
+            saved.ppt_group = Some(self.platform.enable_ppt_group()?);
+            self.platform.set_enable_ppt_group(enable)?;
+        }
+        if let (Some(enable), Some(fan_curves)) = (app.fan_curves, &self.fan_curves) {
+            let profile = self.platform.platform_profile()?;
+            let enabled = fan_curves_enabled(&fan_curves.fan_curve_data(profile)?);
+            saved.fan_curves = Some((profile, enabled));
+            fan_curves.set_fan_curves_enabled(profile, enable)?;
+        }
+        Ok(())
+    }
+
+    /// Restore in the reverse order of `apply()`, the fan curves belong to the
+    /// profile that was set by it
+    fn restore(&self, saved: &Saved) {
+        if let (Some((profile, enabled)), Some(fan_curves)) = (&saved.fan_curves, &self.fan_curves)
+        {
+            for &(fan, enabled) in enabled {
+                fan_curves
+                    .set_profile_fan_curve_enabled(*profile, fan, enabled)
+                    .map_err(|e| warn!("Could not restore the {fan:?} fan curve: {e}"))
+                    .ok();
+            }
+        }
+        if let Some(enable) = saved.ppt_group {
+            self.platform
+                .set_enable_ppt_group(enable)
+                .map_err(|e| warn!("Could not restore the PPT tuning group: {e}"))
+                .ok();
+        }
+        if let Some(profile) = saved.platform_profile {
+            self.platform
+                .set_platform_profile(profile)
+                .map_err(|e| warn!("Could not restore the platform profile: {e}"))
+                .ok();
+        }
+    }
+
+    /// Apply the profile of the running application if it changed
+    pub fn check(&mut self) {
+        let processes = running_processes();
+        let next = find_app_profile(&self.profiles, &processes).cloned();
+        if self.active.as_ref().map(|(app, _)| app) == next.as_ref() {
+            return;
+        }
+        if let Some((app, saved)) = self.active.take() {
+            info!("Application profile for {} is no longer active", app.exe);
+            self.restore(&saved);
+        }
+        if let Some(app) = next {
+            info!("{} is running, applying its application profile", app.exe);
+            let mut saved = Saved::default();
+            self.apply(&app, &mut saved)
+                .map_err(|e| warn!("Could not apply the profile for {}: {e}", app.exe))
+                .ok();
+            self.active = Some((app, saved));
+        }
+    }
+
+    /// Check the running processes until the daemon exits
+    pub fn run(mut self) {
+        loop {
+            self.check();
+            sleep(CHECK_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_profile_first_running() {
+        let app = |exe: &str| AppProfile {
+            exe: exe.to_owned(),
+            platform_profile: Some(PlatformProfile::Performance),
+            ..Default::default()
+        };
+        let profiles = vec![
+            app(""),
+            app("steam"),
+            app("MonsterHunterWilds.exe"),
+        ];
+        let processes = vec![
+            "bash".to_owned(),
+            "MonsterHunterWi".to_owned(),
+            "steam".to_owned(),
+        ];
+        assert_eq!(
+            find_app_profile(&profiles, &processes).map(|app| app.exe.as_str()),
+            Some("steam")
+        );
+        // The kernel truncates the name
+        assert_eq!(
+            find_app_profile(&profiles[2..], &processes).map(|app| app.exe.as_str()),
+            Some("MonsterHunterWilds.exe")
+        );
+        assert!(find_app_profile(&profiles, &["bash".to_owned()]).is_none());
+    }
+
+    #[test]
+    fn fan_curves_enabled_per_fan() {
+        let curve = |fan, enabled| CurveData {
+            fan,
+            enabled,
+            ..Default::default()
+        };
+        let curves = vec![
+            curve(FanCurvePU::CPU, true),
+            curve(FanCurvePU::GPU, false),
+            curve(FanCurvePU::MID, true),
+        ];
+        // A fan that was off stays off when the others were on
+        assert_eq!(fan_curves_enabled(&curves), vec![
+            (FanCurvePU::CPU, true),
+            (FanCurvePU::GPU, false),
+            (FanCurvePU::MID, true),
+        ]);
+        assert!(fan_curves_enabled(&[]).is_empty());
+    }
+}
@@ -6,6 +6,7 @@ use rog_anime::{ActionLoader, AnimTime, AnimeType, Fade, Sequences as AnimeSeque
 use rog_aura::effects::{AdvancedEffects as AuraSequences, Breathe, DoomFlicker, Effect, Static};
 use rog_aura::keyboard::LedCode;
 use rog_aura::{Colour, Speed};
+use rog_platform::platform::PlatformProfile;
 use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
@@ -203,6 +204,20 @@ impl StdConfig for ConfigAura {
 
 impl StdConfigLoad for ConfigAura {}
 
+/// Settings asked of `asusd` while an application is running. Those left as
+/// `None` are not changed.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AppProfile {
+    /// The executable name as in `/proc/<pid>/comm`, e.g `"steam"`
+    pub exe: String,
+    pub platform_profile: Option<PlatformProfile>,
+    /// Enable or disable the PPT tuning group
+    pub ppt_group: Option<bool>,
+    /// Enable or disable the fan curves of the platform profile in use
+    pub fan_curves: Option<bool>,
+}
+
 #[derive(Debug, Default, Deserialize, Serialize)]
 #[serde(default)]
 pub struct ConfigBase {
@@ -210,6 +225,8 @@ pub struct ConfigBase {
     pub active_anime: Option<String>,
     /// Name of active aura config file in the user config directory
     pub active_aura: Option<String>,
+    /// Checked in order, the first with a running executable is applied
+    pub app_profiles: Vec<AppProfile>,
 }
 
 impl StdConfig for ConfigBase {
@@ -217,6 +234,7 @@ impl StdConfig for ConfigBase {
         Self {
             active_anime: Some("anime-default".to_owned()),
             active_aura: Some("aura-default".to_owned()),
+            app_profiles: Vec::new(),
         }
     }
 
@@ -3,14 +3,18 @@ use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 
+use asusd_user::app_profiles::AppWatcher;
 use asusd_user::config::*;
 use asusd_user::ctrl_anime::{CtrlAnime, CtrlAnimeInner};
+use asusd_user::input::KeyPresses;
 use config_traits::{StdConfig, StdConfigLoad};
 use rog_anime::usb::get_anime_type;
 use rog_aura::aura_detection::LedSupportData;
 use rog_aura::keyboard::KeyLayout;
 use rog_dbus::zbus_anime::AnimeProxyBlocking;
 use rog_dbus::zbus_aura::AuraProxyBlocking;
+use rog_dbus::zbus_fan_curves::FanCurvesProxyBlocking;
+use rog_dbus::zbus_platform::PlatformProxyBlocking;
 use rog_dbus::{list_iface_blocking, DBUS_NAME};
 use smol::Executor;
 use zbus::Connection;
@@ -40,6 +44,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = ConfigBase::new().load();
     let executor = Executor::new();
 
+    // Watch for applications with their own profile
+    if !config.app_profiles.is_empty() && supported.contains(&"xyz.ljones.Platform".to_string()) {
+        let platform = PlatformProxyBlocking::new(&conn)?;
+        let fan_curves = if supported.contains(&"xyz.ljones.FanCurves".to_string()) {
+            Some(FanCurvesProxyBlocking::new(&conn)?)
+        } else {
+            None
+        };
+        let watcher = AppWatcher::new(config.app_profiles.clone(), platform, fan_curves);
+        std::thread::spawn(move || watcher.run());
+    }
+
     let early_return = Arc::new(AtomicBool::new(false));
     // Set up the anime data and run loop/thread
     if supported.contains(&"xyz.ljones.Anime".to_string()) {
@@ -97,10 +113,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             })
             .unwrap_or_else(|_| KeyLayout::default_layout());
 
+        // Only read the keyboard if an effect needs it
+        let key_presses = if aura_config.aura.is_reactive() {
+            KeyPresses::open()
+                .map_err(|e| println!("Reactive effects disabled: {e}"))
+                .ok()
+        } else {
+            None
+        };
+
         let aura_proxy_blocking = AuraProxyBlocking::new(&conn).unwrap();
         executor
             .spawn(async move {
                 loop {
+                    if let Some(key_presses) = &key_presses {
+                        for code in key_presses.pressed() {
+                            aura_config.aura.key_pressed(code);
+                        }
+                    }
                     aura_config.aura.next_state(&layout);
                     let packets = aura_config.aura.create_packets();
 
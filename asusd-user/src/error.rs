@@ -8,6 +8,7 @@ pub enum Error {
     ConfigLoadFail,
     ConfigLockFail,
     XdgVars,
+    NoKeyboardInput,
     Anime(AnimeError),
 }
 
@@ -19,6 +20,7 @@ impl fmt::Display for Error {
             Error::ConfigLoadFail => write!(f, "Failed to load user config"),
             Error::ConfigLockFail => write!(f, "Failed to lock user config"),
             Error::XdgVars => write!(f, "XDG environment vars appear unset"),
+            Error::NoKeyboardInput => write!(f, "No keyboard input device could be opened"),
             Error::Anime(err) => write!(f, "Anime error: {}", err),
         }
     }
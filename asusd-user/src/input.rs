@@ -0,0 +1,92 @@
+//! Key presses read from the keyboard evdev nodes, for the reactive Aura
+//! effects. The user must be able to read `/dev/input/event*`, typically by
+//! being in the `input` group.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use log::{info, warn};
+
+use crate::error::Error;
+
+/// udev links every keyboard event node here
+const INPUT_BY_PATH: &str = "/dev/input/by-path";
+const KBD_SUFFIX: &str = "-event-kbd";
+
+/// `struct input_event` is a `struct timeval` then type, code, value
+#[cfg(target_pointer_width = "64")]
+const TIMEVAL_LEN: usize = 16;
+#[cfg(not(target_pointer_width = "64"))]
+const TIMEVAL_LEN: usize = 8;
+const EVENT_LEN: usize = TIMEVAL_LEN + 8;
+
+const EV_KEY: u16 = 0x01;
+/// Key values are 0 for release, 1 for press and 2 for autorepeat
+const KEY_PRESS: i32 = 1;
+
+/// The Linux key code if the event is a key being pressed
+fn key_press(event: &[u8; EVENT_LEN]) -> Option<u16> {
+    let field = &event[TIMEVAL_LEN..];
+    let kind = u16::from_ne_bytes(field[0..2].try_into().ok()?);
+    let code = u16::from_ne_bytes(field[2..4].try_into().ok()?);
+    let value = i32::from_ne_bytes(field[4..8].try_into().ok()?);
+    (kind == EV_KEY && value == KEY_PRESS).then_some(code)
+}
+
+/// Every keyboard event node
+pub fn keyboard_devices() -> Result<Vec<PathBuf>, Error> {
+    let mut devices = Vec::new();
+    for entry in std::fs::read_dir(INPUT_BY_PATH)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(KBD_SUFFIX) {
+            devices.push(path);
+        }
+    }
+    Ok(devices)
+}
+
+fn read_keys(mut device: File, sender: Sender<u16>) {
+    let mut event = [0u8; EVENT_LEN];
+    while device.read_exact(&mut event).is_ok() {
+        if let Some(code) = key_press(&event) {
+            if sender.send(code).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Reads key presses from every keyboard on a thread each
+pub struct KeyPresses {
+    receiver: Receiver<u16>,
+}
+
+impl KeyPresses {
+    /// Start reading every keyboard. Fails if none could be opened.
+    pub fn open() -> Result<Self, Error> {
+        let (sender, receiver) = channel();
+        let mut opened = 0;
+        for path in keyboard_devices()? {
+            match File::open(&path) {
+                Ok(device) => {
+                    info!("Reading key presses from {path:?}");
+                    let sender = sender.clone();
+                    std::thread::spawn(move || read_keys(device, sender));
+                    opened += 1;
+                }
+                Err(e) => warn!("Could not open {path:?} for key presses: {e}"),
+            }
+        }
+        if opened == 0 {
+            return Err(Error::NoKeyboardInput);
+        }
+        Ok(Self { receiver })
+    }
+
+    /// The key codes of the keys pressed since the last call
+    pub fn pressed(&self) -> impl Iterator<Item = u16> + '_ {
+        self.receiver.try_iter()
+    }
+}
@@ -4,6 +4,10 @@ pub mod error;
 
 pub mod ctrl_anime;
 
+pub mod app_profiles;
+
+pub mod input;
+
 pub mod zbus_anime;
 
 pub static VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -3,6 +3,7 @@ use std::collections::BTreeMap;
 use config_traits::{StdConfig, StdConfigLoad};
 use log::{debug, info, warn};
 use rog_aura::aura_detection::LedSupportData;
+use rog_aura::effects::AdvancedEffects;
 use rog_aura::keyboard::LaptopAuraPower;
 use rog_aura::{
     AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, Direction, LedBrightness, Speed, GRADIENT,
@@ -30,6 +31,18 @@ pub struct AuraConfig {
     pub enabled: LaptopAuraPower,
     #[serde(skip)]
     pub per_key_mode_active: bool,
+    /// The software effect program run by asusd
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub effect: Option<AdvancedEffects>,
+    /// Restart the software effect on boot if set
+    #[serde(default)]
+    pub effect_running: bool,
+    #[serde(default = "default_effect_frame_rate")]
+    pub effect_frame_rate: u8,
+}
+
+fn default_effect_frame_rate() -> u8 {
+    30
 }
 
 impl StdConfig for AuraConfig {
@@ -75,6 +88,9 @@ impl AuraConfig {
             multizone_on: false,
             enabled,
             per_key_mode_active: false,
+            effect: None,
+            effect_running: false,
+            effect_frame_rate: default_effect_frame_rate(),
         };
 
         for n in &config.support_data.basic_modes {
@@ -0,0 +1,282 @@
+//! The software effect runner. This steps an `AdvancedEffects` program and
+//! writes each frame to the keyboard as per-key packets, so that animations
+//! keep running without a user session.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use config_traits::StdConfig;
+use log::{info, warn};
+use rog_aura::keyboard::{AuraLaptopUsbPackets, KeyLayout, LedUsbPackets};
+use rog_aura::{AuraDeviceType, LedBrightness};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+use super::config::AuraConfig;
+use super::Aura;
+use crate::error::RogError;
+
+/// Where the keyboard layouts are installed
+const LAYOUT_DATA_DIR: &str = "/usr/share/rog-gui/";
+pub const MAX_EFFECT_FRAME_RATE: u8 = 60;
+
+/// Why the effect is paused. Each is a bit of `EffectRunner::paused`, so the
+/// effect only resumes once all of them have cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Sleep = 1,
+    Shutdown = 2,
+    LidClosed = 4,
+}
+
+/// Tracks the task running the effect. The task exits when `generation` no
+/// longer matches the one it was started with.
+#[derive(Debug, Clone, Default)]
+pub struct EffectRunner {
+    generation: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
+    // The `PauseReason` bits set while the laptop is suspended, shutting down
+    // or the lid is closed
+    paused: Arc<AtomicU8>,
+    // The suspend and lid watchers, aborted when the device is removed
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl EffectRunner {
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst) != 0
+    }
+
+    /// Set or clear one reason to pause. Returns true if this cleared the last
+    /// reason, so the effect resumes.
+    fn set_paused(&self, reason: PauseReason, paused: bool) -> bool {
+        let bit = reason as u8;
+        if paused {
+            self.paused.fetch_or(bit, Ordering::SeqCst);
+            false
+        } else {
+            let last = self.paused.fetch_and(!bit, Ordering::SeqCst);
+            last & bit != 0 && last & !bit == 0
+        }
+    }
+
+    /// Keep the handles of the event tasks for this device, aborting any kept
+    /// before so that adding the device again doesn't duplicate them
+    pub fn set_tasks(&self, tasks: Vec<JoinHandle<()>>) {
+        if let Ok(mut lock) = self.tasks.lock() {
+            for task in lock.drain(..) {
+                task.abort();
+            }
+            *lock = tasks;
+        }
+    }
+
+    fn abort_tasks(&self) {
+        self.set_tasks(Vec::new());
+    }
+}
+
+/// A frame stepped from the effect program, to be written without holding the
+/// config lock
+struct EffectFrame {
+    led_type: AuraDeviceType,
+    packets: AuraLaptopUsbPackets,
+    // The per-key init must be sent first
+    init: bool,
+}
+
+impl Aura {
+    /// Step the loaded effect program once. Returns `None` if no program is
+    /// loaded.
+    fn next_effect_frame(config: &mut AuraConfig, layout: &KeyLayout) -> Option<EffectFrame> {
+        let effect = config.effect.as_mut()?;
+        effect.next_state(layout);
+        let packets = effect.create_packets();
+        if config.brightness == LedBrightness::Off {
+            config.brightness = LedBrightness::Med;
+            config.write();
+        }
+        let init = !config.per_key_mode_active;
+        config.per_key_mode_active = true;
+        Some(EffectFrame {
+            led_type: config.led_type,
+            packets,
+            init,
+        })
+    }
+
+    /// Write a frame for the effect task started as `generation`. The device
+    /// is locked before the generation is checked so that a frame can't land
+    /// after a builtin mode written once the effect was stopped. Returns false
+    /// if the task is stale and must exit.
+    async fn write_effect_frame(
+        &self,
+        frame: &EffectFrame,
+        generation: u64,
+    ) -> Result<bool, RogError> {
+        let is_current = || self.effect_runner.generation.load(Ordering::SeqCst) == generation;
+        if let Some(hid_raw) = &self.hid {
+            let hid_raw = hid_raw.lock().await;
+            if !is_current() {
+                return Ok(false);
+            }
+            if frame.init {
+                hid_raw.write_bytes(&LedUsbPackets::get_init_msg())?;
+            }
+            for row in frame.packets.iter() {
+                hid_raw.write_bytes(row)?;
+            }
+        } else if matches!(frame.led_type, AuraDeviceType::LaptopKeyboardTuf) {
+            if let Some(tuf) = &self.backlight {
+                let tuf = tuf.lock().await;
+                if !is_current() {
+                    return Ok(false);
+                }
+                for row in frame.packets.iter() {
+                    tuf.set_kbd_rgb_mode(&[
+                        0, 0, row[9], row[10], row[11], 0,
+                    ])?;
+                }
+            }
+        }
+        Ok(is_current())
+    }
+
+    /// Start, or restart, the effect task at the frame rate set in config.
+    /// Any previous task will exit before writing another frame.
+    pub async fn start_effect(&self) -> Result<(), RogError> {
+        let (frame_rate, support_data) = {
+            let config = self.config.lock().await;
+            if config.effect.is_none() {
+                return Err(RogError::MissingFunction(
+                    "No software effect is loaded".to_string(),
+                ));
+            }
+            (
+                config.effect_frame_rate.clamp(1, MAX_EFFECT_FRAME_RATE),
+                config.support_data.clone(),
+            )
+        };
+        let layout = KeyLayout::find_layout(support_data, PathBuf::from(LAYOUT_DATA_DIR))
+            .map_err(|e| warn!("Software effect could not load a layout, using default: {e}"))
+            .unwrap_or_else(|_| KeyLayout::default_layout());
+
+        let runner = self.effect_runner.clone();
+        let generation = runner.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        runner.running.store(true, Ordering::SeqCst);
+        let inner = self.clone();
+
+        tokio::spawn(async move {
+            info!("Software effect started at {frame_rate} FPS");
+            let mut timer = tokio::time::interval(Duration::from_secs(1) / u32::from(frame_rate));
+            timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                timer.tick().await;
+                if runner.is_paused() {
+                    continue;
+                }
+                let frame = {
+                    let mut config = inner.config.lock().await;
+                    if runner.generation.load(Ordering::SeqCst) != generation {
+                        break;
+                    }
+                    Self::next_effect_frame(&mut config, &layout)
+                };
+                let Some(frame) = frame else {
+                    continue;
+                };
+                match inner.write_effect_frame(&frame, generation).await {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(e) => {
+                        warn!("Software effect stopped: {e}");
+                        runner.running.store(false, Ordering::SeqCst);
+                        break;
+                    }
+                }
+            }
+            info!("Software effect task exited");
+        });
+        Ok(())
+    }
+
+    /// Stop the effect task. The task will exit on its next frame.
+    pub fn stop_effect(&self) {
+        self.effect_runner.generation.fetch_add(1, Ordering::SeqCst);
+        self.effect_runner.running.store(false, Ordering::SeqCst);
+    }
+
+    /// Stop the effect and abort the event tasks, for when the device is
+    /// removed
+    pub fn stop_tasks(&self) {
+        self.stop_effect();
+        self.effect_runner.abort_tasks();
+    }
+
+    /// Pause or resume writing frames, for suspend, shutdown and lid close.
+    /// Frames are written again once every reason has cleared, and the
+    /// per-key init is sent again as the keyboard may have been reset.
+    pub async fn pause_effect(&self, reason: PauseReason, paused: bool) {
+        let mut config = self.config.lock().await;
+        if self.effect_runner.set_paused(reason, paused) {
+            config.per_key_mode_active = false;
+        }
+    }
+
+    /// Start the effect if it was running when asusd last stopped
+    pub async fn restore_effect(&self) -> Result<(), RogError> {
+        let running = {
+            let config = self.config.lock().await;
+            config.effect_running && config.effect.is_some()
+        };
+        if running {
+            self.start_effect().await?;
+        }
+        Ok(())
+    }
+
+    /// Stop the effect and write the builtin mode back to the keyboard
+    pub async fn stop_effect_and_restore(&self) -> Result<(), RogError> {
+        self.stop_effect();
+        let mut config = self.config.lock().await;
+        config.effect_running = false;
+        config.per_key_mode_active = false;
+        config.write();
+        self.write_current_config_mode(&mut config).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EffectRunner, PauseReason};
+
+    #[test]
+    fn effect_pause_reasons() {
+        let runner = EffectRunner::default();
+        assert!(!runner.is_paused());
+
+        // Resuming from suspend with the lid still closed stays paused
+        assert!(!runner.set_paused(PauseReason::LidClosed, true));
+        assert!(!runner.set_paused(PauseReason::Sleep, true));
+        assert!(!runner.set_paused(PauseReason::Sleep, false));
+        assert!(runner.is_paused());
+        assert!(runner.set_paused(PauseReason::LidClosed, false));
+        assert!(!runner.is_paused());
+
+        // Opening the lid while shutting down stays paused
+        runner.set_paused(PauseReason::Shutdown, true);
+        runner.set_paused(PauseReason::LidClosed, true);
+        assert!(!runner.set_paused(PauseReason::LidClosed, false));
+        assert!(runner.is_paused());
+
+        // Clearing a reason that wasn't set doesn't resume
+        runner.set_paused(PauseReason::Shutdown, false);
+        assert!(!runner.set_paused(PauseReason::Sleep, false));
+    }
+}
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use config::AuraConfig;
 use config_traits::StdConfig;
+use effects::EffectRunner;
 use futures_util::lock::{Mutex, MutexGuard};
 use log::info;
 use rog_aura::keyboard::{AuraLaptopUsbPackets, LedUsbPackets};
@@ -13,6 +14,7 @@ use rog_platform::keyboard_led::KeyboardBacklight;
 use crate::error::RogError;
 
 pub mod config;
+pub mod effects;
 pub mod trait_impls;
 
 #[derive(Debug, Clone)]
@@ -20,6 +22,7 @@ pub struct Aura {
     pub hid: Option<Arc<Mutex<HidRaw>>>,
     pub backlight: Option<Arc<Mutex<KeyboardBacklight>>>,
     pub config: Arc<Mutex<AuraConfig>>,
+    pub effect_runner: EffectRunner,
 }
 
 impl Aura {
@@ -227,3 +230,162 @@ impl Aura {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures_util::lock::Mutex;
+    use rog_aura::effects::{AdvancedEffects, Effect, Static};
+    use rog_aura::keyboard::{AuraPowerState, LaptopAuraPower, LedCode, LedUsbPackets};
+    use rog_aura::usb::AuraLaptopReport;
+    use rog_aura::{
+        AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, Colour, Direction, PowerZones, Speed,
+    };
+    use rog_platform::hid_raw::{HidRaw, VirtualHidRaw};
+
+    use super::config::AuraConfig;
+    use super::Aura;
+
+    fn virtual_aura(prod_id: &str) -> (Aura, VirtualHidRaw) {
+        let endpoint = VirtualHidRaw::new();
+        let aura = Aura {
+            hid: Some(Arc::new(Mutex::new(HidRaw::new_virtual(
+                prod_id, &endpoint,
+            )))),
+            backlight: None,
+            config: Arc::new(Mutex::new(AuraConfig::new(prod_id))),
+            effect_runner: Default::default(),
+        };
+        (aura, endpoint)
+    }
+
+    fn decode(aura_type: AuraDeviceType, endpoint: &VirtualHidRaw) -> Vec<AuraLaptopReport> {
+        endpoint
+            .take_reports()
+            .iter()
+            .map(|r| AuraLaptopReport::decode(aura_type, r).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn effect_on_the_wire() {
+        let (aura, endpoint) = virtual_aura("19b6");
+        let effect = AuraEffect {
+            mode: AuraModeNum::Breathe,
+            zone: AuraZone::Key2,
+            colour1: Colour {
+                r: 0xff,
+                g: 0x11,
+                b: 0xdd,
+            },
+            colour2: Colour {
+                r: 0x01,
+                g: 0x02,
+                b: 0x03,
+            },
+            speed: Speed::High,
+            direction: Direction::Up,
+        };
+        aura.write_effect_and_apply(AuraDeviceType::LaptopKeyboard2021, &effect)
+            .await
+            .unwrap();
+        assert_eq!(decode(AuraDeviceType::LaptopKeyboard2021, &endpoint), vec![
+            AuraLaptopReport::Effect(effect),
+            AuraLaptopReport::Set,
+            AuraLaptopReport::Apply
+        ]);
+    }
+
+    #[tokio::test]
+    async fn power_on_the_wire() {
+        let (aura, endpoint) = virtual_aura("19b6");
+        let mut config = aura.lock_config().await.clone();
+        config.led_type = AuraDeviceType::LaptopKeyboard2021;
+        config.enabled = LaptopAuraPower {
+            states: vec![
+                AuraPowerState {
+                    zone: PowerZones::Keyboard,
+                    boot: true,
+                    awake: true,
+                    sleep: false,
+                    shutdown: false,
+                },
+                AuraPowerState {
+                    zone: PowerZones::Lightbar,
+                    boot: false,
+                    awake: true,
+                    sleep: true,
+                    shutdown: true,
+                },
+            ],
+        };
+        aura.set_power_states(&config).await.unwrap();
+
+        let reports = decode(AuraDeviceType::LaptopKeyboard2021, &endpoint);
+        assert_eq!(reports.len(), 1);
+        let AuraLaptopReport::Power(power) = &reports[0] else {
+            panic!("Expected a power report, got {reports:?}");
+        };
+        for state in &config.enabled.states {
+            assert!(power.states.contains(state));
+        }
+    }
+
+    #[tokio::test]
+    async fn ally_power_on_the_wire() {
+        let (aura, endpoint) = virtual_aura("1abe");
+        let mut config = aura.lock_config().await.clone();
+        let state = AuraPowerState {
+            zone: PowerZones::Ally,
+            boot: false,
+            awake: true,
+            sleep: false,
+            shutdown: true,
+        };
+        config.enabled = LaptopAuraPower {
+            states: vec![state],
+        };
+        aura.set_power_states(&config).await.unwrap();
+
+        assert_eq!(decode(AuraDeviceType::Ally, &endpoint), vec![
+            AuraLaptopReport::Power(config.enabled)
+        ]);
+    }
+
+    #[tokio::test]
+    async fn effect_frames_on_the_wire() {
+        let (aura, endpoint) = virtual_aura("19b6");
+        let colour = Colour {
+            r: 0xff,
+            g: 0x7f,
+            b: 0x00,
+        };
+        let mut effect = AdvancedEffects::new(false);
+        effect.push(Effect::Static(Static::new(LedCode::F, colour)));
+
+        // Nothing runs until a program is loaded
+        assert!(aura.start_effect().await.is_err());
+        aura.lock_config().await.effect = Some(effect);
+        aura.start_effect().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        aura.stop_effect();
+        // Let a frame that was being written finish
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut reports = endpoint.take_reports();
+        // Only the first frame needs the per-key init
+        assert_eq!(reports.remove(0), LedUsbPackets::get_init_msg());
+        let rows = LedUsbPackets::new_per_key().get().len();
+        assert!(reports.len() >= rows && reports.len() % rows == 0);
+        for frame in reports.chunks(rows) {
+            let leds = LedUsbPackets::from_packets(frame).unwrap();
+            assert_eq!(leds.get_colour(LedCode::F), Some(colour));
+        }
+
+        // A stopped effect writes no more frames
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(endpoint.take_reports().is_empty());
+    }
+}
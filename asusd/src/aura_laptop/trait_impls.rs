@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 
 use config_traits::StdConfig;
 use log::{debug, error, info, warn};
+use rog_aura::effects::AdvancedEffects;
 use rog_aura::keyboard::{AuraLaptopUsbPackets, LaptopAuraPower};
 use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, LedBrightness, PowerZones};
 use zbus::fdo::Error as ZbErr;
@@ -9,6 +10,7 @@ use zbus::object_server::SignalEmitter;
 use zbus::zvariant::OwnedObjectPath;
 use zbus::{interface, Connection};
 
+use super::effects::{PauseReason, MAX_EFFECT_FRAME_RATE};
 use super::Aura;
 use crate::error::RogError;
 use crate::{CtrlTask, Reloadable};
@@ -24,13 +26,44 @@ impl AuraZbus {
         Self(aura)
     }
 
+    /// Pause the software effect while suspended, shutting down, or the lid is
+    /// closed. The tasks are kept by the effect runner and aborted when the
+    /// device is removed.
+    async fn create_effect_tasks(&self) {
+        let inner1 = self.0.clone();
+        let inner2 = self.0.clone();
+        let inner3 = self.0.clone();
+        let tasks = self
+            .create_sys_event_tasks(
+                move |sleeping| {
+                    let inner = inner1.clone();
+                    async move { inner.pause_effect(PauseReason::Sleep, sleeping).await }
+                },
+                move |shutting_down| {
+                    let inner = inner2.clone();
+                    async move {
+                        inner
+                            .pause_effect(PauseReason::Shutdown, shutting_down)
+                            .await
+                    }
+                },
+                move |lid_closed| {
+                    let inner = inner3.clone();
+                    async move { inner.pause_effect(PauseReason::LidClosed, lid_closed).await }
+                },
+                move |_power_plugged| async move {},
+            )
+            .await;
+        self.0.effect_runner.set_tasks(tasks);
+    }
+
     pub async fn start_tasks(
         mut self,
         connection: &Connection,
         // _signal_ctx: SignalEmitter<'static>,
         path: OwnedObjectPath,
     ) -> Result<(), RogError> {
-        // let task = zbus.clone();
+        let task = self.clone();
         // let signal_ctx = signal_ctx.clone();
         self.reload()
             .await
@@ -41,6 +74,7 @@ impl AuraZbus {
             .await
             .map_err(|e| error!("Couldn't add server at path: {path}, {e:?}"))
             .ok();
+        task.create_effect_tasks().await;
         // TODO: skip this until we keep handles to tasks so they can be killed
         // task.create_tasks(signal_ctx).await
         Ok(())
@@ -125,7 +159,9 @@ impl AuraZbus {
     /// the effect is stored and config written to disk.
     #[zbus(property)]
     async fn set_led_mode(&mut self, num: AuraModeNum) -> Result<(), ZbErr> {
+        self.0.stop_effect();
         let mut config = self.0.config.lock().await;
+        config.effect_running = false;
         config.current_mode = num;
         self.0.write_current_config_mode(&mut config).await?;
         if config.brightness == LedBrightness::Off {
@@ -167,6 +203,8 @@ impl AuraZbus {
             )));
         }
 
+        self.0.stop_effect();
+        config.effect_running = false;
         self.0
             .write_effect_and_apply(config.led_type, &effect)
             .await?;
@@ -218,11 +256,80 @@ impl AuraZbus {
     /// On machine that have some form of either per-key keyboard or per-zone
     /// this can be used to write custom effects over dbus. The input is a
     /// nested `Vec<Vec<8>>` where `Vec<u8>` is a raw USB packet
+    ///
+    /// This stops the software effect until it is started again.
     async fn direct_addressing_raw(&self, data: AuraLaptopUsbPackets) -> Result<(), ZbErr> {
+        self.0.stop_effect();
         let mut config = self.0.config.lock().await;
         self.0.write_effect_block(&mut config, &data).await?;
         Ok(())
     }
+
+    /// Load a software effect program, an `AdvancedEffects` in RON format. If
+    /// an effect is running it switches to the new program.
+    async fn effect_load(&self, program: &str) -> Result<(), ZbErr> {
+        let effect: AdvancedEffects = ron::from_str(program)
+            .map_err(|e| ZbErr::InvalidArgs(format!("Invalid effect program: {e}")))?;
+        let mut config = self.0.config.lock().await;
+        config.effect = Some(effect);
+        config.write();
+        Ok(())
+    }
+
+    /// Start running the loaded software effect. It is restarted on boot until
+    /// stopped.
+    async fn effect_start(
+        &self,
+        #[zbus(signal_emitter)] ctxt: SignalEmitter<'_>,
+    ) -> Result<(), ZbErr> {
+        self.0.start_effect().await?;
+        let mut config = self.0.config.lock().await;
+        config.effect_running = true;
+        config.write();
+        drop(config);
+        self.effect_running_changed(&ctxt).await?;
+        Ok(())
+    }
+
+    /// Stop the software effect and restore the builtin mode
+    async fn effect_stop(
+        &self,
+        #[zbus(signal_emitter)] ctxt: SignalEmitter<'_>,
+    ) -> Result<(), ZbErr> {
+        self.0.stop_effect_and_restore().await?;
+        self.effect_running_changed(&ctxt).await?;
+        Ok(())
+    }
+
+    /// If the software effect is running. It does not write frames while the
+    /// laptop is suspended or the lid is closed.
+    #[zbus(property)]
+    async fn effect_running(&self) -> bool {
+        self.0.effect_runner.is_running()
+    }
+
+    /// Frames per second written by the software effect
+    #[zbus(property)]
+    async fn effect_frame_rate(&self) -> u8 {
+        self.0.config.lock().await.effect_frame_rate
+    }
+
+    #[zbus(property)]
+    async fn set_effect_frame_rate(&mut self, frame_rate: u8) -> Result<(), ZbErr> {
+        if !(1..=MAX_EFFECT_FRAME_RATE).contains(&frame_rate) {
+            return Err(ZbErr::InvalidArgs(format!(
+                "Frame rate must be 1 to {MAX_EFFECT_FRAME_RATE}"
+            )));
+        }
+        let mut config = self.0.config.lock().await;
+        config.effect_frame_rate = frame_rate;
+        config.write();
+        drop(config);
+        if self.0.effect_runner.is_running() {
+            self.0.start_effect().await?;
+        }
+        Ok(())
+    }
 }
 
 impl CtrlTask for AuraZbus {
@@ -338,6 +445,90 @@ impl Reloadable for AuraZbus {
             .await
             .map_err(|err| warn!("{err}"))
             .ok();
+        drop(config);
+        self.0
+            .restore_effect()
+            .await
+            .map_err(|err| warn!("{err}"))
+            .ok();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string, write};
+    use std::sync::Arc;
+
+    use futures_util::lock::Mutex;
+    use rog_aura::usb::AuraLaptopReport;
+    use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, Colour, Speed};
+    use rog_platform::hid_raw::{HidRaw, VirtualHidRaw};
+    use rog_platform::keyboard_led::KeyboardBacklight;
+
+    use super::super::config::AuraConfig;
+    use super::super::Aura;
+    use super::AuraZbus;
+
+    #[tokio::test]
+    async fn set_led_mode_data_on_the_wire() {
+        let leds = crate::test_sysfs_root().join("class/leds/asus::kbd_backlight");
+        create_dir_all(&leds).unwrap();
+        write(leds.join("brightness"), "0\n").unwrap();
+
+        let endpoint = VirtualHidRaw::new();
+        let mut config = AuraConfig::new("19b6");
+        config.led_type = AuraDeviceType::LaptopKeyboard2021;
+        config.support_data.basic_modes = vec![
+            AuraModeNum::Static,
+            AuraModeNum::Breathe,
+        ];
+        config.support_data.basic_zones = vec![];
+        let aura = Aura {
+            hid: Some(Arc::new(Mutex::new(HidRaw::new_virtual("19b6", &endpoint)))),
+            backlight: Some(Arc::new(Mutex::new(KeyboardBacklight::new().unwrap()))),
+            config: Arc::new(Mutex::new(config)),
+            effect_runner: Default::default(),
+        };
+        let mut zbus = AuraZbus::new(aura);
+
+        let effect = AuraEffect {
+            mode: AuraModeNum::Breathe,
+            colour1: Colour {
+                r: 0x12,
+                g: 0x34,
+                b: 0x56,
+            },
+            speed: Speed::Low,
+            ..Default::default()
+        };
+        zbus.set_led_mode_data(effect.clone()).await.unwrap();
+        let reports: Vec<AuraLaptopReport> = endpoint
+            .take_reports()
+            .iter()
+            .map(|r| AuraLaptopReport::decode(AuraDeviceType::LaptopKeyboard2021, r).unwrap())
+            .collect();
+        assert_eq!(reports, vec![
+            AuraLaptopReport::Effect(effect.clone()),
+            AuraLaptopReport::Set,
+            AuraLaptopReport::Apply
+        ]);
+        assert_eq!(zbus.led_mode_data().await.unwrap(), effect);
+        assert_eq!(read_to_string(leds.join("brightness")).unwrap(), "2");
+
+        // Modes and zones the keyboard doesn't have are refused unwritten
+        for effect in [
+            AuraEffect {
+                mode: AuraModeNum::Rain,
+                ..Default::default()
+            },
+            AuraEffect {
+                zone: AuraZone::Key1,
+                ..Default::default()
+            },
+        ] {
+            assert!(zbus.set_led_mode_data(effect).await.is_err());
+        }
+        assert!(endpoint.take_reports().is_empty());
+    }
+}
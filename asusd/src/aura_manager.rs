@@ -6,13 +6,13 @@
 
 use std::sync::Arc;
 
-use dmi_id::DMIID;
 use futures_lite::future::block_on;
 use futures_util::lock::Mutex;
 use log::{debug, error, info, warn};
 use mio::{Events, Interest, Poll, Token};
 use rog_platform::error::PlatformError;
 use rog_platform::hid_raw::HidRaw;
+use rog_platform::sysfs::SysfsRoot;
 use udev::{Device, MonitorBuilder};
 use zbus::zvariant::{ObjectPath, OwnedObjectPath};
 use zbus::Connection;
@@ -340,8 +340,9 @@ impl DeviceManager {
             // TUF AURA LAPTOP DEVICE
             // product_name = ASUS TUF Gaming F15 FX507ZE_FX507ZE
             // product_family = ASUS TUF Gaming F15
-            let product_name = DMIID::new().unwrap_or_default().product_name;
-            let product_family = DMIID::new().unwrap_or_default().product_family;
+            let dmi = SysfsRoot::get().dmi_id().unwrap_or_default();
+            let product_name = dmi.product_name;
+            let product_family = dmi.product_family;
             info!(
                 "No USB keyboard aura, system is {product_name}, try using sysfs backlight control"
             );
@@ -485,7 +486,8 @@ impl DeviceManager {
                                             let dev = devices.lock().await.remove(*index);
                                             let path = path.clone();
                                             let res = match dev.device {
-                                                DeviceHandle::Aura(_) => {
+                                                DeviceHandle::Aura(aura) => {
+                                                    aura.stop_tasks();
                                                     conn_copy
                                                         .object_server()
                                                         .remove::<AuraZbus, _>(&path)
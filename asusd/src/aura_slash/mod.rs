@@ -67,3 +67,54 @@ impl Slash {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures_util::lock::Mutex;
+    use rog_platform::hid_raw::{HidRaw, VirtualHidRaw};
+    use rog_slash::usb::SlashReport;
+    use rog_slash::{SlashMode, SlashType};
+
+    use super::config::SlashConfig;
+    use super::Slash;
+
+    #[tokio::test]
+    async fn initialization_on_the_wire() {
+        let endpoint = VirtualHidRaw::new();
+        let config = SlashConfig {
+            slash_type: SlashType::GA403,
+            brightness: 136,
+            display_interval: 2,
+            display_mode: SlashMode::Spectrum,
+            ..Default::default()
+        };
+        let slash = Slash::new(
+            Some(Arc::new(Mutex::new(HidRaw::new_virtual(
+                SlashType::GA403.prod_id_str(),
+                &endpoint,
+            )))),
+            None,
+            Arc::new(Mutex::new(config)),
+        );
+        slash.do_initialization().await.unwrap();
+
+        let reports: Vec<SlashReport> = endpoint
+            .take_reports()
+            .iter()
+            .map(|r| SlashReport::decode(SlashType::GA403, r).unwrap())
+            .collect();
+        assert_eq!(reports, vec![
+            SlashReport::Init1,
+            SlashReport::Init2,
+            SlashReport::Enable(true),
+            SlashReport::Options {
+                enabled: true,
+                brightness: 136,
+                interval: 2
+            },
+            SlashReport::Mode(SlashMode::Spectrum),
+        ]);
+    }
+}
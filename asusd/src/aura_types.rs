@@ -202,6 +202,7 @@ impl DeviceHandle {
             hid: device,
             backlight,
             config: Arc::new(Mutex::new(config)),
+            effect_runner: Default::default(),
         };
         aura.do_initialization().await?;
         Ok(Self::Aura(aura))
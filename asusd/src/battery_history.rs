@@ -0,0 +1,233 @@
+//! A fixed size history of battery samples, kept in a ring-buffer file so the
+//! file never grows and each new sample is a single small write. The file is a
+//! header followed by `capacity` records:
+//!
+//! - header: `RBH1`, then capacity, next slot and length as `u32` LE
+//! - record: time `u64`, capacity, charge limit, 2 spare bytes, cycle count
+//!   `u32`, then health, rate, voltage and temperature as `f32`, all LE
+
+use std::collections::VecDeque;
+use std::fs::{read, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use log::warn;
+use rog_platform::power::BatterySample;
+
+use crate::error::RogError;
+
+const MAGIC: &[u8; 4] = b"RBH1";
+const HEADER_LEN: usize = 16;
+const RECORD_LEN: usize = 32;
+
+fn encode(sample: &BatterySample) -> [u8; RECORD_LEN] {
+    let mut record = [0; RECORD_LEN];
+    record[0..8].copy_from_slice(&sample.time.to_le_bytes());
+    record[8] = sample.capacity;
+    record[9] = sample.charge_limit;
+    record[12..16].copy_from_slice(&sample.cycle_count.to_le_bytes());
+    for (i, value) in [
+        sample.health, sample.rate, sample.voltage, sample.temp,
+    ]
+    .iter()
+    .enumerate()
+    {
+        let start = 16 + i * 4;
+        record[start..start + 4].copy_from_slice(&(*value as f32).to_le_bytes());
+    }
+    record
+}
+
+fn decode(record: &[u8]) -> BatterySample {
+    let u32_at = |i: usize| {
+        u32::from_le_bytes([
+            record[i],
+            record[i + 1],
+            record[i + 2],
+            record[i + 3],
+        ])
+    };
+    let f64_at = |i: usize| f32::from_bits(u32_at(i)) as f64;
+    let mut time = [0; 8];
+    time.copy_from_slice(&record[0..8]);
+    BatterySample {
+        time: u64::from_le_bytes(time),
+        capacity: record[8],
+        charge_limit: record[9],
+        cycle_count: u32_at(12),
+        health: f64_at(16),
+        rate: f64_at(20),
+        voltage: f64_at(24),
+        temp: f64_at(28),
+    }
+}
+
+#[derive(Debug)]
+pub struct BatteryHistory {
+    path: PathBuf,
+    capacity: usize,
+    /// The slot the next sample is written to
+    next: usize,
+    /// Oldest first
+    samples: VecDeque<BatterySample>,
+    /// The file is missing or unreadable and is rewritten on the next push
+    reset: bool,
+}
+
+impl BatteryHistory {
+    /// Load the history at `path`. A missing file, or one of another capacity
+    /// or format, starts an empty history.
+    pub fn load(path: PathBuf, capacity: usize) -> Self {
+        let mut history = Self {
+            path,
+            capacity: capacity.max(1),
+            next: 0,
+            samples: VecDeque::new(),
+            reset: true,
+        };
+        let Ok(data) = read(&history.path) else {
+            return history;
+        };
+        let header = |i: usize| {
+            data.get(i..i + 4).map(|b| {
+                u32::from_le_bytes([
+                    b[0], b[1], b[2], b[3],
+                ]) as usize
+            })
+        };
+        let (Some(file_capacity), Some(next), Some(len)) = (header(4), header(8), header(12))
+        else {
+            warn!(
+                "Battery history {:?} is too short, starting again",
+                history.path
+            );
+            return history;
+        };
+        if !data.starts_with(MAGIC)
+            || file_capacity != history.capacity
+            || next >= file_capacity
+            || len > file_capacity
+            || data.len() < HEADER_LEN + len * RECORD_LEN
+        {
+            warn!(
+                "Battery history {:?} is not valid, starting again",
+                history.path
+            );
+            return history;
+        }
+        let start = (next + file_capacity - len) % file_capacity;
+        for i in 0..len {
+            let offset = HEADER_LEN + ((start + i) % file_capacity) * RECORD_LEN;
+            if let Some(record) = data.get(offset..offset + RECORD_LEN) {
+                history.samples.push_back(decode(record));
+            }
+        }
+        history.next = next;
+        history.reset = false;
+        history
+    }
+
+    /// Oldest first
+    pub fn samples(&self) -> &VecDeque<BatterySample> {
+        &self.samples
+    }
+
+    /// Add a sample, writing over the oldest once full
+    pub fn push(&mut self, sample: BatterySample) -> Result<(), RogError> {
+        let record = encode(&sample);
+        self.samples.push_back(sample);
+        if self.samples.len() > self.capacity {
+            self.samples.pop_front();
+        }
+        let slot = self.next;
+        self.next = (self.next + 1) % self.capacity;
+
+        let path = self.path.to_string_lossy().to_string();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(self.reset)
+            .write(true)
+            .open(&self.path)
+            .map_err(|e| RogError::Write(path.clone(), e))?;
+        if self.reset {
+            // Everything in memory goes in the slots from 0
+            let mut data = Vec::with_capacity(self.samples.len() * RECORD_LEN);
+            for sample in &self.samples {
+                data.extend_from_slice(&encode(sample));
+            }
+            self.next = self.samples.len() % self.capacity;
+            file.seek(SeekFrom::Start(HEADER_LEN as u64))
+                .and_then(|_| file.write_all(&data))
+                .map_err(|e| RogError::Write(path.clone(), e))?;
+            self.reset = false;
+        } else {
+            file.seek(SeekFrom::Start((HEADER_LEN + slot * RECORD_LEN) as u64))
+                .and_then(|_| file.write_all(&record))
+                .map_err(|e| RogError::Write(path.clone(), e))?;
+        }
+
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4..8].copy_from_slice(&(self.capacity as u32).to_le_bytes());
+        header[8..12].copy_from_slice(&(self.next as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&(self.samples.len() as u32).to_le_bytes());
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.write_all(&header))
+            .map_err(|e| RogError::Write(path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_file;
+
+    use super::*;
+
+    fn sample(time: u64) -> BatterySample {
+        BatterySample {
+            time,
+            capacity: 80,
+            charge_limit: 80,
+            cycle_count: 100,
+            health: 92.5,
+            rate: -11.25,
+            voltage: 16.5,
+            temp: 31.5,
+        }
+    }
+
+    #[test]
+    fn battery_history_ring() {
+        let path = std::env::temp_dir().join(format!("rog-battery-history-{}", std::process::id()));
+        remove_file(&path).ok();
+
+        let mut history = BatteryHistory::load(path.clone(), 4);
+        assert!(history.samples().is_empty());
+        for time in 0..6 {
+            history.push(sample(time)).unwrap();
+        }
+        let times = |h: &BatteryHistory| h.samples().iter().map(|s| s.time).collect::<Vec<_>>();
+        assert_eq!(times(&history), vec![2, 3, 4, 5]);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len() as usize,
+            HEADER_LEN + 4 * RECORD_LEN
+        );
+
+        // Reloading keeps the order and values, and carries on from the slot
+        let mut history = BatteryHistory::load(path.clone(), 4);
+        assert_eq!(times(&history), vec![2, 3, 4, 5]);
+        assert_eq!(history.samples()[0], sample(2));
+        history.push(sample(6)).unwrap();
+        assert_eq!(times(&BatteryHistory::load(path.clone(), 4)), vec![
+            3, 4, 5, 6
+        ]);
+
+        // Another capacity starts again
+        let mut history = BatteryHistory::load(path.clone(), 8);
+        assert!(history.samples().is_empty());
+        history.push(sample(7)).unwrap();
+        assert_eq!(times(&BatteryHistory::load(path.clone(), 8)), vec![7]);
+
+        remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,548 @@
+//! Raise the charge limit ahead of planned use, e.g hold at 60% but be at
+//! 100% by 07:30 on weekdays, or before a calendar event marked as travel.
+//! Outside of those times the configured charge limit applies.
+//!
+//! The times the laptop is usually unplugged are also learned, and the limit
+//! raised before them, unless `adaptive` is switched off.
+
+use std::fmt::Display;
+
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::error::RogError;
+
+/// Unplugs older than this are forgotten
+const UNPLUG_WEEKS: i64 = 8;
+/// Unplugs on a day of the week needed before it has a learned time
+const MIN_UNPLUGS: usize = 3;
+/// How far from the usual time an unplug may be to count towards it
+const UNPLUG_SPREAD_MINUTES: i64 = 45;
+/// Shorter times on AC are not taken as charging before use
+pub const MIN_PLUGGED_MINUTES: i64 = 60;
+
+fn parse_err(what: &str, value: &str) -> RogError {
+    RogError::DoTask(format!("Charge schedule: invalid {what} {value:?}"))
+}
+
+/// Days of the week as a bit mask from Monday, parsed from `mon-fri`,
+/// `sat,sun`, `daily` and the like
+fn parse_days(days: &str) -> Result<u8, RogError> {
+    const NAMES: [&str; 7] = [
+        "mon", "tue", "wed", "thu", "fri", "sat", "sun",
+    ];
+    let day = |name: &str| {
+        NAMES
+            .iter()
+            .position(|d| name.trim().to_ascii_lowercase().starts_with(d))
+            .ok_or_else(|| parse_err("day", name))
+    };
+    let mut mask = 0;
+    for part in days.split(',') {
+        match part.trim().to_ascii_lowercase().as_str() {
+            "daily" => mask |= 0x7f,
+            "weekdays" => mask |= 0x1f,
+            "weekends" => mask |= 0x60,
+            part => {
+                let (first, last) = part.split_once('-').unwrap_or((part, part));
+                let (first, last) = (day(first)?, day(last)?);
+                let mut d = first;
+                loop {
+                    mask |= 1 << d;
+                    if d == last {
+                        break;
+                    }
+                    d = (d + 1) % 7;
+                }
+            }
+        }
+    }
+    Ok(mask)
+}
+
+/// A limit to reach by a time on some days of every week
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ChargeTarget {
+    /// `mon-fri`, `sat,sun`, `weekdays`, `weekends` or `daily`
+    pub days: String,
+    /// Local time as `HH:MM`
+    pub time: String,
+    /// Percent, 20-100
+    pub limit: u8,
+}
+
+impl ChargeTarget {
+    fn time(&self) -> Result<NaiveTime, RogError> {
+        NaiveTime::parse_from_str(self.time.trim(), "%H:%M")
+            .map_err(|_| parse_err("time", &self.time))
+    }
+
+    pub fn check(&self) -> Result<(), RogError> {
+        parse_days(&self.days)?;
+        self.time()?;
+        if !(20..=100).contains(&self.limit) {
+            return Err(RogError::ChargeLimit(self.limit));
+        }
+        Ok(())
+    }
+}
+
+/// An event from the calendar file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+/// A `DTSTART`/`DTEND` value as local time, and whether it is a whole day
+fn parse_ical_time(value: &str) -> Option<(NaiveDateTime, bool)> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return date.and_hms_opt(0, 0, 0).map(|t| (t, true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((
+            Utc.from_utc_datetime(&time)
+                .with_timezone(&Local)
+                .naive_local(),
+            false,
+        ));
+    }
+    // A TZID is taken to be the local zone
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|t| (t, false))
+}
+
+/// The summary, start and whether it is a whole day, and end of an event
+/// being read
+type PartialEvent = (String, Option<(NaiveDateTime, bool)>, Option<NaiveDateTime>);
+
+/// Read the `VEVENT`s of an iCalendar file. Recurring events are only read
+/// for their first occurrence.
+pub fn parse_calendar(text: &str) -> Vec<CalendarEvent> {
+    // Long lines are folded on to lines starting with a space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let folded = line.strip_prefix(' ').or_else(|| line.strip_prefix('\t'));
+        match (folded, lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.trim_end().to_owned()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut event: Option<PartialEvent> = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Parameters such as `;TZID=...` follow the name
+        let name = name.split(';').next().unwrap_or_default();
+        match (name, &mut event) {
+            ("BEGIN", _) if value == "VEVENT" => event = Some((String::new(), None, None)),
+            ("END", Some((summary, Some((start, whole_day)), end))) if value == "VEVENT" => {
+                let end = end.unwrap_or(if *whole_day {
+                    *start + Duration::days(1)
+                } else {
+                    *start
+                });
+                events.push(CalendarEvent {
+                    summary: summary.clone(),
+                    start: *start,
+                    end,
+                });
+                event = None;
+            }
+            ("END", _) if value == "VEVENT" => event = None,
+            ("SUMMARY", Some((summary, ..))) => *summary = value.to_owned(),
+            ("DTSTART", Some((_, start, _))) => *start = parse_ical_time(value),
+            ("DTEND", Some((.., end))) => *end = parse_ical_time(value).map(|(t, _)| t),
+            _ => {}
+        }
+    }
+    events
+}
+
+/// When and why the charge limit is raised
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ChargeSchedule {
+    pub enabled: bool,
+    pub targets: Vec<ChargeTarget>,
+    /// How long before a target or event the limit is raised, so there is
+    /// time to charge
+    pub lead_minutes: u16,
+    /// An iCalendar file, empty for none
+    pub calendar_file: String,
+    /// Events with this in the summary raise the limit until they end
+    pub calendar_keyword: String,
+    pub calendar_limit: u8,
+    /// Learn when the laptop is usually unplugged and raise the limit to
+    /// `adaptive_limit` before then. This works without `enabled`.
+    pub adaptive: bool,
+    pub adaptive_limit: u8,
+    /// Local times the laptop was unplugged over the last weeks
+    pub unplugs: Vec<NaiveDateTime>,
+}
+
+impl Default for ChargeSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            targets: Vec::new(),
+            lead_minutes: 120,
+            calendar_file: String::new(),
+            calendar_keyword: "travel".to_owned(),
+            calendar_limit: 100,
+            adaptive: false,
+            adaptive_limit: 100,
+            unplugs: Vec::new(),
+        }
+    }
+}
+
+/// A time the charge limit is raised
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    limit: u8,
+    reason: String,
+}
+
+/// A change to the charge limit. `limit` of `None` goes back to the
+/// configured limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChargeAction {
+    pub at: NaiveDateTime,
+    pub limit: Option<u8>,
+    pub reason: String,
+}
+
+impl Display for ChargeAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let at = self.at.format("%a %Y-%m-%d %H:%M");
+        match self.limit {
+            Some(limit) => write!(f, "Charge to {limit}% from {at} for {}", self.reason),
+            None => write!(f, "Return to the charge limit at {at}"),
+        }
+    }
+}
+
+/// The raised limit now and the next change
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ChargePlan {
+    pub limit: Option<u8>,
+    pub next: Option<ChargeAction>,
+}
+
+impl ChargeSchedule {
+    pub fn check(&self) -> Result<(), RogError> {
+        for target in &self.targets {
+            target.check()?;
+        }
+        for limit in [
+            self.calendar_limit, self.adaptive_limit,
+        ] {
+            if !(20..=100).contains(&limit) {
+                return Err(RogError::ChargeLimit(limit));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the time the laptop was unplugged, forgetting the old ones
+    pub fn log_unplug(&mut self, at: NaiveDateTime) {
+        let oldest = at - Duration::weeks(UNPLUG_WEEKS);
+        self.unplugs.retain(|t| *t > oldest && *t <= at);
+        self.unplugs.push(at);
+    }
+
+    /// A target for each day of the week the laptop is usually unplugged
+    /// around the same time, at the earliest of those unplugs so it is
+    /// charged by then
+    pub fn learned_targets(&self, now: NaiveDateTime) -> Vec<ChargeTarget> {
+        let oldest = now - Duration::weeks(UNPLUG_WEEKS);
+        let mut targets = Vec::new();
+        for (day, name) in [
+            "mon", "tue", "wed", "thu", "fri", "sat", "sun",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut minutes: Vec<i64> = self
+                .unplugs
+                .iter()
+                .filter(|t| **t > oldest && t.weekday().num_days_from_monday() as usize == day)
+                .map(|t| (t.time() - NaiveTime::MIN).num_minutes())
+                .collect();
+            if minutes.len() < MIN_UNPLUGS {
+                continue;
+            }
+            minutes.sort();
+            let median = minutes[minutes.len() / 2];
+            let usual: Vec<i64> = minutes
+                .into_iter()
+                .filter(|m| (m - median).abs() <= UNPLUG_SPREAD_MINUTES)
+                .collect();
+            if usual.len() < MIN_UNPLUGS {
+                continue;
+            }
+            targets.push(ChargeTarget {
+                days: (*name).to_owned(),
+                time: format!("{:02}:{:02}", usual[0] / 60, usual[0] % 60),
+                limit: self.adaptive_limit,
+            });
+        }
+        targets
+    }
+
+    /// The events of the calendar file with the keyword
+    pub fn calendar_events(&self) -> Vec<CalendarEvent> {
+        if self.calendar_file.is_empty() {
+            return Vec::new();
+        }
+        let keyword = self.calendar_keyword.to_lowercase();
+        match std::fs::read_to_string(&self.calendar_file) {
+            Ok(text) => parse_calendar(&text)
+                .into_iter()
+                .filter(|e| e.summary.to_lowercase().contains(&keyword))
+                .collect(),
+            Err(e) => {
+                warn!("Charge schedule: {}: {e}", self.calendar_file);
+                Vec::new()
+            }
+        }
+    }
+
+    /// The windows from yesterday to a week ahead, invalid targets are skipped.
+    /// The targets and events are only used if `enabled`, the learned times
+    /// if `adaptive`.
+    fn windows(&self, now: NaiveDateTime, events: &[CalendarEvent]) -> Vec<Window> {
+        let lead = Duration::minutes(self.lead_minutes as i64);
+        let mut targets = Vec::new();
+        if self.enabled {
+            for target in &self.targets {
+                targets.push((target.clone(), format!("{} {}", target.days, target.time)));
+            }
+        }
+        if self.adaptive {
+            for target in self.learned_targets(now) {
+                let reason = format!("the usual unplug at {}", target.time);
+                targets.push((target, reason));
+            }
+        }
+        let events = if self.enabled { events } else { &[] };
+
+        let mut windows = Vec::new();
+        for (target, reason) in targets {
+            let (Ok(days), Ok(time)) = (parse_days(&target.days), target.time()) else {
+                continue;
+            };
+            for offset in -1..=7 {
+                let date = now.date() + Duration::days(offset);
+                if days & (1 << date.weekday().num_days_from_monday()) == 0 {
+                    continue;
+                }
+                let end = date.and_time(time);
+                windows.push(Window {
+                    start: end - lead,
+                    end,
+                    limit: target.limit,
+                    reason: reason.clone(),
+                });
+            }
+        }
+        for event in events {
+            windows.push(Window {
+                start: event.start - lead,
+                end: event.end,
+                limit: self.calendar_limit,
+                reason: event.summary.clone(),
+            });
+        }
+        windows
+    }
+
+    /// The highest limit of the windows at a time
+    fn limit_at(windows: &[Window], time: NaiveDateTime) -> Option<&Window> {
+        windows
+            .iter()
+            .filter(|w| w.start <= time && time < w.end)
+            .max_by_key(|w| w.limit)
+    }
+
+    pub fn plan(&self, now: NaiveDateTime, events: &[CalendarEvent]) -> ChargePlan {
+        let windows = self.windows(now, events);
+        let limit = Self::limit_at(&windows, now).map(|w| w.limit);
+        let mut changes: Vec<NaiveDateTime> = windows
+            .iter()
+            .flat_map(|w| {
+                [
+                    w.start, w.end,
+                ]
+            })
+            .filter(|t| *t > now)
+            .collect();
+        changes.sort();
+        let next = changes.into_iter().find_map(|at| {
+            let window = Self::limit_at(&windows, at);
+            (window.map(|w| w.limit) != limit).then(|| ChargeAction {
+                at,
+                limit: window.map(|w| w.limit),
+                reason: window.map(|w| w.reason.clone()).unwrap_or_default(),
+            })
+        });
+        ChargePlan { limit, next }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn charge_schedule_days() {
+        assert_eq!(parse_days("mon-fri").unwrap(), 0x1f);
+        assert_eq!(parse_days("weekdays").unwrap(), 0x1f);
+        assert_eq!(parse_days("Sat, sunday").unwrap(), 0x60);
+        // Ranges wrap over the end of the week
+        assert_eq!(parse_days("sat-mon").unwrap(), 0x61);
+        assert!(parse_days("someday").is_err());
+    }
+
+    #[test]
+    fn charge_schedule_plan() {
+        let schedule = ChargeSchedule {
+            enabled: true,
+            targets: vec![
+                ChargeTarget {
+                    days: "mon-fri".to_owned(),
+                    time: "07:30".to_owned(),
+                    limit: 100,
+                },
+            ],
+            ..Default::default()
+        };
+        schedule.check().unwrap();
+
+        // 2026-10-16 is a Friday
+        let plan = schedule.plan(at("2026-10-16 06:00"), &[]);
+        assert_eq!(plan.limit, Some(100));
+        assert_eq!(
+            plan.next,
+            Some(ChargeAction {
+                at: at("2026-10-16 07:30"),
+                limit: None,
+                reason: String::new(),
+            })
+        );
+
+        // Nothing over the weekend, Monday is next
+        let plan = schedule.plan(at("2026-10-16 08:00"), &[]);
+        assert_eq!(plan.limit, None);
+        let next = plan.next.unwrap();
+        assert_eq!(next.at, at("2026-10-19 05:30"));
+        assert_eq!(next.limit, Some(100));
+        assert_eq!(
+            next.to_string(),
+            "Charge to 100% from Mon 2026-10-19 05:30 for mon-fri 07:30"
+        );
+
+        // A trip over the weekend raises the limit until it ends
+        let trip = CalendarEvent {
+            summary: "Travel to Berlin".to_owned(),
+            start: at("2026-10-17 10:00"),
+            end: at("2026-10-18 18:00"),
+        };
+        let plan = schedule.plan(at("2026-10-17 09:00"), &[trip]);
+        assert_eq!(plan.limit, Some(100));
+        assert_eq!(plan.next.unwrap().at, at("2026-10-18 18:00"));
+    }
+
+    #[test]
+    fn charge_schedule_calendar() {
+        let calendar = [
+            "BEGIN:VCALENDAR",
+            "BEGIN:VEVENT",
+            "SUMMARY:Travel to the",
+            "  conference",
+            "DTSTART;TZID=Europe/Berlin:20261020T073000",
+            "DTEND:20261020T180000",
+            "END:VEVENT",
+            "BEGIN:VEVENT",
+            "SUMMARY:Holiday",
+            "DTSTART;VALUE=DATE:20261024",
+            "END:VEVENT",
+            "BEGIN:VEVENT",
+            "SUMMARY:No start",
+            "END:VEVENT",
+            "END:VCALENDAR",
+            "",
+        ];
+        let events = parse_calendar(&calendar.join("\r\n"));
+        assert_eq!(events, vec![
+            CalendarEvent {
+                summary: "Travel to the conference".to_owned(),
+                start: at("2026-10-20 07:30"),
+                end: at("2026-10-20 18:00"),
+            },
+            CalendarEvent {
+                summary: "Holiday".to_owned(),
+                start: at("2026-10-24 00:00"),
+                end: at("2026-10-25 00:00"),
+            },
+        ]);
+    }
+
+    #[test]
+    fn charge_schedule_learned() {
+        // Adaptive charging is opt-in
+        assert!(!ChargeSchedule::default().adaptive);
+        let mut schedule = ChargeSchedule {
+            adaptive: true,
+            ..Default::default()
+        };
+        // Mondays around 07:45 with one late start, and two Saturdays
+        for time in [
+            "2026-09-21 07:50", "2026-09-28 07:40", "2026-10-03 10:00", "2026-10-05 11:30",
+            "2026-10-10 10:00", "2026-10-12 08:10",
+        ] {
+            schedule.log_unplug(at(time));
+        }
+        let now = at("2026-10-16 12:00");
+        assert_eq!(schedule.learned_targets(now), vec![
+            ChargeTarget {
+                days: "mon".to_owned(),
+                time: "07:40".to_owned(),
+                limit: 100,
+            }
+        ]);
+
+        // The targets don't need the schedule enabled
+        let next = schedule.plan(now, &[]).next.unwrap();
+        assert_eq!(next.at, at("2026-10-19 05:40"));
+        assert_eq!(
+            next.to_string(),
+            "Charge to 100% from Mon 2026-10-19 05:40 for the usual unplug at 07:40"
+        );
+
+        // Switched off
+        let mut off = schedule.clone();
+        off.adaptive = false;
+        assert_eq!(off.plan(now, &[]), ChargePlan::default());
+
+        // Old unplugs are forgotten
+        assert!(schedule.learned_targets(at("2026-12-01 07:00")).is_empty());
+        schedule.log_unplug(at("2026-12-14 07:45"));
+        assert_eq!(schedule.unplugs.len(), 1);
+    }
+}
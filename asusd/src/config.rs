@@ -1,17 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use chrono::NaiveDateTime;
 use config_traits::{StdConfig, StdConfigLoad2};
-use rog_platform::asus_armoury::FirmwareAttribute;
+use rog_platform::asus_armoury::{ChargeMode, FirmwareAttribute, PowerLimits};
 use rog_platform::cpu::CPUEPP;
 use rog_platform::platform::PlatformProfile;
+use rog_platform::profile_rules::ProfileRule;
 use serde::{Deserialize, Serialize};
 
+use crate::charge_schedule::ChargeSchedule;
+
 const CONFIG_FILE: &str = "asusd.ron";
 
 #[derive(Default, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Tuning {
     pub enabled: bool,
     pub group: HashMap<FirmwareAttribute, i32>,
+    /// Named sets of power limits for this profile and power state
+    #[serde(default)]
+    pub presets: BTreeMap<String, PowerLimits>,
 }
 type Tunings = HashMap<PlatformProfile, Tuning>;
 
@@ -53,9 +60,41 @@ pub struct Config {
     pub screenpad_gamma: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub screenpad_sync_primary: Option<bool>,
+    /// Rules switching the platform profile and other settings, see
+    /// `CtrlProfileRules`
+    #[serde(default)]
+    pub profile_rules: Vec<ProfileRule>,
+    /// Times the charge limit is raised ahead of use
+    #[serde(default)]
+    pub charge_schedule: ChargeSchedule,
+    /// The charge mode, or the mode on battery if it is changed on AC
+    #[serde(default)]
+    pub charge_mode: ChargeMode,
+    /// Which charge mode to use on AC power
+    #[serde(default)]
+    pub charge_mode_on_ac: ChargeMode,
+    /// Should the charge mode be set on bat/ac change?
+    #[serde(default)]
+    pub change_charge_mode_on_ac: bool,
     /// Temporary state for AC/Batt
     #[serde(skip)]
     pub last_power_plugged: u8,
+    /// Temporary state, the platform profile set by the active profile rule
+    #[serde(skip)]
+    pub rule_profile: Option<PlatformProfile>,
+    /// Temporary state, the EPP set by the active profile rule
+    #[serde(skip)]
+    pub rule_epp: Option<CPUEPP>,
+    /// Temporary state, the charge limit set by the active profile rule
+    #[serde(skip)]
+    pub rule_charge_limit: Option<u8>,
+    /// Temporary state, the charge limit raised to by the charge schedule
+    #[serde(skip)]
+    pub schedule_limit: Option<u8>,
+    /// Temporary state, when AC was plugged in for learning the usual unplug
+    /// times
+    #[serde(skip)]
+    pub plugged_in_at: Option<NaiveDateTime>,
 }
 
 impl Config {
@@ -67,6 +106,15 @@ impl Config {
         };
         config.entry(profile).or_insert_with(Tuning::default)
     }
+
+    /// The charge limit to write, the active profile rule's if it sets one,
+    /// raised while the charge schedule asks for more
+    pub fn charge_limit(&self) -> u8 {
+        let limit = self
+            .rule_charge_limit
+            .unwrap_or(self.charge_control_end_threshold);
+        self.schedule_limit.map_or(limit, |s| s.max(limit))
+    }
 }
 
 impl Default for Config {
@@ -92,6 +140,16 @@ impl Default for Config {
             last_power_plugged: Default::default(),
             screenpad_gamma: Default::default(),
             screenpad_sync_primary: Default::default(),
+            profile_rules: Vec::new(),
+            charge_schedule: ChargeSchedule::default(),
+            charge_mode: ChargeMode::default(),
+            charge_mode_on_ac: ChargeMode::default(),
+            change_charge_mode_on_ac: false,
+            rule_profile: None,
+            rule_epp: None,
+            rule_charge_limit: None,
+            schedule_limit: None,
+            plugged_in_at: None,
         }
     }
 }
@@ -168,6 +226,16 @@ impl From<Config611> for Config {
             armoury_settings: HashMap::default(),
             screenpad_gamma: None,
             screenpad_sync_primary: Default::default(),
+            profile_rules: Vec::new(),
+            charge_schedule: ChargeSchedule::default(),
+            charge_mode: ChargeMode::default(),
+            charge_mode_on_ac: ChargeMode::default(),
+            change_charge_mode_on_ac: false,
+            rule_profile: None,
+            rule_epp: None,
+            rule_charge_limit: None,
+            schedule_limit: None,
+            plugged_in_at: None,
         }
     }
 }
@@ -235,6 +303,16 @@ impl From<Config601> for Config {
             armoury_settings: HashMap::default(),
             screenpad_gamma: None,
             screenpad_sync_primary: Default::default(),
+            profile_rules: Vec::new(),
+            charge_schedule: ChargeSchedule::default(),
+            charge_mode: ChargeMode::default(),
+            charge_mode_on_ac: ChargeMode::default(),
+            change_charge_mode_on_ac: false,
+            rule_profile: None,
+            rule_epp: None,
+            rule_charge_limit: None,
+            schedule_limit: None,
+            plugged_in_at: None,
         }
     }
 }
@@ -0,0 +1,164 @@
+//! Battery telemetry: a sample of the battery every `SAMPLE_INTERVAL`, and a
+//! history of samples over the last month kept in `battery_history.bin` for
+//! tracking wear and how the charge limit holds.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_util::lock::Mutex;
+use log::{debug, warn};
+use rog_platform::power::{AsusPower, BatterySample};
+use tokio::time::sleep;
+use zbus::object_server::SignalEmitter;
+use zbus::{interface, Connection};
+
+use crate::battery_history::BatteryHistory;
+use crate::error::RogError;
+use crate::{CtrlTask, CONFIG_PATH_BASE};
+
+const BATTERY_ZBUS_PATH: &str = "/xyz/ljones";
+const HISTORY_FILE: &str = "battery_history.bin";
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+/// Seconds between samples kept in the history
+const HISTORY_INTERVAL: u64 = 10 * 60;
+/// 30 days of samples
+const HISTORY_LEN: usize = 30 * 24 * 6;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Clone)]
+pub struct CtrlBattery {
+    power: AsusPower,
+    sample: Arc<Mutex<BatterySample>>,
+    history: Arc<Mutex<BatteryHistory>>,
+}
+
+impl CtrlBattery {
+    pub fn new(power: AsusPower) -> Result<Self, RogError> {
+        if !power.has_capacity() {
+            return Err(RogError::MissingFunction(
+                "Battery has no capacity attribute".to_owned(),
+            ));
+        }
+        let sample = power.battery_sample(now())?;
+        let history = BatteryHistory::load(
+            PathBuf::from(CONFIG_PATH_BASE).join(HISTORY_FILE),
+            HISTORY_LEN,
+        );
+        Ok(Self {
+            power,
+            sample: Arc::new(Mutex::new(sample)),
+            history: Arc::new(Mutex::new(history)),
+        })
+    }
+
+    /// Read a new sample, and keep it in the history if the last one kept is
+    /// old enough
+    async fn update(&self, signal_ctxt: &SignalEmitter<'_>) {
+        let time = now();
+        let sample = match self.power.battery_sample(time) {
+            Ok(sample) => sample,
+            Err(e) => {
+                warn!("Could not sample the battery: {e}");
+                return;
+            }
+        };
+        debug!("Battery sample: {sample:?}");
+
+        let mut history = self.history.lock().await;
+        let last = history.samples().back().map(|s| s.time);
+        // The clock may have gone back
+        if last.is_none_or(|last| time >= last + HISTORY_INTERVAL || time < last) {
+            history
+                .push(sample.clone())
+                .map_err(|e| warn!("Could not save the battery history: {e}"))
+                .ok();
+        }
+        drop(history);
+
+        *self.sample.lock().await = sample;
+        self.sample_changed(signal_ctxt).await.ok();
+    }
+}
+
+#[interface(name = "xyz.ljones.Battery")]
+impl CtrlBattery {
+    /// The last battery sample, updated every minute
+    #[zbus(property)]
+    async fn sample(&self) -> BatterySample {
+        self.sample.lock().await.clone()
+    }
+
+    /// The samples kept over the last month, oldest first
+    async fn history(&self) -> Vec<BatterySample> {
+        self.history
+            .lock()
+            .await
+            .samples()
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+impl crate::ZbusRun for CtrlBattery {
+    async fn add_to_server(self, server: &mut Connection) {
+        Self::add_to_server_helper(self, BATTERY_ZBUS_PATH, server).await;
+    }
+}
+
+impl crate::Reloadable for CtrlBattery {
+    async fn reload(&mut self) -> Result<(), RogError> {
+        Ok(())
+    }
+}
+
+impl CtrlTask for CtrlBattery {
+    fn zbus_path() -> &'static str {
+        BATTERY_ZBUS_PATH
+    }
+
+    async fn create_tasks(&self, signal_ctxt: SignalEmitter<'static>) -> Result<(), RogError> {
+        let ctrl1 = self.clone();
+        let ctrl2 = self.clone();
+        let signal_ctxt1 = signal_ctxt.clone();
+        let signal_ctxt2 = signal_ctxt.clone();
+        // The rate changes as soon as the charger is plugged or unplugged
+        self.create_sys_event_tasks(
+            move |sleeping| {
+                let ctrl = ctrl1.clone();
+                let signal_ctxt = signal_ctxt1.clone();
+                async move {
+                    if !sleeping {
+                        ctrl.update(&signal_ctxt).await;
+                    }
+                }
+            },
+            move |_shutting_down| async move {},
+            move |_lid_closed| async move {},
+            move |_power_plugged| {
+                let ctrl = ctrl2.clone();
+                let signal_ctxt = signal_ctxt2.clone();
+                async move {
+                    ctrl.update(&signal_ctxt).await;
+                }
+            },
+        )
+        .await;
+
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            loop {
+                ctrl.update(&signal_ctxt).await;
+                sleep(SAMPLE_INTERVAL).await;
+            }
+        });
+        Ok(())
+    }
+}
@@ -1,36 +1,138 @@
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use config_traits::{StdConfig, StdConfigLoad};
 use futures_lite::StreamExt;
 use futures_util::lock::Mutex;
 use log::{debug, error, info, warn};
 use rog_platform::platform::{PlatformProfile, RogPlatform};
+use rog_platform::power::AsusPower;
+use rog_platform::sysfs::SysfsRoot;
+use rog_profiles::calibration::FanCalibration;
+use rog_profiles::curve_points::{CurvePoints, CurvePreset};
 use rog_profiles::error::ProfileError;
-use rog_profiles::fan_curve_set::CurveData;
+use rog_profiles::export::FanCurveExport;
+use rog_profiles::fan_curve_set::{CurveData, CurveLimits};
+use rog_profiles::software_curve::{find_pwm_node, SoftwareCurve, TempSource};
+use rog_profiles::telemetry::{FanTelemetry, TelemetryNodes};
 use rog_profiles::{find_fan_curve_node, FanCurvePU, FanCurveProfiles};
+use ron::ser::PrettyConfig;
 use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
 use zbus::object_server::SignalEmitter;
 use zbus::{interface, Connection};
 
 use crate::error::RogError;
+use crate::fan_calibration::calibrate_fan;
+use crate::fan_control::{release_fan, SoftwareFanConfig, SoftwareFanControl};
 use crate::{CtrlTask, CONFIG_PATH_BASE};
 
 pub const FAN_CURVE_ZBUS_NAME: &str = "FanCurves";
 pub const FAN_CURVE_ZBUS_PATH: &str = "/xyz/ljones";
+/// Telemetry can't be sampled faster than this
+pub const MIN_TELEMETRY_INTERVAL_MS: u32 = 250;
+
+fn default_telemetry_interval_ms() -> u32 {
+    0
+}
+
+/// The limits listed for this model, or the defaults
+fn default_curve_limits() -> CurveLimits {
+    CurveLimits::for_board(&SysfsRoot::get().dmi_id().unwrap_or_default().board_name)
+}
 
 #[derive(Deserialize, Serialize, Debug, Default)]
 pub struct FanCurveConfig {
+    /// The curves on AC, and on battery if `dc_curves_enabled` is not set
     pub profiles: FanCurveProfiles,
+    /// The curves on battery
+    #[serde(default)]
+    pub dc_profiles: FanCurveProfiles,
+    /// Use `dc_profiles` when on battery
+    #[serde(default)]
+    pub dc_curves_enabled: bool,
     #[serde(skip)]
     pub current: PlatformProfile,
+    /// Temporary state for AC/Batt
+    #[serde(skip)]
+    pub on_battery: bool,
+    /// Curves set over dbus must stay within these. Starts as the limits for
+    /// the model.
+    #[serde(default = "default_curve_limits")]
+    pub limits: CurveLimits,
+    /// Curves run by asusd instead of the firmware
+    #[serde(default)]
+    pub software: SoftwareFanConfig,
+    /// Milliseconds between fan telemetry samples, 0 to stop sampling
+    #[serde(default = "default_telemetry_interval_ms")]
+    pub telemetry_interval_ms: u32,
+    /// The measured RPM of each fan across its pwm range
+    #[serde(default)]
+    pub calibration: Vec<FanCalibration>,
+}
+
+impl FanCurveConfig {
+    /// The curves for AC or battery. Battery has its own curves only if
+    /// `dc_curves_enabled` is set.
+    pub fn select_profiles(&mut self, power_plugged: bool) -> &mut FanCurveProfiles {
+        if !power_plugged && self.dc_curves_enabled {
+            &mut self.dc_profiles
+        } else {
+            &mut self.profiles
+        }
+    }
+
+    /// The curves for the current power source
+    pub fn active_profiles(&mut self) -> &mut FanCurveProfiles {
+        self.select_profiles(!self.on_battery)
+    }
+
+    /// If the AC curves are in use, which they are on battery too unless it
+    /// has its own
+    fn uses_ac_curves(&self) -> bool {
+        !self.on_battery || !self.dc_curves_enabled
+    }
+
+    /// The temperatures driving a fan: the software curve sources if the fan
+    /// has a curve, otherwise the usual ones for the fan
+    fn temp_sources(&self, fan: FanCurvePU) -> Vec<TempSource> {
+        self.software
+            .curves
+            .iter()
+            .find(|c| c.fan == fan)
+            .map(|c| c.sources.clone())
+            .unwrap_or_else(|| TempSource::defaults_for(fan))
+    }
+
+    /// The firmware defaults are kept even if they are outside the limits, as
+    /// the firmware runs them anyway, but warn about each that is
+    fn check_defaults(&mut self, profile: PlatformProfile) {
+        let limits = self.limits;
+        for curve in self.active_profiles().get_fan_curves_for(profile) {
+            if let Err(e) = curve.validate(&limits) {
+                warn!("The {profile} default is outside the limits for this model: {e}");
+            }
+        }
+    }
+
+    /// Replace the calibration for the same fan, or add it
+    fn set_calibration(&mut self, calibration: FanCalibration) {
+        self.calibration.retain(|c| c.fan != calibration.fan);
+        self.calibration.push(calibration);
+    }
 }
 
 impl StdConfig for FanCurveConfig {
     /// Create a new config. The defaults are zeroed so the device must be read
     /// to get the actual device defaults.
     fn new() -> Self {
-        Self::default()
+        Self {
+            limits: default_curve_limits(),
+            telemetry_interval_ms: default_telemetry_interval_ms(),
+            ..Default::default()
+        }
     }
 
     fn file_name(&self) -> String {
@@ -48,6 +150,11 @@ impl StdConfigLoad for FanCurveConfig {}
 pub struct CtrlFanCurveZbus {
     config: Arc<Mutex<FanCurveConfig>>,
     platform: RogPlatform,
+    software: SoftwareFanControl,
+    /// The last telemetry sample
+    telemetry: Arc<Mutex<Vec<FanTelemetry>>>,
+    /// Set while fans are being calibrated, clearing it cancels calibration
+    calibrating: Arc<AtomicBool>,
 }
 
 // Non-zbus-derive impl
@@ -67,7 +174,7 @@ impl CtrlFanCurveZbus {
 
                 let current = platform.get_platform_profile()?;
                 let profiles = platform.get_platform_profile_choices()?;
-                for this in profiles {
+                for this in profiles.iter().copied() {
                     // For each profile we need to switch to it before we
                     // can read the existing values from hardware. The ACPI method used
                     // for this is what limits us.
@@ -82,20 +189,212 @@ impl CtrlFanCurveZbus {
                 }
                 platform.set_platform_profile(current.as_str())?;
                 config.profiles = fan_curves;
+                for this in profiles {
+                    config.check_defaults(this);
+                }
                 config.write();
             } else {
                 info!("Fan curves previously stored, loading...");
                 config = config.load();
+                Self::add_missing_fans(&platform, &mut config)?;
+            }
+            if config.dc_curves_enabled {
+                // Fans added to the AC curves above
+                config.dc_profiles.add_missing_from(&config.profiles);
             }
+            config.on_battery = AsusPower::new()
+                .and_then(|power| power.get_online())
+                .is_ok_and(|online| online == 0);
 
             return Ok(Self {
                 config: Arc::new(Mutex::new(config)),
                 platform,
+                software: SoftwareFanControl::default(),
+                telemetry: Arc::new(Mutex::new(Vec::new())),
+                calibrating: Arc::new(AtomicBool::new(false)),
             });
         }
 
         Err(ProfileError::NotSupported.into())
     }
+
+    /// Configs from before a fan was supported, such as the MID fan, have no
+    /// curves for it. Read its defaults from the device for each profile.
+    fn add_missing_fans(
+        platform: &RogPlatform,
+        config: &mut FanCurveConfig,
+    ) -> Result<(), RogError> {
+        let fans = FanCurveProfiles::supported_fans()?;
+        let current = platform.get_platform_profile()?;
+        let mut migrated = false;
+        for this in platform.get_platform_profile_choices()? {
+            let missing = config.profiles.missing_fans(this, &fans);
+            if missing.is_empty() {
+                continue;
+            }
+            info!("Adding default curves for {missing:?} to {this:?}");
+            platform.set_platform_profile(this.into())?;
+            let mut dev = find_fan_curve_node()?;
+            config.profiles.add_fan_defaults(this, &missing, &mut dev)?;
+            migrated = true;
+        }
+        if migrated {
+            platform.set_platform_profile(current.as_str())?;
+            config.write();
+        }
+        Ok(())
+    }
+
+    /// A fan with no stored curve can't be set
+    async fn check_fan(&self, profile: PlatformProfile, fan: FanCurvePU) -> zbus::fdo::Result<()> {
+        if self
+            .config
+            .lock()
+            .await
+            .active_profiles()
+            .get_fan_curve_for(&profile, fan)
+            .is_none()
+        {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "There is no {fan:?} fan curve for {profile}, this machine may not have the fan"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Validate and store a curve, and write it if it is for the active
+    /// profile and power source. Battery curves are refused unless
+    /// `BatteryFanCurves` is set, rather than changing the AC curves.
+    async fn store_fan_curve(
+        &self,
+        profile: PlatformProfile,
+        power_plugged: bool,
+        curve: CurveData,
+    ) -> zbus::fdo::Result<()> {
+        self.check_not_calibrating()?;
+        let mut config = self.config.lock().await;
+        if !power_plugged && !config.dc_curves_enabled {
+            return Err(zbus::fdo::Error::InvalidArgs(
+                "Battery fan curves are disabled, enable BatteryFanCurves first".to_owned(),
+            ));
+        }
+        let limits = config.limits;
+        let is_active = power_plugged == config.uses_ac_curves();
+        let profiles = config.select_profiles(power_plugged);
+        if profiles.get_fan_curve_for(&profile, curve.fan).is_none() {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "There is no {:?} fan curve for {profile}, this machine may not have the fan",
+                curve.fan
+            )));
+        }
+        curve.validate(&limits)?;
+        profiles.save_fan_curve(curve, profile)?;
+        let active: PlatformProfile = self.platform.get_platform_profile()?.into();
+        if active == profile && is_active {
+            profiles.write_profile_curve_to_platform(profile, &mut find_fan_curve_node()?)?;
+        }
+        config.write();
+        Ok(())
+    }
+
+    /// Sample every fan. The temperature is from the software curve sources if
+    /// the fan has a curve.
+    async fn read_telemetry(
+        &self,
+        nodes: &mut TelemetryNodes,
+        fans: &[FanCurvePU],
+    ) -> Vec<FanTelemetry> {
+        let sources: Vec<Vec<TempSource>> = {
+            let config = self.config.lock().await;
+            fans.iter().map(|fan| config.temp_sources(*fan)).collect()
+        };
+        fans.iter()
+            .zip(sources.iter())
+            .map(|(fan, sources)| nodes.read(*fan, sources))
+            .collect()
+    }
+
+    /// Hand any fans under software control or being calibrated back to the
+    /// firmware, for when the daemon exits. Software control starts again on
+    /// the next run.
+    pub async fn release_fans(&self) {
+        if self.calibrating.swap(false, Ordering::SeqCst) {
+            for fan in FanCurveProfiles::supported_fans().unwrap_or_default() {
+                if let Ok(mut device) = find_pwm_node(fan) {
+                    release_fan(fan, &mut device);
+                }
+            }
+            self.restore_curves()
+                .await
+                .unwrap_or_else(|e| warn!("Could not restore fan curves: {e}"));
+        }
+        let config = self.config.lock().await;
+        self.software.stop(&config.software);
+    }
+
+    /// Fan curves are left alone while fans are calibrated. The calibration
+    /// writes the curves of the active profile and power source when done.
+    fn check_not_calibrating(&self) -> Result<(), RogError> {
+        if self.calibrating.load(Ordering::SeqCst) {
+            return Err(RogError::DoTask(
+                "fan curves can't be changed while fans are calibrated".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Stop software fan control and put the firmware curves back
+    async fn stop_software_control(&self) -> Result<(), RogError> {
+        let config = self.config.lock().await;
+        self.software.stop(&config.software);
+        drop(config);
+        self.restore_curves().await
+    }
+
+    /// Write the curves for the new power source if battery has its own.
+    /// Software control and calibration set the fans themselves.
+    async fn power_source_changed(&self, power_plugged: bool) {
+        let mut config = self.config.lock().await;
+        config.on_battery = !power_plugged;
+        let dc_curves_enabled = config.dc_curves_enabled;
+        drop(config);
+        if dc_curves_enabled
+            && !self.software.is_running()
+            && !self.calibrating.load(Ordering::SeqCst)
+        {
+            info!("Power source changed, setting fan curves");
+            self.restore_curves()
+                .await
+                .unwrap_or_else(|e| warn!("Could not set fan curves: {e}"));
+        }
+    }
+
+    /// Write the curves of the active profile. Handing a fan back to the
+    /// firmware turns off its custom curve.
+    async fn restore_curves(&self) -> Result<(), RogError> {
+        let active = self.platform.get_platform_profile()?.into();
+        self.config
+            .lock()
+            .await
+            .active_profiles()
+            .write_profile_curve_to_platform(active, &mut find_fan_curve_node()?)?;
+        Ok(())
+    }
+
+    /// Set all fan curves for a profile to enabled status and write them
+    pub(crate) async fn enable_profile_curves(
+        &self,
+        profile: PlatformProfile,
+        enabled: bool,
+    ) -> Result<(), RogError> {
+        self.check_not_calibrating()?;
+        let mut config = self.config.lock().await;
+        let profiles = config.active_profiles();
+        profiles.set_profile_curves_enabled(profile, enabled);
+        profiles.write_profile_curve_to_platform(profile, &mut find_fan_curve_node()?)?;
+        config.write();
+        Ok(())
+    }
 }
 
 #[interface(name = "xyz.ljones.FanCurves")]
@@ -107,18 +406,7 @@ impl CtrlFanCurveZbus {
         profile: PlatformProfile,
         enabled: bool,
     ) -> zbus::fdo::Result<()> {
-        self.config
-            .lock()
-            .await
-            .profiles
-            .set_profile_curves_enabled(profile, enabled);
-        self.config
-            .lock()
-            .await
-            .profiles
-            .write_profile_curve_to_platform(profile, &mut find_fan_curve_node()?)?;
-        self.config.lock().await.write();
-        Ok(())
+        Ok(self.enable_profile_curves(profile, enabled).await?)
     }
 
     /// Set a single fan curve for a profile to enabled status. Will also
@@ -129,15 +417,17 @@ impl CtrlFanCurveZbus {
         fan: FanCurvePU,
         enabled: bool,
     ) -> zbus::fdo::Result<()> {
+        self.check_fan(profile, fan).await?;
+        self.check_not_calibrating()?;
         self.config
             .lock()
             .await
-            .profiles
+            .active_profiles()
             .set_profile_fan_curve_enabled(profile, fan, enabled);
         self.config
             .lock()
             .await
-            .profiles
+            .active_profiles()
             .write_profile_curve_to_platform(profile, &mut find_fan_curve_node()?)?;
         self.config.lock().await.write();
         Ok(())
@@ -152,34 +442,83 @@ impl CtrlFanCurveZbus {
             .config
             .lock()
             .await
-            .profiles
+            .active_profiles()
             .get_fan_curves_for(profile)
             .to_vec();
         Ok(curve)
     }
 
-    /// Set the fan curve for the specified profile.
-    /// Will also activate the fan curve if the user is in the same mode.
+    /// Set the fan curve for the specified profile on the current power
+    /// source. Will also activate the fan curve if the user is in the same
+    /// mode. The curve is rejected if it fails validation against the limits
+    /// in config.
     async fn set_fan_curve(
         &mut self,
         profile: PlatformProfile,
         curve: CurveData,
     ) -> zbus::fdo::Result<()> {
-        self.config
+        let power_plugged = self.config.lock().await.uses_ac_curves();
+        self.store_fan_curve(profile, power_plugged, curve).await
+    }
+
+    /// Get the fan curves of a profile for AC or battery. Battery has the AC
+    /// curves unless `BatteryFanCurves` is set.
+    async fn fan_curve_data_for_power(
+        &self,
+        profile: PlatformProfile,
+        power_plugged: bool,
+    ) -> zbus::fdo::Result<Vec<CurveData>> {
+        Ok(self
+            .config
             .lock()
             .await
-            .profiles
-            .save_fan_curve(curve, profile)?;
-        let active: PlatformProfile = self.platform.get_platform_profile()?.into();
-        if active == profile {
-            self.config
-                .lock()
-                .await
-                .profiles
-                .write_profile_curve_to_platform(profile, &mut find_fan_curve_node()?)?;
-        }
-        self.config.lock().await.write();
-        Ok(())
+            .select_profiles(power_plugged)
+            .get_fan_curves_for(profile)
+            .to_vec())
+    }
+
+    /// Set the fan curve of a profile for AC or battery. Will also activate
+    /// the fan curve if it is for the current profile and power source.
+    async fn set_fan_curve_for_power(
+        &mut self,
+        profile: PlatformProfile,
+        power_plugged: bool,
+        curve: CurveData,
+    ) -> zbus::fdo::Result<()> {
+        self.store_fan_curve(profile, power_plugged, curve).await
+    }
+
+    /// Set the fan curve for the specified profile on the current power source
+    /// from any number of control points, resampled to the 8 the firmware
+    /// takes
+    async fn set_fan_curve_points(
+        &mut self,
+        profile: PlatformProfile,
+        points: CurvePoints,
+    ) -> zbus::fdo::Result<()> {
+        let power_plugged = self.config.lock().await.uses_ac_curves();
+        self.set_fan_curve_points_for_power(profile, power_plugged, points)
+            .await
+    }
+
+    /// Set the fan curve of a profile for AC or battery from any number of
+    /// control points
+    async fn set_fan_curve_points_for_power(
+        &mut self,
+        profile: PlatformProfile,
+        power_plugged: bool,
+        points: CurvePoints,
+    ) -> zbus::fdo::Result<()> {
+        let limits = self.config.lock().await.limits;
+        let curve = points.resample(&limits)?;
+        self.store_fan_curve(profile, power_plugged, curve).await
+    }
+
+    /// The control points of a preset for a fan, generated from the curve
+    /// limits of this model
+    async fn curve_preset(&self, preset: CurvePreset, fan: FanCurvePU) -> CurvePoints {
+        let limits = self.config.lock().await.limits;
+        preset.points(fan, &limits)
     }
 
     /// Reset the stored (self) and device curves to the defaults of the
@@ -188,14 +527,16 @@ impl CtrlFanCurveZbus {
     /// Each platform_profile has a different default and the default can be
     /// read only for the currently active profile.
     async fn set_curves_to_defaults(&mut self, profile: PlatformProfile) -> zbus::fdo::Result<()> {
+        self.check_not_calibrating()?;
         let active = self.platform.get_platform_profile()?;
         self.platform.set_platform_profile(profile.into())?;
         self.config
             .lock()
             .await
-            .profiles
+            .active_profiles()
             .set_active_curve_to_defaults(profile, &mut find_fan_curve_node()?)?;
         self.platform.set_platform_profile(active.as_str())?;
+        self.config.lock().await.check_defaults(profile);
         self.config.lock().await.write();
         Ok(())
     }
@@ -206,19 +547,253 @@ impl CtrlFanCurveZbus {
     /// Each platform_profile has a different default and the defualt can be
     /// read only for the currently active profile.
     async fn reset_profile_curves(&self, profile: PlatformProfile) -> zbus::fdo::Result<()> {
+        self.check_not_calibrating()?;
         let active = self.platform.get_platform_profile()?;
 
         self.platform.set_platform_profile(profile.into())?;
         self.config
             .lock()
             .await
-            .profiles
-            .set_active_curve_to_defaults(active.as_str().into(), &mut find_fan_curve_node()?)?;
+            .active_profiles()
+            .set_active_curve_to_defaults(profile, &mut find_fan_curve_node()?)?;
         self.platform.set_platform_profile(active.as_str())?;
+        self.config.lock().await.check_defaults(profile);
 
         self.config.lock().await.write();
         Ok(())
     }
+
+    /// Export the fan curves of `profiles` for `fans`, or all if empty, as a
+    /// RON document. The document records the board name and fans of this
+    /// machine.
+    async fn export_fan_curves(
+        &self,
+        profiles: Vec<PlatformProfile>,
+        fans: Vec<FanCurvePU>,
+    ) -> zbus::fdo::Result<String> {
+        let board_name = SysfsRoot::get().dmi_id().unwrap_or_default().board_name;
+        let machine_fans = FanCurveProfiles::supported_fans()?;
+        let export = self
+            .config
+            .lock()
+            .await
+            .active_profiles()
+            .export(&profiles, &fans, &board_name, &machine_fans);
+        ron::ser::to_string_pretty(&export, PrettyConfig::new().depth_limit(4))
+            .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Import a document from `ExportFanCurves`. A document from another
+    /// board or from a machine with a different number of fans is refused
+    /// unless `force` is set. Every curve is validated before any are stored.
+    /// Returns warnings about the import, such as curves for fans this
+    /// machine doesn't have.
+    async fn import_fan_curves(
+        &mut self,
+        document: &str,
+        force: bool,
+    ) -> zbus::fdo::Result<Vec<String>> {
+        self.check_not_calibrating()?;
+        let export: FanCurveExport = ron::from_str(document)
+            .map_err(|e| zbus::fdo::Error::InvalidArgs(format!("Invalid fan curves: {e}")))?;
+        let board_name = SysfsRoot::get().dmi_id().unwrap_or_default().board_name;
+        let mut warnings = export.mismatches(&board_name, &FanCurveProfiles::supported_fans()?);
+        if !warnings.is_empty() {
+            if !force {
+                return Err(zbus::fdo::Error::InvalidArgs(format!(
+                    "Fan curves are for another machine: {}",
+                    warnings.join(", ")
+                )));
+            }
+            warn!("Importing fan curves anyway: {}", warnings.join(", "));
+        }
+
+        let mut config = self.config.lock().await;
+        for (_, curve) in export.curves() {
+            curve.validate(&config.limits)?;
+        }
+        warnings.append(&mut config.active_profiles().import(&export));
+        let active: PlatformProfile = self.platform.get_platform_profile()?.into();
+        if export.curves().any(|(profile, _)| profile == active) {
+            config
+                .active_profiles()
+                .write_profile_curve_to_platform(active, &mut find_fan_curve_node()?)?;
+        }
+        config.write();
+        Ok(warnings)
+    }
+
+    /// Get the curves used by software fan control
+    async fn software_fan_curves(&self) -> zbus::fdo::Result<Vec<SoftwareCurve>> {
+        Ok(self.config.lock().await.software.curves.clone())
+    }
+
+    /// Set the software fan control curve for the fan in the curve. Takes
+    /// effect immediately if software fan control is running.
+    async fn set_software_fan_curve(&mut self, curve: SoftwareCurve) -> zbus::fdo::Result<()> {
+        let mut config = self.config.lock().await;
+        curve
+            .validate(&config.limits)
+            .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))?;
+        config.software.set_curve(curve);
+        if self.software.is_running() {
+            self.software.start(&config.software, &config.limits)?;
+        }
+        config.write();
+        Ok(())
+    }
+
+    /// Measure the RPM of `fans`, or all if empty, across their pwm range and
+    /// store the results. Each fan takes around a minute and a half during
+    /// which `Calibrating` is true. Can't be run with software fan control.
+    async fn calibrate_fans(
+        &self,
+        #[zbus(signal_emitter)] ctxt: SignalEmitter<'_>,
+        fans: Vec<FanCurvePU>,
+    ) -> zbus::fdo::Result<()> {
+        if self.software.is_running() {
+            return Err(zbus::fdo::Error::Failed(
+                "Software fan control must be stopped to calibrate fans".to_owned(),
+            ));
+        }
+        let fans = if fans.is_empty() {
+            FanCurveProfiles::supported_fans()?
+        } else {
+            fans
+        };
+        if self.calibrating.swap(true, Ordering::SeqCst) {
+            return Err(zbus::fdo::Error::Failed(
+                "Fan calibration is already running".to_owned(),
+            ));
+        }
+        self.calibrating_changed(&ctxt).await.ok();
+
+        let ctrl = self.clone();
+        let ctxt = ctxt.to_owned();
+        tokio::spawn(async move {
+            for fan in fans {
+                let (sources, max_temp) = {
+                    let config = ctrl.config.lock().await;
+                    (config.temp_sources(fan), config.limits.hot_temp)
+                };
+                info!("Calibrating the {fan:?} fan");
+                match calibrate_fan(fan, &sources, max_temp, &ctrl.calibrating).await {
+                    Ok(calibration) => {
+                        let mut config = ctrl.config.lock().await;
+                        config.set_calibration(calibration);
+                        config.write();
+                    }
+                    Err(e) => {
+                        warn!("Calibration of the {fan:?} fan failed: {e}");
+                        break;
+                    }
+                }
+            }
+            ctrl.restore_curves()
+                .await
+                .unwrap_or_else(|e| warn!("Could not restore fan curves: {e}"));
+            ctrl.calibrating.store(false, Ordering::SeqCst);
+            ctrl.calibrating_changed(&ctxt).await.ok();
+        });
+        Ok(())
+    }
+
+    /// Stop a running calibration. Fans already measured keep their results.
+    async fn cancel_fan_calibration(&self) {
+        self.calibrating.store(false, Ordering::SeqCst);
+    }
+
+    /// The pwm to RPM table of each calibrated fan
+    async fn fan_calibration(&self) -> zbus::fdo::Result<Vec<FanCalibration>> {
+        Ok(self.config.lock().await.calibration.clone())
+    }
+
+    /// Use separate fan curves on battery, which start as a copy of the AC
+    /// curves
+    #[zbus(property)]
+    async fn battery_fan_curves(&self) -> bool {
+        self.config.lock().await.dc_curves_enabled
+    }
+
+    #[zbus(property)]
+    async fn set_battery_fan_curves(&mut self, enabled: bool) -> Result<(), zbus::Error> {
+        let mut config = self.config.lock().await;
+        config.dc_curves_enabled = enabled;
+        if enabled {
+            let FanCurveConfig {
+                profiles,
+                dc_profiles,
+                ..
+            } = &mut *config;
+            dc_profiles.add_missing_from(profiles);
+        }
+        config.write();
+        let on_battery = config.on_battery;
+        drop(config);
+        if on_battery && !self.software.is_running() && !self.calibrating.load(Ordering::SeqCst) {
+            self.restore_curves().await?;
+        }
+        Ok(())
+    }
+
+    /// True while fans are being calibrated
+    #[zbus(property)]
+    async fn calibrating(&self) -> bool {
+        self.calibrating.load(Ordering::SeqCst)
+    }
+
+    /// Run the fans from the software fan curves instead of the firmware. If
+    /// the daemon stalls the fans are returned to the firmware.
+    #[zbus(property)]
+    async fn software_fan_control(&self) -> bool {
+        self.software.is_running()
+    }
+
+    /// The latest RPM, pwm and temperature of each fan. Changes are signalled
+    /// every `TelemetryInterval`.
+    #[zbus(property)]
+    async fn fan_telemetry(&self) -> Vec<FanTelemetry> {
+        self.telemetry.lock().await.clone()
+    }
+
+    /// Milliseconds between fan telemetry samples, 0 to stop sampling
+    #[zbus(property)]
+    async fn telemetry_interval(&self) -> u32 {
+        self.config.lock().await.telemetry_interval_ms
+    }
+
+    #[zbus(property)]
+    async fn set_telemetry_interval(&mut self, interval: u32) -> Result<(), zbus::fdo::Error> {
+        if interval != 0 && interval < MIN_TELEMETRY_INTERVAL_MS {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "Interval must be 0 or at least {MIN_TELEMETRY_INTERVAL_MS}ms"
+            )));
+        }
+        let mut config = self.config.lock().await;
+        config.telemetry_interval_ms = interval;
+        config.write();
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn set_software_fan_control(&mut self, enabled: bool) -> Result<(), zbus::Error> {
+        if enabled && self.calibrating.load(Ordering::SeqCst) {
+            return Err(zbus::fdo::Error::Failed(
+                "Software fan control can't start while fans are calibrated".to_owned(),
+            )
+            .into());
+        }
+        if enabled {
+            let config = self.config.lock().await;
+            self.software.start(&config.software, &config.limits)?;
+        } else {
+            self.stop_software_control().await?;
+        }
+        let mut config = self.config.lock().await;
+        config.software.enabled = enabled;
+        config.write();
+        Ok(())
+    }
 }
 
 impl crate::ZbusRun for CtrlFanCurveZbus {
@@ -232,11 +807,12 @@ impl CtrlTask for CtrlFanCurveZbus {
         FAN_CURVE_ZBUS_PATH
     }
 
-    async fn create_tasks(&self, _signal_ctxt: SignalEmitter<'static>) -> Result<(), RogError> {
+    async fn create_tasks(&self, signal_ctxt: SignalEmitter<'static>) -> Result<(), RogError> {
         let watch_platform_profile = self.platform.monitor_platform_profile()?;
         let platform = self.platform.clone();
         let config = self.config.clone();
         let fan_curves = self.config.clone();
+        let calibrating = self.calibrating.clone();
 
         tokio::spawn(async move {
             let mut buffer = [0; 32];
@@ -252,16 +828,20 @@ impl CtrlTask for CtrlFanCurveZbus {
                             })
                     {
                         if profile != config.lock().await.current {
-                            fan_curves
-                                .lock()
-                                .await
-                                .profiles
-                                .write_profile_curve_to_platform(
-                                    profile,
-                                    &mut find_fan_curve_node().unwrap(),
-                                )
-                                .map_err(|e| warn!("write_profile_curve_to_platform, {}", e))
-                                .ok();
+                            // Calibration writes the curves of the new profile when
+                            // done
+                            if !calibrating.load(Ordering::SeqCst) {
+                                fan_curves
+                                    .lock()
+                                    .await
+                                    .active_profiles()
+                                    .write_profile_curve_to_platform(
+                                        profile,
+                                        &mut find_fan_curve_node().unwrap(),
+                                    )
+                                    .map_err(|e| warn!("write_profile_curve_to_platform, {}", e))
+                                    .ok();
+                            }
                             config.lock().await.current = profile;
                         }
                     }
@@ -269,6 +849,72 @@ impl CtrlTask for CtrlFanCurveZbus {
             }
         });
 
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            let fans = FanCurveProfiles::supported_fans().unwrap_or_default();
+            let mut nodes = TelemetryNodes::default();
+            loop {
+                let interval = ctrl.config.lock().await.telemetry_interval_ms;
+                if interval == 0 {
+                    // Check again later in case it is turned back on
+                    sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                let telemetry = ctrl.read_telemetry(&mut nodes, &fans).await;
+                *ctrl.telemetry.lock().await = telemetry;
+                ctrl.fan_telemetry_changed(&signal_ctxt).await.ok();
+                sleep(Duration::from_millis(
+                    interval.max(MIN_TELEMETRY_INTERVAL_MS) as u64,
+                ))
+                .await;
+            }
+        });
+
+        // Fans are given back to the firmware over suspend and shutdown, and
+        // the curves follow the power source
+        let ctrl1 = self.clone();
+        let ctrl2 = self.clone();
+        let ctrl3 = self.clone();
+        self.create_sys_event_tasks(
+            move |sleeping| {
+                let ctrl = ctrl1.clone();
+                async move {
+                    if sleeping {
+                        ctrl.calibrating.store(false, Ordering::SeqCst);
+                    }
+                    let config = ctrl.config.lock().await;
+                    if !config.software.enabled {
+                        return;
+                    }
+                    if sleeping {
+                        ctrl.software.stop(&config.software);
+                    } else {
+                        ctrl.software
+                            .start(&config.software, &config.limits)
+                            .unwrap_or_else(|e| warn!("Software fan control could not start: {e}"));
+                    }
+                }
+            },
+            move |shutting_down| {
+                let ctrl = ctrl2.clone();
+                async move {
+                    if shutting_down {
+                        ctrl.calibrating.store(false, Ordering::SeqCst);
+                        let config = ctrl.config.lock().await;
+                        ctrl.software.stop(&config.software);
+                    }
+                }
+            },
+            |_| async {},
+            move |power_plugged| {
+                let ctrl = ctrl3.clone();
+                async move {
+                    ctrl.power_source_changed(power_plugged).await;
+                }
+            },
+        )
+        .await;
+
         Ok(())
     }
 }
@@ -280,10 +926,200 @@ impl crate::Reloadable for CtrlFanCurveZbus {
         let mut config = self.config.lock().await;
         if let Ok(mut device) = find_fan_curve_node() {
             config
-                .profiles
+                .active_profiles()
                 .write_profile_curve_to_platform(active, &mut device)?;
         }
+        if config.software.enabled {
+            self.software
+                .start(&config.software, &config.limits)
+                .unwrap_or_else(|e| warn!("Software fan control could not start: {e}"));
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string, write};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use config_traits::StdConfig;
+    use futures_util::lock::Mutex;
+    use rog_platform::platform::{PlatformProfile, RogPlatform};
+    use rog_profiles::fan_curve_set::CurveData;
+    use rog_profiles::FanCurvePU;
+
+    use super::{CtrlFanCurveZbus, FanCurveConfig};
+    use crate::fan_control::SoftwareFanControl;
+
+    fn curve(fan: FanCurvePU, pwm: u8) -> CurveData {
+        CurveData {
+            fan,
+            pwm: [pwm; 8],
+            temp: [
+                30, 40, 50, 60, 70, 80, 90, 100,
+            ],
+            enabled: true,
+        }
+    }
+
+    /// Separate AC and battery curves for the CPU fan in balanced
+    fn test_config() -> FanCurveConfig {
+        let mut config = FanCurveConfig::new();
+        config.profiles.balanced = vec![curve(
+            FanCurvePU::CPU,
+            120,
+        )];
+        config.dc_profiles.balanced = vec![curve(
+            FanCurvePU::CPU,
+            180,
+        )];
+        config.dc_curves_enabled = true;
+        config
+    }
+
+    /// A controller on AC in balanced, with its fan curve node in the test
+    /// sysfs root
+    fn test_ctrl() -> (CtrlFanCurveZbus, PathBuf) {
+        let root = crate::test_sysfs_root();
+        create_dir_all(root.join("class/platform/asus-nb-wmi")).unwrap();
+        create_dir_all(root.join("firmware/acpi")).unwrap();
+        write(root.join("firmware/acpi/platform_profile"), "balanced\n").unwrap();
+        let node = root.join("class/hwmon/hwmon9");
+        create_dir_all(&node).unwrap();
+        write(node.join("name"), "asus_custom_fan_curve\n").unwrap();
+        write(node.join("pwm1_auto_point1_pwm"), "0").unwrap();
+
+        let ctrl = CtrlFanCurveZbus {
+            config: Arc::new(Mutex::new(test_config())),
+            platform: RogPlatform::new().unwrap(),
+            software: SoftwareFanControl::default(),
+            telemetry: Default::default(),
+            calibrating: Arc::new(AtomicBool::new(false)),
+        };
+        (ctrl, node)
+    }
+
+    /// The first CPU fan point written to the node
+    fn written_pwm(node: &Path) -> String {
+        read_to_string(node.join("pwm1_auto_point1_pwm")).unwrap()
+    }
+
+    #[test]
+    fn select_profiles_for_battery() {
+        let mut config = test_config();
+        assert_eq!(config.select_profiles(true).balanced[0].pwm, [120; 8]);
+        assert_eq!(config.select_profiles(false).balanced[0].pwm, [180; 8]);
+        config.on_battery = true;
+        assert_eq!(config.active_profiles().balanced[0].pwm, [180; 8]);
+
+        // Battery uses the AC curves unless it has its own
+        config.dc_curves_enabled = false;
+        assert_eq!(config.select_profiles(false).balanced[0].pwm, [120; 8]);
+        assert_eq!(config.active_profiles().balanced[0].pwm, [120; 8]);
+    }
+
+    #[tokio::test]
+    async fn store_fan_curve_for_battery() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let (ctrl, node) = test_ctrl();
+        let balanced = PlatformProfile::Balanced;
+
+        // On AC a battery curve is stored but not written
+        ctrl.store_fan_curve(balanced, false, curve(FanCurvePU::CPU, 200))
+            .await
+            .unwrap();
+        assert_eq!(written_pwm(&node), "0");
+        let mut config = ctrl.config.lock().await;
+        assert_eq!(config.select_profiles(false).balanced[0].pwm, [200; 8]);
+        assert_eq!(config.select_profiles(true).balanced[0].pwm, [120; 8]);
+        config.on_battery = true;
+        drop(config);
+
+        // On battery it is written
+        ctrl.store_fan_curve(balanced, false, curve(FanCurvePU::CPU, 210))
+            .await
+            .unwrap();
+        assert_eq!(written_pwm(&node), "210");
+
+        // A fan with no curve in the battery set, a curve outside the limits,
+        // and any curve while calibrating are refused
+        assert!(ctrl
+            .store_fan_curve(balanced, false, curve(FanCurvePU::GPU, 200))
+            .await
+            .is_err());
+        assert!(ctrl
+            .store_fan_curve(balanced, false, curve(FanCurvePU::CPU, 0))
+            .await
+            .is_err());
+        ctrl.calibrating.store(true, Ordering::SeqCst);
+        assert!(ctrl
+            .store_fan_curve(balanced, false, curve(FanCurvePU::CPU, 220))
+            .await
+            .is_err());
+        assert_eq!(written_pwm(&node), "210");
+    }
+
+    #[tokio::test]
+    async fn store_fan_curve_battery_disabled() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let (mut ctrl, node) = test_ctrl();
+        let balanced = PlatformProfile::Balanced;
+        {
+            let mut config = ctrl.config.lock().await;
+            config.dc_curves_enabled = false;
+            config.on_battery = true;
+        }
+
+        // A battery curve must not change the AC curve
+        assert!(matches!(
+            ctrl.store_fan_curve(balanced, false, curve(FanCurvePU::CPU, 200))
+                .await,
+            Err(zbus::fdo::Error::InvalidArgs(_))
+        ));
+        assert_eq!(written_pwm(&node), "0");
+        assert_eq!(
+            ctrl.config.lock().await.select_profiles(true).balanced[0].pwm,
+            [120; 8]
+        );
+
+        // The current power source on battery is the AC curves
+        ctrl.set_fan_curve(balanced, curve(FanCurvePU::CPU, 130))
+            .await
+            .unwrap();
+        assert_eq!(written_pwm(&node), "130");
+        let mut config = ctrl.config.lock().await;
+        assert_eq!(config.select_profiles(true).balanced[0].pwm, [130; 8]);
+        assert_eq!(config.dc_profiles.balanced[0].pwm, [180; 8]);
+    }
+
+    #[tokio::test]
+    async fn power_source_change_sets_curves() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let (ctrl, node) = test_ctrl();
+
+        ctrl.power_source_changed(false).await;
+        assert!(ctrl.config.lock().await.on_battery);
+        assert_eq!(written_pwm(&node), "180");
+        ctrl.power_source_changed(true).await;
+        assert_eq!(written_pwm(&node), "120");
+
+        // Held while calibrating, the calibration writes the curves when done
+        ctrl.calibrating.store(true, Ordering::SeqCst);
+        ctrl.power_source_changed(false).await;
+        assert!(ctrl.config.lock().await.on_battery);
+        assert_eq!(written_pwm(&node), "120");
+        ctrl.calibrating.store(false, Ordering::SeqCst);
+        ctrl.restore_curves().await.unwrap();
+        assert_eq!(written_pwm(&node), "180");
+
+        // Nothing to write without battery curves
+        ctrl.config.lock().await.dc_curves_enabled = false;
+        ctrl.power_source_changed(true).await;
+        assert!(!ctrl.config.lock().await.on_battery);
+        assert_eq!(written_pwm(&node), "180");
+    }
+}
@@ -1,22 +1,28 @@
 use std::path::Path;
 use std::process::Command;
 use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::Local;
 use config_traits::StdConfig;
 use futures_util::lock::Mutex;
 use log::{debug, error, info, warn};
-use rog_platform::asus_armoury::{AttrValue, FirmwareAttribute, FirmwareAttributes};
+use rog_platform::asus_armoury::{
+    AttrValue, ChargeMode, FirmwareAttribute, FirmwareAttributes, PowerLimits,
+};
 use rog_platform::cpu::{CPUControl, CPUGovernor, CPUEPP};
 use rog_platform::platform::{PlatformProfile, Properties, RogPlatform};
 use rog_platform::power::AsusPower;
+use tokio::time::sleep;
 use zbus::fdo::Error as FdoErr;
 use zbus::object_server::SignalEmitter;
 use zbus::{interface, Connection};
 
 use crate::asus_armoury::set_config_or_default;
+use crate::charge_schedule::{ChargePlan, MIN_PLUGGED_MINUTES};
 use crate::config::Config;
 use crate::error::RogError;
-use crate::{task_watch_item, CtrlTask, ReloadAndNotify};
+use crate::{CtrlTask, ReloadAndNotify};
 
 const PLATFORM_ZBUS_PATH: &str = "/xyz/ljones";
 
@@ -39,6 +45,9 @@ macro_rules! platform_get_value {
     }
 }
 
+/// How often the charge schedule is checked
+const CHARGE_SCHEDULE_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Clone)]
 pub struct CtrlPlatform {
     power: AsusPower,
@@ -207,6 +216,9 @@ impl CtrlPlatform {
     }
 
     async fn get_config_epp_for_throttle(&self, throttle: PlatformProfile) -> CPUEPP {
+        if let Some(epp) = self.config.lock().await.rule_epp {
+            return epp;
+        }
         match throttle {
             PlatformProfile::Balanced => self.config.lock().await.profile_balanced_epp,
             PlatformProfile::Performance => self.config.lock().await.profile_performance_epp,
@@ -217,6 +229,10 @@ impl CtrlPlatform {
     }
 
     async fn update_policy_ac_or_bat(&self, power_plugged: bool, change_epp: bool) {
+        if self.config.lock().await.rule_profile.is_some() {
+            debug!("Power status changed but a profile rule sets the platform profile");
+            return;
+        }
         if power_plugged && !self.config.lock().await.change_platform_profile_on_ac {
             debug!(
                 "Power status changed but set_platform_profile_on_ac set false. Not setting the \
@@ -242,6 +258,181 @@ impl CtrlPlatform {
         self.platform.set_platform_profile(throttle.into()).ok();
         self.check_and_set_epp(epp, change_epp);
     }
+
+    /// Switch to the platform profile and EPP of the active profile rule. With
+    /// no profile the AC or battery profile is used again.
+    pub(crate) async fn apply_rule_profile(
+        &self,
+        profile: Option<PlatformProfile>,
+        epp: Option<CPUEPP>,
+    ) {
+        {
+            let mut config = self.config.lock().await;
+            config.rule_profile = profile;
+            config.rule_epp = epp;
+        }
+        if !self.platform.has_platform_profile() {
+            return;
+        }
+        let change_epp = self.config.lock().await.platform_profile_linked_epp;
+        if let Some(profile) = profile {
+            self.platform
+                .set_platform_profile(profile.into())
+                .map_err(|e| warn!("Profile rule: platform_profile {e}"))
+                .ok();
+        } else if let Ok(power_plugged) = self.power.get_online() {
+            self.update_policy_ac_or_bat(power_plugged > 0, change_epp)
+                .await;
+        }
+        if let Ok(current) = self.platform.get_platform_profile() {
+            let current_epp = self.get_config_epp_for_throttle(current.into()).await;
+            self.check_and_set_epp(current_epp, change_epp || epp.is_some());
+        }
+    }
+
+    /// Set the charge limit of the active profile rule, or put the configured
+    /// limit back with `None`. The rule's limit is only written to sysfs.
+    pub(crate) async fn apply_rule_charge_limit(&self, limit: Option<u8>) {
+        if !self.power.has_charge_control_end_threshold() {
+            return;
+        }
+        let limit = {
+            let mut config = self.config.lock().await;
+            config.rule_charge_limit = limit;
+            config.charge_limit()
+        };
+        if self.power.get_charge_control_end_threshold().ok() != Some(limit) {
+            self.power
+                .set_charge_control_end_threshold(limit)
+                .map_err(|e| warn!("Profile rule: charge_control_end_threshold {e}"))
+                .ok();
+        }
+    }
+
+    /// Store a charge limit written to sysfs by something other than asusd as
+    /// the configured limit, which also ends a profile rule's limit. The limits
+    /// asusd writes itself are ignored. Returns true if it was stored.
+    async fn charge_limit_written(&self, limit: u8) -> bool {
+        let mut config = self.config.lock().await;
+        if limit == config.charge_limit() {
+            return false;
+        }
+        debug!("charge_control_end_threshold was changed to {limit} externally");
+        config.charge_control_end_threshold = limit;
+        config.rule_charge_limit = None;
+        config.write();
+        true
+    }
+
+    /// The charge schedule's plan from now, empty if neither it nor adaptive
+    /// charging is enabled
+    async fn charge_plan(&self) -> ChargePlan {
+        let schedule = self.config.lock().await.charge_schedule.clone();
+        if !schedule.enabled && !schedule.adaptive {
+            return ChargePlan::default();
+        }
+        let events = if schedule.enabled {
+            schedule.calendar_events()
+        } else {
+            Vec::new()
+        };
+        schedule.plan(Local::now().naive_local(), &events)
+    }
+
+    /// Keep the times the laptop is unplugged after charging for a while, to
+    /// learn when it is usually needed
+    async fn log_power_change(&self, signal_ctxt: &SignalEmitter<'_>, power_plugged: bool) {
+        let now = Local::now().naive_local();
+        let mut config = self.config.lock().await;
+        let plugged_in_at =
+            std::mem::replace(&mut config.plugged_in_at, power_plugged.then_some(now));
+        if power_plugged || !config.charge_schedule.adaptive {
+            return;
+        }
+        if plugged_in_at
+            .is_some_and(|at| now - at >= chrono::Duration::minutes(MIN_PLUGGED_MINUTES))
+        {
+            debug!("Adaptive charging: unplugged at {now}");
+            config.charge_schedule.log_unplug(now);
+            config.write();
+            drop(config);
+            self.learned_charge_targets_changed(signal_ctxt).await.ok();
+        }
+    }
+
+    /// Raise the charge limit while the charge schedule asks for more than the
+    /// configured limit, and put the configured limit back after. The raised
+    /// limit is kept in `schedule_limit`, which the watcher of the sysfs node
+    /// ignores so it is never stored as the configured limit.
+    async fn check_charge_schedule(&self) -> ChargePlan {
+        if !self.power.has_charge_control_end_threshold() {
+            return ChargePlan::default();
+        }
+        let plan = self.charge_plan().await;
+        let mut config = self.config.lock().await;
+        let last = std::mem::replace(&mut config.schedule_limit, plan.limit);
+        let limit = config.charge_limit();
+        if last != plan.limit {
+            info!("Charge schedule: charge limit is now {limit}");
+        }
+        if (plan.limit.is_some() || last.is_some())
+            && self.power.get_charge_control_end_threshold().ok() != Some(limit)
+        {
+            self.power
+                .set_charge_control_end_threshold(limit)
+                .map_err(|e| warn!("Charge schedule: charge_control_end_threshold {e}"))
+                .ok();
+        }
+        plan
+    }
+
+    /// Set the charge mode for AC or battery, `charge_mode_on_ac` is used on AC
+    /// if `change_charge_mode_on_ac` is set
+    async fn update_charge_mode_ac_or_bat(&self, power_plugged: bool) {
+        let Some(attr) = self.attributes.charge_mode() else {
+            return;
+        };
+        let mode = {
+            let config = self.config.lock().await;
+            if power_plugged && config.change_charge_mode_on_ac {
+                config.charge_mode_on_ac
+            } else {
+                config.charge_mode
+            }
+        };
+        if ChargeMode::read(attr).ok() != Some(mode) {
+            debug!("Setting charge_mode to {mode}");
+            mode.write(attr)
+                .map_err(|e| warn!("Platform: charge_mode {e}"))
+                .ok();
+        }
+    }
+
+    /// If AC is plugged in, and the platform profile, which select the tuning
+    /// group
+    fn tuning_key(&self) -> Result<(bool, PlatformProfile), FdoErr> {
+        let power_plugged = self
+            .power
+            .get_online()
+            .map_err(|e| {
+                error!("Could not get power status: {e:?}");
+                e
+            })
+            .unwrap_or_default();
+        let profile: PlatformProfile = self.platform.get_platform_profile()?.into();
+        Ok((power_plugged == 1, profile))
+    }
+
+    /// Use or stop using the PPT tuning group of the current profile
+    pub(crate) async fn apply_rule_ppt_group(
+        &mut self,
+        ctxt: &SignalEmitter<'_>,
+        enabled: bool,
+    ) -> Result<(), FdoErr> {
+        self.set_enable_ppt_group(enabled).await?;
+        self.enable_ppt_group_changed(ctxt).await?;
+        Ok(())
+    }
 }
 
 #[interface(name = "xyz.ljones.Platform")]
@@ -283,6 +474,10 @@ impl CtrlPlatform {
 
         platform_name!(platform_profile, Properties::ThrottlePolicy);
 
+        if self.attributes.charge_mode().is_some() {
+            supported.push(Properties::ChargeMode);
+        }
+
         supported
     }
 
@@ -300,10 +495,76 @@ impl CtrlPlatform {
         self.power.set_charge_control_end_threshold(limit)?;
         self.config.lock().await.charge_control_end_threshold = limit;
         self.config.lock().await.base_charge_control_end_threshold = limit;
+        // Set by hand, so it stays until the next profile rule
+        self.config.lock().await.rule_charge_limit = None;
         self.config.lock().await.write();
+        self.check_charge_schedule().await;
+        Ok(())
+    }
+
+    /// Raise the charge limit ahead of the targets and calendar events of
+    /// `charge_schedule` in `asusd.ron`
+    #[zbus(property)]
+    async fn charge_schedule_enabled(&self) -> bool {
+        self.config.lock().await.charge_schedule.enabled
+    }
+
+    #[zbus(property)]
+    async fn set_charge_schedule_enabled(&mut self, enabled: bool) -> Result<(), FdoErr> {
+        {
+            let mut config = self.config.lock().await;
+            if enabled {
+                config.charge_schedule.check()?;
+            }
+            config.charge_schedule.enabled = enabled;
+            config.write();
+        }
+        self.check_charge_schedule().await;
+        Ok(())
+    }
+
+    /// Learn when the laptop is usually unplugged and raise the charge limit
+    /// before then
+    #[zbus(property)]
+    async fn adaptive_charging(&self) -> bool {
+        self.config.lock().await.charge_schedule.adaptive
+    }
+
+    #[zbus(property)]
+    async fn set_adaptive_charging(&mut self, enabled: bool) -> Result<(), FdoErr> {
+        {
+            let mut config = self.config.lock().await;
+            config.charge_schedule.adaptive = enabled;
+            config.write();
+        }
+        self.check_charge_schedule().await;
         Ok(())
     }
 
+    /// The learned unplug times as `<day> HH:MM`, the charge limit is raised
+    /// ahead of these while adaptive charging is enabled
+    #[zbus(property)]
+    async fn learned_charge_targets(&self) -> Vec<String> {
+        self.config
+            .lock()
+            .await
+            .charge_schedule
+            .learned_targets(Local::now().naive_local())
+            .into_iter()
+            .map(|target| format!("{} {}", target.days, target.time))
+            .collect()
+    }
+
+    /// The next change the charge schedule makes, empty if there is none
+    #[zbus(property)]
+    async fn next_charge_action(&self) -> String {
+        self.charge_plan()
+            .await
+            .next
+            .map(|action| action.to_string())
+            .unwrap_or_default()
+    }
+
     async fn one_shot_full_charge(&self) -> Result<(), FdoErr> {
         let base_limit = std::mem::replace(
             &mut self.config.lock().await.charge_control_end_threshold,
@@ -396,6 +657,76 @@ impl CtrlPlatform {
         }
     }
 
+    /// How the battery is used on AC, from the `charge_mode` firmware
+    /// attribute
+    #[zbus(property)]
+    fn charge_mode(&self) -> Result<ChargeMode, FdoErr> {
+        let attr = self.attributes.charge_mode().ok_or_else(|| {
+            FdoErr::NotSupported("RogPlatform: charge_mode not supported".to_owned())
+        })?;
+        Ok(ChargeMode::read(attr)?)
+    }
+
+    #[zbus(property)]
+    async fn set_charge_mode(&mut self, mode: ChargeMode) -> Result<(), FdoErr> {
+        let attr = self.attributes.charge_mode().ok_or_else(|| {
+            FdoErr::NotSupported("RogPlatform: charge_mode not supported".to_owned())
+        })?;
+        let mut config = self.config.lock().await;
+        // On AC the firmware keeps `charge_mode_on_ac` if it is in use
+        let on_ac = self.power.get_online().is_ok_and(|online| online > 0);
+        if !on_ac || !config.change_charge_mode_on_ac {
+            mode.write(attr)?;
+        }
+        config.charge_mode = mode;
+        config.write();
+        Ok(())
+    }
+
+    /// The charge modes the firmware supports
+    #[zbus(property)]
+    fn charge_mode_choices(&self) -> Vec<ChargeMode> {
+        self.attributes
+            .charge_mode()
+            .map(ChargeMode::choices)
+            .unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    async fn charge_mode_on_ac(&self) -> Result<ChargeMode, FdoErr> {
+        Ok(self.config.lock().await.charge_mode_on_ac)
+    }
+
+    #[zbus(property)]
+    async fn set_charge_mode_on_ac(&mut self, mode: ChargeMode) -> Result<(), FdoErr> {
+        if !self.charge_mode_choices().contains(&mode) {
+            return Err(FdoErr::NotSupported(format!(
+                "RogPlatform: charge_mode {mode} not supported"
+            )));
+        }
+        self.config.lock().await.charge_mode_on_ac = mode;
+        self.config.lock().await.write();
+        if let Ok(power_plugged) = self.power.get_online() {
+            self.update_charge_mode_ac_or_bat(power_plugged > 0).await;
+        }
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn change_charge_mode_on_ac(&self) -> Result<bool, FdoErr> {
+        Ok(self.config.lock().await.change_charge_mode_on_ac)
+    }
+
+    #[zbus(property)]
+    async fn set_change_charge_mode_on_ac(&mut self, change: bool) -> Result<(), FdoErr> {
+        self.config.lock().await.change_charge_mode_on_ac = change;
+        self.config.lock().await.write();
+        if let Ok(power_plugged) = self.power.get_online() {
+            self.update_charge_mode_ac_or_bat(power_plugged > 0).await;
+        }
+        Ok(())
+    }
+
     #[zbus(property)]
     async fn platform_profile_linked_epp(&self) -> Result<bool, FdoErr> {
         Ok(self.config.lock().await.platform_profile_linked_epp)
@@ -515,6 +846,130 @@ impl CtrlPlatform {
         Ok(())
     }
 
+    /// The power limits of the current profile and power state, `-1` for
+    /// those the laptop doesn't have. The PPT limits are those of the tuning
+    /// group while it is enabled, otherwise those currently set.
+    async fn power_limits(&self) -> Result<PowerLimits, FdoErr> {
+        let (power_plugged, profile) = self.tuning_key()?;
+        let mut config = self.config.lock().await;
+        let tuning = config.select_tunings(power_plugged, profile);
+        let mut limits = PowerLimits::default();
+        for attr in self.attributes.attributes() {
+            let name: FirmwareAttribute = attr.name().into();
+            if !PowerLimits::contains(name) {
+                continue;
+            }
+            let value = match (tuning.group.get(&name), attr.current_value()) {
+                (Some(value), _) if tuning.enabled && name.is_ppt() => *value,
+                (_, Ok(AttrValue::Integer(value))) => value,
+                _ => continue,
+            };
+            limits.set(name, value)?;
+        }
+        Ok(limits)
+    }
+
+    /// Apply a set of power limits at once. Each is checked against the
+    /// `min_value`, `max_value` and `scalar_increment` of its attribute, and
+    /// PL1 <= PL2 <= PL3, before any is written. Limits of `-1` are left as
+    /// they are. The PPT limits are kept in the tuning group of the current
+    /// profile and power state, and are only written while that group is
+    /// enabled.
+    async fn apply_power_limits(&self, limits: PowerLimits) -> Result<(), FdoErr> {
+        limits.check(&self.attributes)?;
+        let (power_plugged, profile) = self.tuning_key()?;
+        let mut config = self.config.lock().await;
+        let enabled = config.select_tunings(power_plugged, profile).enabled;
+        let mut write = limits;
+        for (attr, _) in limits.iter() {
+            if attr.is_ppt() && !enabled {
+                write.set(attr, -1)?;
+            }
+        }
+        write.write(&self.attributes)?;
+
+        for (attr, value) in limits.iter() {
+            if attr.is_ppt() {
+                config
+                    .select_tunings(power_plugged, profile)
+                    .group
+                    .insert(attr, value);
+            } else {
+                config.armoury_settings.insert(attr, value);
+            }
+        }
+        config.write();
+        Ok(())
+    }
+
+    /// The names of the power limit presets of the current profile and power
+    /// state
+    async fn power_limit_presets(&self) -> Result<Vec<String>, FdoErr> {
+        let (power_plugged, profile) = self.tuning_key()?;
+        Ok(self
+            .config
+            .lock()
+            .await
+            .select_tunings(power_plugged, profile)
+            .presets
+            .keys()
+            .cloned()
+            .collect())
+    }
+
+    async fn power_limit_preset(&self, name: &str) -> Result<PowerLimits, FdoErr> {
+        let (power_plugged, profile) = self.tuning_key()?;
+        self.config
+            .lock()
+            .await
+            .select_tunings(power_plugged, profile)
+            .presets
+            .get(name)
+            .copied()
+            .ok_or_else(|| FdoErr::InvalidArgs(format!("No power limit preset named {name}")))
+    }
+
+    /// Save a preset for the current profile and power state, replacing one
+    /// of the same name
+    async fn save_power_limit_preset(&self, name: &str, limits: PowerLimits) -> Result<(), FdoErr> {
+        if name.is_empty() || limits.is_empty() {
+            return Err(FdoErr::InvalidArgs(
+                "A power limit preset needs a name and a limit".to_owned(),
+            ));
+        }
+        limits.check(&self.attributes)?;
+        let (power_plugged, profile) = self.tuning_key()?;
+        let mut config = self.config.lock().await;
+        config
+            .select_tunings(power_plugged, profile)
+            .presets
+            .insert(name.to_owned(), limits);
+        config.write();
+        Ok(())
+    }
+
+    async fn apply_power_limit_preset(&self, name: &str) -> Result<(), FdoErr> {
+        let limits = self.power_limit_preset(name).await?;
+        self.apply_power_limits(limits).await
+    }
+
+    async fn remove_power_limit_preset(&self, name: &str) -> Result<(), FdoErr> {
+        let (power_plugged, profile) = self.tuning_key()?;
+        let mut config = self.config.lock().await;
+        if config
+            .select_tunings(power_plugged, profile)
+            .presets
+            .remove(name)
+            .is_none()
+        {
+            return Err(FdoErr::InvalidArgs(format!(
+                "No power limit preset named {name}"
+            )));
+        }
+        config.write();
+        Ok(())
+    }
+
     /// Set if the PPT tuning group for the current profile is enabled
     #[zbus(property)]
     async fn enable_ppt_group(&self) -> Result<bool, FdoErr> {
@@ -611,9 +1066,15 @@ impl ReloadAndNotify for CtrlPlatform {
     async fn reload_and_notify(
         &mut self,
         signal_context: &SignalEmitter<'static>,
-        data: Self::Data,
+        mut data: Self::Data,
     ) -> Result<(), RogError> {
         let mut config = self.config.lock().await;
+        // Not stored in the file
+        data.rule_profile = config.rule_profile;
+        data.rule_epp = config.rule_epp;
+        data.rule_charge_limit = config.rule_charge_limit;
+        data.schedule_limit = config.schedule_limit;
+        data.plugged_in_at = config.plugged_in_at;
         if *config != data {
             info!("asusd.ron updated externally, reloading and updating internal copy");
 
@@ -649,9 +1110,21 @@ impl ReloadAndNotify for CtrlPlatform {
             }
             // reload_and_notify!(platform_profile, "platform_profile");
 
+            let charge_mode_changed = config.charge_mode != data.charge_mode
+                || config.charge_mode_on_ac != data.charge_mode_on_ac
+                || config.change_charge_mode_on_ac != data.change_charge_mode_on_ac;
+
             *config = data;
             config.base_charge_control_end_threshold =
                 base_charge_control_end_threshold.unwrap_or_default();
+            drop(config);
+
+            if charge_mode_changed {
+                if let Ok(power_plugged) = self.power.get_online() {
+                    self.update_charge_mode_ac_or_bat(power_plugged > 0).await;
+                    self.charge_mode_changed(signal_context).await.ok();
+                }
+            }
         }
         Ok(())
     }
@@ -662,20 +1135,28 @@ impl crate::Reloadable for CtrlPlatform {
         info!("Begin Platform settings restore");
         if self.power.has_charge_control_end_threshold() {
             // self.restore_charge_limit().await;
-            let limit = self.config.lock().await.charge_control_end_threshold;
+            let limit = self.config.lock().await.charge_limit();
             info!("reloading charge_control_end_threshold to {limit}");
             self.power.set_charge_control_end_threshold(limit)?;
+            self.check_charge_schedule().await;
         } else {
             warn!("No charge_control_end_threshold found")
         }
 
         if let Ok(power_plugged) = self.power.get_online() {
-            self.config.lock().await.last_power_plugged = power_plugged;
+            {
+                let mut config = self.config.lock().await;
+                config.last_power_plugged = power_plugged;
+                if power_plugged > 0 && config.plugged_in_at.is_none() {
+                    config.plugged_in_at = Some(Local::now().naive_local());
+                }
+            }
             if self.platform.has_platform_profile() {
                 let change_epp = self.config.lock().await.platform_profile_linked_epp;
                 self.update_policy_ac_or_bat(power_plugged > 0, change_epp)
                     .await;
             }
+            self.update_charge_mode_ac_or_bat(power_plugged > 0).await;
             self.run_ac_or_bat_cmd(power_plugged > 0).await;
         }
 
@@ -684,7 +1165,41 @@ impl crate::Reloadable for CtrlPlatform {
 }
 
 impl CtrlPlatform {
-    task_watch_item!(charge_control_end_threshold "charge_control_end_threshold" power);
+    /// Keep a charge limit set outside of asusd, see `charge_limit_written()`
+    async fn watch_charge_control_end_threshold(
+        &self,
+        signal_ctxt: SignalEmitter<'static>,
+    ) -> Result<(), RogError> {
+        use futures_util::StreamExt;
+
+        let ctrl = self.clone();
+        match self.power.monitor_charge_control_end_threshold() {
+            Ok(watch) => {
+                tokio::spawn(async move {
+                    let mut buffer = [0; 32];
+                    watch
+                        .into_event_stream(&mut buffer)
+                        .unwrap()
+                        .for_each(|_| async {
+                            if let Ok(limit) = ctrl.power.get_charge_control_end_threshold() {
+                                if ctrl.charge_limit_written(limit).await {
+                                    ctrl.charge_control_end_threshold_changed(&signal_ctxt)
+                                        .await
+                                        .ok();
+                                }
+                            }
+                        })
+                        .await;
+                });
+            }
+            Err(e) => info!(
+                "inotify watch failed: {}. You can ignore this if your device does not support \
+                 the feature",
+                e
+            ),
+        }
+        Ok(())
+    }
 }
 
 impl CtrlTask for CtrlPlatform {
@@ -714,9 +1229,10 @@ impl CtrlTask for CtrlPlatform {
                         platform1
                             .power
                             .set_charge_control_end_threshold(
-                                platform1.config.lock().await.charge_control_end_threshold,
+                                platform1.config.lock().await.charge_limit(),
                             )
                             .ok();
+                        platform1.check_charge_schedule().await;
                     }
                     if let Ok(power_plugged) = platform1.power.get_online() {
                         if platform1.config.lock().await.last_power_plugged != power_plugged {
@@ -728,6 +1244,9 @@ impl CtrlTask for CtrlPlatform {
                                     .await;
                             }
                             if !sleeping {
+                                platform1
+                                    .update_charge_mode_ac_or_bat(power_plugged > 0)
+                                    .await;
                                 platform1.run_ac_or_bat_cmd(power_plugged > 0).await;
                             }
                             platform1.config.lock().await.last_power_plugged = power_plugged;
@@ -773,7 +1292,12 @@ impl CtrlTask for CtrlPlatform {
                             .update_policy_ac_or_bat(power_plugged, change_epp)
                             .await;
                     }
+                    platform3.update_charge_mode_ac_or_bat(power_plugged).await;
+                    platform3.charge_mode_changed(&signal_ctxt_copy).await.ok();
                     platform3.run_ac_or_bat_cmd(power_plugged).await;
+                    platform3
+                        .log_power_change(&signal_ctxt_copy, power_plugged)
+                        .await;
                     // In case one-shot charge was used, restore the old charge limit
                     if platform3.power.has_charge_control_end_threshold() && !power_plugged {
                         platform3.restore_charge_limit().await;
@@ -811,6 +1335,23 @@ impl CtrlTask for CtrlPlatform {
         self.watch_charge_control_end_threshold(signal_ctxt_copy.clone())
             .await?;
 
+        if self.power.has_charge_control_end_threshold() {
+            let ctrl = self.clone();
+            let signal_ctxt = signal_ctxt_copy.clone();
+            tokio::spawn(async move {
+                let mut next_action = String::new();
+                loop {
+                    let plan = ctrl.check_charge_schedule().await;
+                    let action = plan.next.map(|a| a.to_string()).unwrap_or_default();
+                    if action != next_action {
+                        next_action = action;
+                        ctrl.next_charge_action_changed(&signal_ctxt).await.ok();
+                    }
+                    sleep(CHARGE_SCHEDULE_INTERVAL).await;
+                }
+            });
+        }
+
         let watch_platform_profile = self.platform.monitor_platform_profile()?;
         let ctrl = self.clone();
 
@@ -860,3 +1401,185 @@ impl CtrlTask for CtrlPlatform {
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use chrono::Local;
+    use config_traits::StdConfig;
+    use futures_util::lock::Mutex;
+    use rog_platform::asus_armoury::{ChargeMode, FirmwareAttribute, FirmwareAttributes};
+    use rog_platform::platform::{PlatformProfile, RogPlatform};
+    use rog_platform::power::AsusPower;
+
+    use super::CtrlPlatform;
+    use crate::charge_schedule::ChargeTarget;
+    use crate::config::Config;
+
+    /// A controller on AC with a charge limit of 80 and the bypass charge mode,
+    /// and the path of the battery's charge limit node
+    fn test_ctrl() -> (CtrlPlatform, PathBuf) {
+        let mut config = Config::new();
+        config.charge_control_end_threshold = 80;
+        test_ctrl_with(Arc::new(Mutex::new(config)))
+    }
+
+    /// As `test_ctrl()` with the config shared with other controllers, the
+    /// charge limit in it should be 80. The platform profile is balanced.
+    pub(crate) fn test_ctrl_with(config: Arc<Mutex<Config>>) -> (CtrlPlatform, PathBuf) {
+        let root = crate::test_sysfs_root();
+        create_dir_all(root.join("class/platform/asus-nb-wmi")).unwrap();
+        create_dir_all(root.join("firmware/acpi")).unwrap();
+        write(root.join("firmware/acpi/platform_profile"), "balanced\n").unwrap();
+        let mains = root.join("class/power_supply/AC0");
+        create_dir_all(&mains).unwrap();
+        write(mains.join("type"), "Mains\n").unwrap();
+        write(mains.join("online"), "1\n").unwrap();
+        let battery = root.join("class/power_supply/BAT0");
+        create_dir_all(&battery).unwrap();
+        write(battery.join("type"), "Battery\n").unwrap();
+        let node = battery.join("charge_control_end_threshold");
+        write(&node, "80\n").unwrap();
+        let charge_mode =
+            root.join("class/firmware-attributes/asus-armoury/attributes/charge_mode");
+        create_dir_all(&charge_mode).unwrap();
+        write(charge_mode.join("current_value"), "1\n").unwrap();
+
+        let ctrl = CtrlPlatform {
+            power: AsusPower::new().unwrap(),
+            platform: RogPlatform::new().unwrap(),
+            attributes: FirmwareAttributes::new(),
+            cpu_control: None,
+            config,
+        };
+        (ctrl, node)
+    }
+
+    fn written_limit(node: &PathBuf) -> u8 {
+        read_to_string(node).unwrap().trim().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn rule_charge_limit_is_not_stored() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let (ctrl, node) = test_ctrl();
+
+        ctrl.apply_rule_charge_limit(Some(60)).await;
+        assert_eq!(written_limit(&node), 60);
+        // The watcher sees the rule's limit, which must not become the
+        // configured limit
+        assert!(!ctrl.charge_limit_written(60).await);
+        assert_eq!(ctrl.config.lock().await.charge_control_end_threshold, 80);
+
+        ctrl.apply_rule_charge_limit(None).await;
+        assert_eq!(written_limit(&node), 80);
+        assert_eq!(ctrl.config.lock().await.charge_control_end_threshold, 80);
+
+        // A limit set outside of asusd is kept, and ends the rule's limit
+        ctrl.apply_rule_charge_limit(Some(60)).await;
+        assert!(ctrl.charge_limit_written(70).await);
+        let config = ctrl.config.lock().await;
+        assert_eq!(config.charge_control_end_threshold, 70);
+        assert_eq!(config.charge_limit(), 70);
+    }
+
+    #[tokio::test]
+    async fn schedule_limit_is_not_stored() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let (ctrl, node) = test_ctrl();
+
+        // A target an hour from now is inside its lead time
+        let time = (Local::now() + chrono::Duration::hours(1)).format("%H:%M");
+        {
+            let mut config = ctrl.config.lock().await;
+            config.charge_schedule.enabled = true;
+            config.charge_schedule.targets = vec![
+                ChargeTarget {
+                    days: "daily".to_owned(),
+                    time: time.to_string(),
+                    limit: 100,
+                },
+            ];
+        }
+        ctrl.check_charge_schedule().await;
+        assert_eq!(written_limit(&node), 100);
+        // The watcher sees the raised limit
+        assert!(!ctrl.charge_limit_written(100).await);
+        assert_eq!(ctrl.config.lock().await.charge_control_end_threshold, 80);
+
+        ctrl.config.lock().await.charge_schedule.targets.clear();
+        ctrl.check_charge_schedule().await;
+        assert_eq!(written_limit(&node), 80);
+        assert_eq!(ctrl.config.lock().await.charge_control_end_threshold, 80);
+    }
+
+    #[tokio::test]
+    async fn charge_mode_kept_on_ac() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let (mut ctrl, _) = test_ctrl();
+        let root = crate::test_sysfs_root();
+        let mode = root.join("class/firmware-attributes/asus-armoury/attributes/charge_mode");
+        let written_mode = || read_to_string(mode.join("current_value")).unwrap();
+        {
+            let mut config = ctrl.config.lock().await;
+            config.change_charge_mode_on_ac = true;
+            config.charge_mode_on_ac = ChargeMode::Bypass;
+        }
+
+        // The mode for AC stays while on AC
+        ctrl.set_charge_mode(ChargeMode::Normal).await.unwrap();
+        assert_eq!(written_mode().trim(), "1");
+        assert_eq!(ctrl.config.lock().await.charge_mode, ChargeMode::Normal);
+
+        ctrl.config.lock().await.change_charge_mode_on_ac = false;
+        ctrl.set_charge_mode(ChargeMode::AcOnly).await.unwrap();
+        assert_eq!(written_mode().trim(), "2");
+
+        // On battery
+        ctrl.config.lock().await.change_charge_mode_on_ac = true;
+        write(root.join("class/power_supply/AC0/online"), "0\n").unwrap();
+        ctrl.set_charge_mode(ChargeMode::Normal).await.unwrap();
+        assert_eq!(written_mode().trim(), "0");
+    }
+
+    #[tokio::test]
+    async fn power_limits_of_disabled_group() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let root = crate::test_sysfs_root();
+        let attrs = root.join("class/firmware-attributes/asus-armoury/attributes");
+        for (name, value) in [
+            ("ppt_pl1_spl", "45\n"),
+            ("nv_temp_target", "87\n"),
+        ] {
+            create_dir_all(attrs.join(name)).unwrap();
+            write(attrs.join(name).join("current_value"), value).unwrap();
+        }
+        let (ctrl, _) = test_ctrl();
+        ctrl.config
+            .lock()
+            .await
+            .select_tunings(true, PlatformProfile::Balanced)
+            .group
+            .insert(FirmwareAttribute::PptPl1Spl, 60);
+
+        // The group isn't applied, so the limits are those currently set
+        let limits = ctrl.power_limits().await.unwrap();
+        assert_eq!(limits.ppt_pl1_spl, 45);
+        assert_eq!(limits.nv_temp_target, 87);
+
+        ctrl.config
+            .lock()
+            .await
+            .select_tunings(true, PlatformProfile::Balanced)
+            .enabled = true;
+        let limits = ctrl.power_limits().await.unwrap();
+        assert_eq!(limits.ppt_pl1_spl, 60);
+        assert_eq!(limits.nv_temp_target, 87);
+
+        remove_dir_all(attrs.join("ppt_pl1_spl")).unwrap();
+        remove_dir_all(attrs.join("nv_temp_target")).unwrap();
+    }
+}
@@ -0,0 +1,518 @@
+//! Switch the platform profile and related settings by rules. The rules are
+//! checked every `RULE_CHECK_INTERVAL` and when the power source, lid or sleep
+//! state changes. Actions are only applied when the active rule changes, so a
+//! setting changed by hand stays until the next rule takes over. The profile,
+//! EPP and charge limit go back to their usual settings when a rule ends, the
+//! PPT tuning group, fan curves and keyboard brightness stay as it left them.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Timelike;
+use config_traits::StdConfig;
+use futures_util::lock::Mutex;
+use log::{info, warn};
+use logind_zbus::manager::ManagerProxy;
+use rog_platform::keyboard_led::KeyboardBacklight;
+use rog_platform::platform::{PlatformProfile, RogPlatform};
+use rog_platform::power::AsusPower;
+use rog_platform::profile_rules::{
+    active_rule, running_processes, ProfileRule, RuleAction, RuleState,
+};
+use rog_platform::sysfs::SysfsRoot;
+use tokio::time::sleep;
+use zbus::fdo::Error as FdoErr;
+use zbus::object_server::SignalEmitter;
+use zbus::proxy::CacheProperties;
+use zbus::{interface, Connection};
+
+use crate::config::Config;
+use crate::ctrl_fancurves::{CtrlFanCurveZbus, FAN_CURVE_ZBUS_PATH};
+use crate::ctrl_platform::CtrlPlatform;
+use crate::error::RogError;
+use crate::CtrlTask;
+
+const RULES_ZBUS_PATH: &str = "/xyz/ljones";
+/// How often the time, battery, display and process conditions are checked
+const RULE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// DRM connectors of the laptop's own panel
+const INTERNAL_CONNECTORS: [&str; 3] = [
+    "eDP", "LVDS", "DSI",
+];
+
+/// Whether a DRM connector other than the laptop panel is connected
+fn external_display_connected() -> bool {
+    let Ok(devices) = SysfsRoot::get().scan_subsystem("drm") else {
+        return false;
+    };
+    devices.iter().any(|device| {
+        let name = device.sysname().to_string_lossy();
+        // Connectors are card<N>-<type>-<M>, the cards themselves have no status
+        name.contains('-')
+            && !INTERNAL_CONNECTORS.iter().any(|c| name.contains(c))
+            && device
+                .attribute_value("status")
+                .is_some_and(|s| s.to_string_lossy().trim() == "connected")
+    })
+}
+
+#[derive(Clone)]
+pub struct CtrlProfileRules {
+    ctrl_platform: CtrlPlatform,
+    platform: RogPlatform,
+    power: AsusPower,
+    /// Kept up to date by the lid hook
+    lid_closed: Arc<AtomicBool>,
+    config: Arc<Mutex<Config>>,
+    /// The rule whose actions were last applied
+    active: Arc<Mutex<Option<ProfileRule>>>,
+}
+
+impl CtrlProfileRules {
+    pub async fn new(
+        ctrl_platform: CtrlPlatform,
+        config: Arc<Mutex<Config>>,
+        connection: &Connection,
+    ) -> Result<Self, RogError> {
+        let manager = ManagerProxy::builder(connection)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        let lid_closed = manager.lid_closed().await.unwrap_or_default();
+        Ok(Self {
+            ctrl_platform,
+            platform: RogPlatform::new()?,
+            power: AsusPower::new()?,
+            lid_closed: Arc::new(AtomicBool::new(lid_closed)),
+            config,
+            active: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn read_state(&self) -> RuleState {
+        let now = chrono::Local::now();
+        RuleState {
+            power_plugged: self.power.get_online().map_or(true, |online| online > 0),
+            battery: self.power.get_capacity().unwrap_or(100),
+            minute: (now.hour() * 60 + now.minute()) as u16,
+            lid_closed: self.lid_closed.load(Ordering::SeqCst),
+            external_display: external_display_connected(),
+            processes: running_processes(),
+        }
+    }
+
+    /// Apply the actions of the rule which should be active, if it changed
+    async fn evaluate(&self, ctxt: &SignalEmitter<'_>) {
+        // Reading the state scans every process, skip it without rules
+        let next = if self.config.lock().await.profile_rules.is_empty() {
+            None
+        } else {
+            self.next_rule(&self.read_state()).await
+        };
+        let mut active = self.active.lock().await;
+        if !self.switch_rule(&mut active, next).await {
+            return;
+        }
+        self.apply_bus_actions(active.as_ref(), ctxt).await;
+        drop(active);
+        self.active_rule_changed(ctxt).await.ok();
+    }
+
+    /// The rule which should be active in the state
+    async fn next_rule(&self, state: &RuleState) -> Option<ProfileRule> {
+        active_rule(&self.config.lock().await.profile_rules, state).cloned()
+    }
+
+    /// Make `next` the active rule and apply its actions, other than those
+    /// applied through D-Bus by `apply_bus_actions()`. Returns false if it was
+    /// already the active rule.
+    async fn switch_rule(
+        &self,
+        active: &mut Option<ProfileRule>,
+        next: Option<ProfileRule>,
+    ) -> bool {
+        if *active == next {
+            return false;
+        }
+        match &next {
+            Some(rule) => info!("Profile rule {} is active", rule.name),
+            None => info!("No profile rule is active"),
+        }
+        self.apply(active.as_ref(), next.as_ref()).await;
+        *active = next;
+        true
+    }
+
+    /// The profile, EPP and charge limit go back to their usual settings when
+    /// the previous rule set them and the next doesn't. The PPT tuning group,
+    /// fan curves and keyboard brightness aren't restored, they stay as the
+    /// last rule to set them left them
+    async fn apply(&self, last: Option<&ProfileRule>, next: Option<&ProfileRule>) {
+        let profile = next.and_then(|r| r.profile());
+        let epp = next.and_then(|r| r.epp());
+        if profile.is_some()
+            || epp.is_some()
+            || last.is_some_and(|r| r.profile().is_some() || r.epp().is_some())
+        {
+            self.ctrl_platform.apply_rule_profile(profile, epp).await;
+        }
+
+        let limit = next.and_then(|r| r.charge_limit());
+        if limit.is_some() || last.is_some_and(|r| r.charge_limit().is_some()) {
+            self.ctrl_platform.apply_rule_charge_limit(limit).await;
+        }
+
+        for action in next.map(|r| r.actions.as_slice()).unwrap_or_default() {
+            if let RuleAction::KbdBrightness(level) = action {
+                if let Err(e) = KeyboardBacklight::new().and_then(|k| k.set_brightness(*level)) {
+                    warn!("Profile rule: keyboard brightness {e}");
+                }
+            }
+        }
+    }
+
+    /// The actions of the rule which go through the other controllers on
+    /// D-Bus
+    async fn apply_bus_actions(&self, next: Option<&ProfileRule>, ctxt: &SignalEmitter<'_>) {
+        for action in next.map(|r| r.actions.as_slice()).unwrap_or_default() {
+            match action {
+                RuleAction::PptGroup(enabled) => {
+                    if let Err(e) = self
+                        .ctrl_platform
+                        .clone()
+                        .apply_rule_ppt_group(ctxt, *enabled)
+                        .await
+                    {
+                        warn!("Profile rule: PPT tuning group {e}");
+                    }
+                }
+                RuleAction::FanCurves(enabled) => {
+                    if let Err(e) = self.enable_fan_curves(ctxt, *enabled).await {
+                        warn!("Profile rule: fan curves {e}");
+                    }
+                }
+                // Applied by `apply()`
+                RuleAction::Profile(_)
+                | RuleAction::Epp(_)
+                | RuleAction::ChargeLimit(_)
+                | RuleAction::KbdBrightness(_) => {}
+            }
+        }
+    }
+
+    /// Enable the fan curves of the current profile, through the fan curve
+    /// controller if this laptop has one
+    async fn enable_fan_curves(
+        &self,
+        ctxt: &SignalEmitter<'_>,
+        enabled: bool,
+    ) -> Result<(), RogError> {
+        let profile: PlatformProfile = self.platform.get_platform_profile()?.into();
+        let fan_curves = ctxt
+            .connection()
+            .object_server()
+            .interface::<_, CtrlFanCurveZbus>(FAN_CURVE_ZBUS_PATH)
+            .await?;
+        let fan_curves = fan_curves.get().await;
+        fan_curves.enable_profile_curves(profile, enabled).await
+    }
+}
+
+#[interface(name = "xyz.ljones.ProfileRules")]
+impl CtrlProfileRules {
+    /// All rules, the active one is the highest priority of those matching
+    async fn rules(&self) -> Vec<ProfileRule> {
+        self.config.lock().await.profile_rules.clone()
+    }
+
+    /// Add a rule, replacing any rule of the same name
+    async fn add_rule(
+        &self,
+        #[zbus(signal_emitter)] ctxt: SignalEmitter<'_>,
+        rule: ProfileRule,
+    ) -> Result<(), FdoErr> {
+        rule.check()
+            .map_err(|e| FdoErr::InvalidArgs(e.to_string()))?;
+        {
+            let mut config = self.config.lock().await;
+            if let Some(existing) = config
+                .profile_rules
+                .iter_mut()
+                .find(|r| r.name == rule.name)
+            {
+                *existing = rule;
+            } else {
+                config.profile_rules.push(rule);
+            }
+            config.write();
+        }
+        self.evaluate(&ctxt).await;
+        Ok(())
+    }
+
+    /// Remove a rule by name
+    async fn remove_rule(
+        &self,
+        #[zbus(signal_emitter)] ctxt: SignalEmitter<'_>,
+        name: String,
+    ) -> Result<(), FdoErr> {
+        {
+            let mut config = self.config.lock().await;
+            let count = config.profile_rules.len();
+            config.profile_rules.retain(|r| r.name != name);
+            if config.profile_rules.len() == count {
+                return Err(FdoErr::InvalidArgs(format!(
+                    "There is no profile rule named {name}"
+                )));
+            }
+            config.write();
+        }
+        self.evaluate(&ctxt).await;
+        Ok(())
+    }
+
+    /// The name of the rule whose actions are applied, empty if there is none
+    #[zbus(property)]
+    async fn active_rule(&self) -> String {
+        self.active
+            .lock()
+            .await
+            .as_ref()
+            .map(|r| r.name.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl crate::ZbusRun for CtrlProfileRules {
+    async fn add_to_server(self, server: &mut Connection) {
+        Self::add_to_server_helper(self, RULES_ZBUS_PATH, server).await;
+    }
+}
+
+impl crate::Reloadable for CtrlProfileRules {
+    async fn reload(&mut self) -> Result<(), RogError> {
+        // The rules are checked once the tasks start
+        Ok(())
+    }
+}
+
+impl CtrlTask for CtrlProfileRules {
+    fn zbus_path() -> &'static str {
+        RULES_ZBUS_PATH
+    }
+
+    async fn create_tasks(&self, signal_ctxt: SignalEmitter<'static>) -> Result<(), RogError> {
+        let rules1 = self.clone();
+        let rules2 = self.clone();
+        let rules3 = self.clone();
+        let signal_ctxt1 = signal_ctxt.clone();
+        let signal_ctxt2 = signal_ctxt.clone();
+        let signal_ctxt3 = signal_ctxt.clone();
+        self.create_sys_event_tasks(
+            move |sleeping| {
+                let rules = rules1.clone();
+                let signal_ctxt = signal_ctxt1.clone();
+                async move {
+                    if !sleeping {
+                        rules.evaluate(&signal_ctxt).await;
+                    }
+                }
+            },
+            move |_shutting_down| async move {},
+            move |lid_closed| {
+                let rules = rules2.clone();
+                let signal_ctxt = signal_ctxt2.clone();
+                async move {
+                    rules.lid_closed.store(lid_closed, Ordering::SeqCst);
+                    rules.evaluate(&signal_ctxt).await;
+                }
+            },
+            move |_power_plugged| {
+                let rules = rules3.clone();
+                let signal_ctxt = signal_ctxt3.clone();
+                async move {
+                    rules.evaluate(&signal_ctxt).await;
+                }
+            },
+        )
+        .await;
+
+        let rules = self.clone();
+        tokio::spawn(async move {
+            loop {
+                rules.evaluate(&signal_ctxt).await;
+                sleep(RULE_CHECK_INTERVAL).await;
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, write};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use config_traits::StdConfig;
+    use futures_util::lock::Mutex;
+    use rog_platform::cpu::CPUEPP;
+    use rog_platform::platform::{PlatformProfile, RogPlatform};
+    use rog_platform::power::AsusPower;
+    use rog_platform::profile_rules::{ProfileRule, RuleState};
+
+    use super::CtrlProfileRules;
+    use crate::config::Config;
+    use crate::ctrl_platform::tests::test_ctrl_with;
+
+    fn rule(name: &str, priority: u32, conditions: &[&str], actions: &[&str]) -> ProfileRule {
+        ProfileRule {
+            name: name.to_owned(),
+            priority,
+            enabled: true,
+            conditions: conditions.iter().map(|c| c.parse().unwrap()).collect(),
+            actions: actions.iter().map(|a| a.parse().unwrap()).collect(),
+        }
+    }
+
+    /// A controller on AC in balanced with a charge limit of 80, and the path
+    /// of the battery's charge limit node
+    fn test_rules(profile_rules: Vec<ProfileRule>) -> (CtrlProfileRules, PathBuf) {
+        let mut config = Config::new();
+        config.charge_control_end_threshold = 80;
+        config.platform_profile_on_ac = PlatformProfile::Balanced;
+        config.change_platform_profile_on_ac = true;
+        config.profile_rules = profile_rules;
+        let config = Arc::new(Mutex::new(config));
+        let (ctrl_platform, node) = test_ctrl_with(config.clone());
+        let rules = CtrlProfileRules {
+            ctrl_platform,
+            platform: RogPlatform::new().unwrap(),
+            power: AsusPower::new().unwrap(),
+            lid_closed: Arc::new(AtomicBool::new(false)),
+            config,
+            active: Arc::new(Mutex::new(None)),
+        };
+        (rules, node)
+    }
+
+    fn platform_profile() -> String {
+        let path = crate::test_sysfs_root().join("firmware/acpi/platform_profile");
+        read_to_string(path).unwrap().trim().to_owned()
+    }
+
+    fn written_limit(node: &Path) -> u8 {
+        read_to_string(node).unwrap().trim().parse().unwrap()
+    }
+
+    fn on_ac(battery: u8, processes: &[&str]) -> RuleState {
+        RuleState {
+            power_plugged: true,
+            battery,
+            processes: processes.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Switch to the rule for the state as `evaluate()` does, true if the
+    /// active rule changed
+    async fn update(rules: &CtrlProfileRules, state: &RuleState) -> bool {
+        let next = rules.next_rule(state).await;
+        let mut active = rules.active.lock().await;
+        rules.switch_rule(&mut active, next).await
+    }
+
+    async fn active_name(rules: &CtrlProfileRules) -> Option<String> {
+        rules.active.lock().await.as_ref().map(|r| r.name.clone())
+    }
+
+    #[tokio::test]
+    async fn rules_by_priority() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let mut disabled = rule("disabled", 30, &["power=ac"], &["profile=low-power"]);
+        disabled.enabled = false;
+        let (rules, _) = test_rules(vec![
+            disabled,
+            rule("plugged", 10, &["power=ac"], &["profile=quiet"]),
+            rule(
+                "gaming",
+                20,
+                &[
+                    "power=ac", "process=steam",
+                ],
+                &["profile=performance"],
+            ),
+        ]);
+
+        // The disabled rule is skipped though it has the highest priority
+        assert!(update(&rules, &on_ac(100, &[])).await);
+        assert_eq!(active_name(&rules).await.as_deref(), Some("plugged"));
+        assert_eq!(platform_profile(), "quiet");
+
+        assert!(update(&rules, &on_ac(100, &["bash", "steam"])).await);
+        assert_eq!(active_name(&rules).await.as_deref(), Some("gaming"));
+        assert_eq!(platform_profile(), "performance");
+
+        assert!(update(&rules, &on_ac(100, &["bash"])).await);
+        assert_eq!(active_name(&rules).await.as_deref(), Some("plugged"));
+        assert_eq!(platform_profile(), "quiet");
+    }
+
+    #[tokio::test]
+    async fn rules_restore_when_ended() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let (rules, node) = test_rules(vec![rule(
+            "low-battery",
+            10,
+            &["battery<50"],
+            &[
+                "profile=quiet", "epp=balance_power", "charge-limit=60",
+            ],
+        )]);
+
+        assert!(update(&rules, &on_ac(40, &[])).await);
+        assert_eq!(platform_profile(), "quiet");
+        assert_eq!(written_limit(&node), 60);
+        {
+            let config = rules.config.lock().await;
+            assert_eq!(config.rule_profile, Some(PlatformProfile::Quiet));
+            assert_eq!(config.rule_epp, Some(CPUEPP::BalancePower));
+        }
+
+        // The usual profile, EPP and charge limit come back
+        assert!(update(&rules, &on_ac(90, &[])).await);
+        assert_eq!(active_name(&rules).await, None);
+        assert_eq!(platform_profile(), "balanced");
+        assert_eq!(written_limit(&node), 80);
+        let config = rules.config.lock().await;
+        assert_eq!(config.rule_profile, None);
+        assert_eq!(config.rule_epp, None);
+        assert_eq!(config.rule_charge_limit, None);
+        assert_eq!(config.charge_control_end_threshold, 80);
+    }
+
+    #[tokio::test]
+    async fn rules_not_reapplied() {
+        let _lock = crate::TEST_SYSFS_LOCK.lock().await;
+        let (rules, _) = test_rules(vec![rule(
+            "plugged",
+            10,
+            &["power=ac"],
+            &["profile=quiet"],
+        )]);
+
+        assert!(update(&rules, &on_ac(100, &[])).await);
+        assert_eq!(platform_profile(), "quiet");
+
+        // A profile set by hand stays while the same rule is active
+        write(
+            crate::test_sysfs_root().join("firmware/acpi/platform_profile"),
+            "performance\n",
+        )
+        .unwrap();
+        assert!(!update(&rules, &on_ac(100, &["steam"])).await);
+        assert_eq!(platform_profile(), "performance");
+    }
+}
@@ -7,8 +7,10 @@ use asusd::asus_armoury::start_attributes_zbus;
 use asusd::aura_manager::DeviceManager;
 use asusd::config::Config;
 use asusd::ctrl_backlight::CtrlBacklight;
+use asusd::ctrl_battery::CtrlBattery;
 use asusd::ctrl_fancurves::CtrlFanCurveZbus;
 use asusd::ctrl_platform::CtrlPlatform;
+use asusd::ctrl_profile_rules::CtrlProfileRules;
 use asusd::{print_board_info, start_tasks, CtrlTask, ZbusRun, DBUS_NAME};
 use config_traits::{StdConfig, StdConfigLoad2};
 use futures_util::lock::Mutex;
@@ -16,6 +18,8 @@ use log::{error, info};
 use rog_platform::asus_armoury::FirmwareAttributes;
 use rog_platform::platform::RogPlatform;
 use rog_platform::power::AsusPower;
+use rog_platform::sysfs::{SysfsRoot, SYSFS_ROOT_ENV};
+use tokio::signal::unix::{signal, SignalKind};
 use zbus::fdo::ObjectManager;
 
 #[tokio::main]
@@ -58,6 +62,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 async fn start_daemon() -> Result<(), Box<dyn Error>> {
     // let supported = SupportedFunctions::get_supported();
     print_board_info();
+    let sysfs = SysfsRoot::get();
+    if !sysfs.is_live() {
+        info!(
+            "{SYSFS_ROOT_ENV} is set, using sysfs tree at {:?} instead of /sys",
+            sysfs.path()
+        );
+    }
     // println!("{:?}", supported.supported_functions());
 
     // Start zbus server
@@ -81,8 +92,10 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
     )
     .await?;
 
+    let mut fans = None;
     match CtrlFanCurveZbus::new() {
         Ok(ctrl) => {
+            fans = Some(ctrl.clone());
             let sig_ctx = CtrlFanCurveZbus::signal_context(&server)?;
             start_tasks(ctrl, &mut server, sig_ctx).await?;
         }
@@ -101,6 +114,16 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    match CtrlBattery::new(power.clone()) {
+        Ok(ctrl) => {
+            let sig_ctx = CtrlBattery::signal_context(&server)?;
+            start_tasks(ctrl, &mut server, sig_ctx).await?;
+        }
+        Err(err) => {
+            error!("Battery: {}", err);
+        }
+    }
+
     match CtrlPlatform::new(
         platform,
         power,
@@ -110,8 +133,19 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
         CtrlPlatform::signal_context(&server)?,
     ) {
         Ok(ctrl) => {
+            let rules = CtrlProfileRules::new(ctrl.clone(), config.clone(), &server).await;
             let sig_ctx = CtrlPlatform::signal_context(&server)?;
             start_tasks(ctrl, &mut server, sig_ctx).await?;
+
+            match rules {
+                Ok(rules) => {
+                    let sig_ctx = CtrlProfileRules::signal_context(&server)?;
+                    start_tasks(rules, &mut server, sig_ctx).await?;
+                }
+                Err(err) => {
+                    error!("ProfileRules: {}", err);
+                }
+            }
         }
         Err(err) => {
             error!("CtrlPlatform: {}", err);
@@ -124,8 +158,21 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
     server.request_name(DBUS_NAME).await?;
 
     info!("Startup success, begining dbus server loop");
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
     loop {
-        // This is just a blocker to idle and ensure the reator reacts
-        server.executor().tick().await;
+        tokio::select! {
+            // This is just a blocker to idle and ensure the reator reacts
+            _ = server.executor().tick() => {}
+            _ = terminate.recv() => break,
+            _ = interrupt.recv() => break,
+        }
     }
+
+    // Fans left in manual mode would stay at their last speed
+    info!("Exiting, returning fans to firmware control");
+    if let Some(fans) = fans {
+        fans.release_fans().await;
+    }
+    Ok(())
 }
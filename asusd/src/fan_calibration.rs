@@ -0,0 +1,80 @@
+//! Measure the RPM each fan reaches across its pwm range. The fan being
+//! measured is put in manual mode and handed back to the firmware when done,
+//! when cancelled, or if a temperature driving it gets too hot.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use log::{debug, warn};
+use rog_profiles::calibration::{calibration_steps, read_rpm, FanCalibration};
+use rog_profiles::software_curve::{
+    find_pwm_node, set_pwm, set_pwm_enable, TempSource, PWM_ENABLE_AUTO, PWM_ENABLE_MANUAL,
+};
+use rog_profiles::{Device, FanCurvePU};
+use tokio::time::sleep;
+
+use crate::error::RogError;
+
+/// Time for the fan to reach a new speed before it is sampled
+const SETTLE: Duration = Duration::from_secs(3);
+/// The RPM at each step is the average of this many samples
+const SAMPLES: u32 = 3;
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The hottest of the sources that could be read
+fn hottest(sources: &[TempSource]) -> Option<f32> {
+    sources
+        .iter()
+        .filter_map(|s| s.find().and_then(|d| s.read(&d)).ok())
+        .reduce(f32::max)
+}
+
+async fn measure(
+    fan: FanCurvePU,
+    path: &Path,
+    sources: &[TempSource],
+    max_temp: u8,
+    running: &AtomicBool,
+) -> Result<FanCalibration, RogError> {
+    let mut points = Vec::new();
+    for pwm in calibration_steps() {
+        set_pwm(&mut Device::Path(path.to_owned()), fan, pwm)?;
+        sleep(SETTLE).await;
+        if !running.load(Ordering::SeqCst) {
+            return Err(RogError::DoTask("fan calibration cancelled".to_owned()));
+        }
+        if let Some(temp) = hottest(sources).filter(|t| *t >= max_temp as f32) {
+            return Err(RogError::DoTask(format!(
+                "fan calibration stopped, {fan:?} temperature reached {temp}C"
+            )));
+        }
+        let mut total = 0;
+        for _ in 0..SAMPLES {
+            total += read_rpm(&Device::Path(path.to_owned()), fan)?;
+            sleep(SAMPLE_INTERVAL).await;
+        }
+        let rpm = total / SAMPLES;
+        debug!("{fan:?} at pwm {pwm}: {rpm} RPM");
+        points.push((pwm, rpm));
+    }
+    Ok(FanCalibration { fan, points })
+}
+
+/// Step the fan from off to full and record the RPM at each step. Takes
+/// around a minute and a half. Stops early if `running` is cleared or a source
+/// reaches `max_temp`.
+pub async fn calibrate_fan(
+    fan: FanCurvePU,
+    sources: &[TempSource],
+    max_temp: u8,
+    running: &AtomicBool,
+) -> Result<FanCalibration, RogError> {
+    let path = find_pwm_node(fan)?.syspath().to_owned();
+    set_pwm_enable(&mut Device::Path(path.clone()), fan, PWM_ENABLE_MANUAL)?;
+    let result = measure(fan, &path, sources, max_temp, running).await;
+    set_pwm_enable(&mut Device::Path(path), fan, PWM_ENABLE_AUTO)
+        .map_err(|e| warn!("Could not return {fan:?} fan to firmware control: {e}"))
+        .ok();
+    result
+}
@@ -0,0 +1,318 @@
+//! The software fan control loop. Each fan with a `SoftwareCurve` is put in
+//! manual mode and its pwm written from the curve at a fixed interval.
+//!
+//! If the loop stops writing for longer than the watchdog timeout, such as the
+//! executor being blocked, a watchdog thread hands the fans back to the
+//! firmware. Fans are also handed back when control is stopped or no
+//! temperature can be read.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rog_profiles::error::ProfileError;
+use rog_profiles::fan_curve_set::CurveLimits;
+use rog_profiles::software_curve::{
+    find_pwm_node, set_pwm, set_pwm_enable, SoftwareCurve, SoftwareCurveState, PWM_ENABLE_AUTO,
+    PWM_ENABLE_MANUAL,
+};
+use rog_profiles::{Device, FanCurvePU};
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+
+use crate::error::RogError;
+
+pub const MIN_INTERVAL_MS: u64 = 100;
+/// The watchdog never times out in less than this
+const MIN_WATCHDOG_TIMEOUT: Duration = Duration::from_secs(5);
+/// Intervals the loop can miss before the watchdog steps in
+const WATCHDOG_MISSED_INTERVALS: u32 = 5;
+
+fn default_interval_ms() -> u64 {
+    1000
+}
+
+/// Stored as part of `FanCurveConfig`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SoftwareFanConfig {
+    /// Run the loop. This is remembered across restarts.
+    pub enabled: bool,
+    /// Milliseconds between each update of the fans
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    pub curves: Vec<SoftwareCurve>,
+}
+
+impl Default for SoftwareFanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_interval_ms(),
+            curves: Vec::new(),
+        }
+    }
+}
+
+impl SoftwareFanConfig {
+    fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.max(MIN_INTERVAL_MS))
+    }
+
+    fn watchdog_timeout(&self) -> Duration {
+        (self.interval() * WATCHDOG_MISSED_INTERVALS).max(MIN_WATCHDOG_TIMEOUT)
+    }
+
+    /// Replace the curve for the same fan, or add it
+    pub fn set_curve(&mut self, curve: SoftwareCurve) {
+        if let Some(existing) = self.curves.iter_mut().find(|c| c.fan == curve.fan) {
+            *existing = curve;
+        } else {
+            self.curves.push(curve);
+        }
+    }
+}
+
+/// Hand the fan back to the firmware
+pub(crate) fn release_fan(fan: FanCurvePU, device: &mut Device) {
+    set_pwm_enable(device, fan, PWM_ENABLE_AUTO)
+        .map_err(|e| warn!("Could not return {fan:?} fan to firmware control: {e}"))
+        .ok();
+}
+
+/// A fan driven by a curve. Devices are kept by path as a udev device can't
+/// be sent to the task.
+#[derive(Debug)]
+pub struct ControlledFan {
+    curve: SoftwareCurve,
+    state: SoftwareCurveState,
+    /// The hwmon device of each source, if found yet
+    sources: Vec<Option<PathBuf>>,
+    pwm: PathBuf,
+    manual: bool,
+}
+
+impl ControlledFan {
+    pub fn new(curve: SoftwareCurve, pwm: PathBuf) -> Self {
+        Self {
+            sources: vec![None; curve.sources.len()],
+            curve,
+            state: SoftwareCurveState::default(),
+            pwm,
+            manual: false,
+        }
+    }
+
+    /// The hottest source that could be read
+    fn read_temp(&mut self) -> Option<f32> {
+        let mut hottest = None;
+        for (source, device) in self.curve.sources.iter().zip(self.sources.iter_mut()) {
+            if device.is_none() {
+                *device = source.find().ok().map(|d| d.syspath().to_owned());
+            }
+            let Some(path) = device.as_ref() else {
+                continue;
+            };
+            match source.read(&Device::Path(path.clone())) {
+                Ok(temp) => hottest = Some(temp.max(hottest.unwrap_or(temp))),
+                Err(_) => {
+                    // The hwmon device may have gone away, find it again next time
+                    *device = None;
+                }
+            }
+        }
+        hottest
+    }
+
+    /// Read the temperatures and set the fan, `elapsed` being the seconds
+    /// since the last update
+    pub fn update(&mut self, elapsed: f32) -> Result<(), ProfileError> {
+        let Some(temp) = self.read_temp() else {
+            if self.manual {
+                warn!(
+                    "No temperature for the {:?} fan, using firmware control",
+                    self.curve.fan
+                );
+                self.release();
+            }
+            return Ok(());
+        };
+        let pwm = self.state.step(&self.curve, temp, elapsed);
+        let mut device = Device::Path(self.pwm.clone());
+        if !self.manual {
+            set_pwm_enable(&mut device, self.curve.fan, PWM_ENABLE_MANUAL)?;
+            self.manual = true;
+        }
+        set_pwm(&mut device, self.curve.fan, pwm)
+    }
+
+    /// Hand the fan back to the firmware. The next update takes it back.
+    pub fn release(&mut self) {
+        release_fan(self.curve.fan, &mut Device::Path(self.pwm.clone()));
+        self.manual = false;
+        self.state.reset();
+    }
+}
+
+/// Tracks the task running the loop. The task exits when `generation` no
+/// longer matches the one it was started with.
+#[derive(Debug, Clone, Default)]
+pub struct SoftwareFanControl {
+    generation: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
+    /// Milliseconds since the loop started that the fans were last updated
+    heartbeat: Arc<AtomicU64>,
+    /// Set by the watchdog when it has released the fans
+    stalled: Arc<AtomicBool>,
+}
+
+impl SoftwareFanControl {
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Start, or restart, the loop with the curves in config. Each curve must
+    /// be within `limits`.
+    pub fn start(&self, config: &SoftwareFanConfig, limits: &CurveLimits) -> Result<(), RogError> {
+        if config.curves.is_empty() {
+            return Err(RogError::MissingFunction(
+                "No software fan curves are set".to_string(),
+            ));
+        }
+        let mut fans = Vec::with_capacity(config.curves.len());
+        for curve in &config.curves {
+            curve.validate(limits)?;
+            let pwm = find_pwm_node(curve.fan)?.syspath().to_owned();
+            fans.push(ControlledFan::new(curve.clone(), pwm));
+        }
+        let released: Vec<(FanCurvePU, PathBuf)> =
+            fans.iter().map(|f| (f.curve.fan, f.pwm.clone())).collect();
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.running.store(true, Ordering::SeqCst);
+        self.stalled.store(false, Ordering::SeqCst);
+        self.heartbeat.store(0, Ordering::SeqCst);
+        let started = Instant::now();
+        let interval = config.interval();
+
+        let control = self.clone();
+        tokio::spawn(async move {
+            info!("Software fan control started for {} fans", fans.len());
+            let mut timer = tokio::time::interval(interval);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            let mut last = Instant::now();
+            loop {
+                timer.tick().await;
+                if control.generation.load(Ordering::SeqCst) != generation {
+                    break;
+                }
+                if control.stalled.swap(false, Ordering::SeqCst) {
+                    info!("Software fan control recovered, taking back the fans");
+                    for fan in &mut fans {
+                        fan.manual = false;
+                        fan.state.reset();
+                    }
+                }
+                let elapsed = last.elapsed().as_secs_f32();
+                last = Instant::now();
+                for fan in &mut fans {
+                    if let Err(e) = fan.update(elapsed) {
+                        warn!("Software fan control of {:?} failed: {e}", fan.curve.fan);
+                        fan.release();
+                    }
+                }
+                control
+                    .heartbeat
+                    .store(started.elapsed().as_millis() as u64, Ordering::SeqCst);
+            }
+            // A restart has a new task holding the fans
+            if !control.is_running() {
+                for fan in &mut fans {
+                    fan.release();
+                }
+            }
+            info!("Software fan control task exited");
+        });
+
+        // A thread rather than a task so that it still runs if the executor
+        // is what stalled
+        let control = self.clone();
+        let timeout = config.watchdog_timeout();
+        std::thread::spawn(move || {
+            while control.generation.load(Ordering::SeqCst) == generation {
+                std::thread::sleep(Duration::from_secs(1));
+                let beat = Duration::from_millis(control.heartbeat.load(Ordering::SeqCst));
+                if started.elapsed().saturating_sub(beat) < timeout
+                    || control.stalled.load(Ordering::SeqCst)
+                    || control.generation.load(Ordering::SeqCst) != generation
+                {
+                    continue;
+                }
+                warn!("Software fan control stalled, returning fans to firmware control");
+                for (fan, path) in &released {
+                    release_fan(*fan, &mut Device::Path(path.clone()));
+                }
+                control.stalled.store(true, Ordering::SeqCst);
+            }
+        });
+        Ok(())
+    }
+
+    /// Stop the loop and hand every fan in config back to the firmware
+    pub fn stop(&self, config: &SoftwareFanConfig) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.running.store(false, Ordering::SeqCst);
+        // The task releases the fans it holds on exit, this is done as well in
+        // case it is stalled
+        for curve in &config.curves {
+            if let Ok(mut device) = find_pwm_node(curve.fan) {
+                release_fan(curve.fan, &mut device);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+
+    use rog_profiles::software_curve::TempSource;
+
+    use super::*;
+
+    #[test]
+    fn controlled_fan_falls_back_to_firmware() {
+        let dir = std::env::temp_dir().join(format!("asusd-fan-control-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("pwm1"), "0").unwrap();
+        write(dir.join("pwm1_enable"), "2").unwrap();
+
+        let curve = SoftwareCurve {
+            fan: FanCurvePU::CPU,
+            sources: vec![TempSource::new("asusd-test", "temp1_input")],
+            points: vec![
+                (40, 50),
+                (80, 250),
+            ],
+            hysteresis: 0,
+            ramp_up: 0,
+            ramp_down: 0,
+        };
+        let mut fan = ControlledFan::new(curve, dir.clone());
+        fan.sources[0] = Some(dir.clone());
+
+        write(dir.join("temp1_input"), "60000").unwrap();
+        fan.update(1.0).unwrap();
+        assert_eq!(read_to_string(dir.join("pwm1_enable")).unwrap(), "1");
+        assert_eq!(read_to_string(dir.join("pwm1")).unwrap(), "150");
+
+        // Losing the temperature hands the fan back
+        std::fs::remove_file(dir.join("temp1_input")).unwrap();
+        fan.update(1.0).unwrap();
+        assert_eq!(read_to_string(dir.join("pwm1_enable")).unwrap(), "2");
+        assert!(!fan.manual);
+
+        remove_dir_all(&dir).ok();
+    }
+}
@@ -2,10 +2,18 @@
 /// Configuration loading, saving
 pub mod config;
 pub mod ctrl_backlight;
+/// Battery telemetry and its history
+pub mod ctrl_battery;
 /// Control platform profiles + fan-curves if available
 pub mod ctrl_fancurves;
 /// Control ASUS bios function such as boot sound, Optimus/Dedicated gfx mode
 pub mod ctrl_platform;
+/// Switch the platform profile and other settings by rules
+pub mod ctrl_profile_rules;
+/// Measuring the RPM of each fan across its pwm range
+pub mod fan_calibration;
+/// Userspace fan control from hwmon temperatures
+pub mod fan_control;
 
 pub mod asus_armoury;
 pub mod aura_anime;
@@ -14,15 +22,18 @@ pub mod aura_manager;
 pub mod aura_scsi;
 pub mod aura_slash;
 pub mod aura_types;
+pub mod battery_history;
+pub mod charge_schedule;
 pub mod error;
 
 use std::future::Future;
 use std::time::Duration;
 
-use dmi_id::DMIID;
 use futures_lite::stream::StreamExt;
 use log::{debug, info, warn};
 use logind_zbus::manager::ManagerProxy;
+use rog_platform::sysfs::SysfsRoot;
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 use zbus::object_server::{Interface, SignalEmitter};
 use zbus::proxy::CacheProperties;
@@ -31,7 +42,11 @@ use zbus::Connection;
 
 use crate::error::RogError;
 
+#[cfg(not(test))]
 const CONFIG_PATH_BASE: &str = "/etc/asusd/";
+/// Tests must never touch the configs of the installed daemon
+#[cfg(test)]
+const CONFIG_PATH_BASE: &str = "/tmp/asusd-test/";
 pub const ASUS_ZBUS_PATH: &str = "/xyz/ljones";
 
 pub static DBUS_NAME: &str = "xyz.ljones.Asusd";
@@ -135,7 +150,7 @@ macro_rules! task_watch_item_notify {
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub fn print_board_info() {
-    let dmi = DMIID::new().unwrap_or_default();
+    let dmi = SysfsRoot::get().dmi_id().unwrap_or_default();
     info!("Product family: {}", dmi.product_family);
     info!("Board name: {}", dmi.board_name);
 }
@@ -208,13 +223,16 @@ pub trait CtrlTask {
     ///
     /// The closures can potentially block, so execution time should be the
     /// minimal possible such as save a variable.
+    ///
+    /// Returns the handles of the spawned tasks so that a controller which can
+    /// be removed is able to abort them.
     fn create_sys_event_tasks<Fut1, Fut2, Fut3, Fut4, F1, F2, F3, F4>(
         &self,
         mut on_prepare_for_sleep: F1,
         mut on_prepare_for_shutdown: F2,
         mut on_lid_change: F3,
         mut on_external_power_change: F4,
-    ) -> impl Future<Output = ()> + Send
+    ) -> impl Future<Output = Vec<JoinHandle<()>>> + Send
     where
         F1: FnMut(bool) -> Fut1 + Send + 'static,
         F2: FnMut(bool) -> Fut2 + Send + 'static,
@@ -237,7 +255,7 @@ pub trait CtrlTask {
                 .expect("Controller could not create ManagerProxy");
 
             let manager1 = manager.clone();
-            tokio::spawn(async move {
+            let shutdown_task = tokio::spawn(async move {
                 if let Ok(mut notif) = manager1.receive_prepare_for_shutdown().await {
                     while let Some(event) = notif.next().await {
                         // blocks thread :|
@@ -250,7 +268,7 @@ pub trait CtrlTask {
             });
 
             let manager2 = manager.clone();
-            tokio::spawn(async move {
+            let sleep_task = tokio::spawn(async move {
                 if let Ok(mut notif) = manager2.receive_prepare_for_sleep().await {
                     while let Some(event) = notif.next().await {
                         // blocks thread :|
@@ -263,7 +281,7 @@ pub trait CtrlTask {
             });
 
             let manager3 = manager.clone();
-            tokio::spawn(async move {
+            let power_task = tokio::spawn(async move {
                 let mut last_power = manager3.on_external_power().await.unwrap_or_default();
 
                 loop {
@@ -277,7 +295,7 @@ pub trait CtrlTask {
                 }
             });
 
-            tokio::spawn(async move {
+            let lid_task = tokio::spawn(async move {
                 let mut last_lid = manager.lid_closed().await.unwrap_or_default();
                 // need to loop on these as they don't emit signals
                 loop {
@@ -290,10 +308,34 @@ pub trait CtrlTask {
                     sleep(Duration::from_secs(2)).await;
                 }
             });
+
+            vec![
+                shutdown_task, sleep_task, power_task, lid_task,
+            ]
         }
     }
 }
 
+/// Held by the tests which write to the nodes they share in
+/// `test_sysfs_root()`
+#[cfg(test)]
+pub(crate) static TEST_SYSFS_LOCK: futures_util::lock::Mutex<()> =
+    futures_util::lock::Mutex::new(());
+
+/// The sysfs tree used by the tests of this crate. `SysfsRoot` is set for the
+/// whole process, so every test that needs sysfs must share this one root and
+/// add only the attributes it uses.
+#[cfg(test)]
+pub(crate) fn test_sysfs_root() -> &'static std::path::Path {
+    static ROOT: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+    ROOT.get_or_init(|| {
+        let root = std::env::temp_dir().join(format!("asusd-sysfs-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        rog_platform::sysfs::SysfsRoot::set(&root);
+        root
+    })
+}
+
 pub trait GetSupported {
     type A;
 
@@ -1,6 +1,10 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
 use log::warn;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Clone)]
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Clone, Deserialize, Serialize)]
 pub struct DMIID {
     pub id_model: String,
     pub dmi_family: String,
@@ -16,6 +20,9 @@ pub struct DMIID {
 }
 
 impl DMIID {
+    /// Read the ids of this machine from udev. This always uses the live
+    /// `/sys`, use `rog_platform::sysfs::SysfsRoot::dmi_id()` to follow a
+    /// sysfs root set for replaying.
     pub fn new() -> Result<Self, String> {
         let mut enumerator = udev::Enumerator::new().map_err(|err| {
             warn!("{}", err);
@@ -82,6 +89,32 @@ impl DMIID {
         }
         Err("dmi not found".into())
     }
+
+    /// Read the ids from a directory laid out like `/sys/class/dmi/id/`. The
+    /// udev properties are derived from the attributes the same way udev does.
+    pub fn from_dir(dir: &Path) -> Result<Self, String> {
+        if !dir.is_dir() {
+            return Err(format!("dmi not found at {dir:?}"));
+        }
+        let read = |attr: &str| {
+            read_to_string(dir.join(attr))
+                .map(|s| s.trim_end_matches('\n').to_string())
+                .unwrap_or("Unknown".to_string())
+        };
+        Ok(Self {
+            id_model: read("product_name"),
+            dmi_family: read("product_family"),
+            dmi_vendor: read("sys_vendor"),
+            board_name: read("board_name"),
+            board_vendor: read("board_vendor"),
+            bios_date: read("bios_date"),
+            bios_release: read("bios_release"),
+            bios_vendor: read("bios_vendor"),
+            bios_version: read("bios_version"),
+            product_family: read("product_family"),
+            product_name: read("product_name"),
+        })
+    }
 }
 
 #[cfg(test)]
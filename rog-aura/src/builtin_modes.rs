@@ -538,6 +538,57 @@ impl From<&AuraEffect> for Vec<u8> {
     }
 }
 
+/// Parses a USB HID packet back in to an `AuraEffect`. This is the inverse of
+/// `<[u8; AURA_LAPTOP_LED_MSG_LEN]>::from(&AuraEffect)` and will fail on any
+/// value that the encoder could not have produced.
+impl TryFrom<&[u8]> for AuraEffect {
+    type Error = Error;
+
+    fn try_from(msg: &[u8]) -> Result<Self, Self::Error> {
+        if msg.len() < AURA_LAPTOP_LED_MSG_LEN || msg[0] != 0x5d || msg[1] != 0xb3 {
+            return Err(Error::ParsePacket(format!(
+                "not an effect packet: {msg:02x?}"
+            )));
+        }
+
+        let mode = AuraModeNum::from(msg[3]);
+        if mode as u8 != msg[3] {
+            return Err(Error::ParsePacket(format!("invalid mode {}", msg[3])));
+        }
+        let zone = AuraZone::from(msg[2] as i32);
+        if zone as u8 != msg[2] {
+            return Err(Error::ParsePacket(format!("invalid zone {}", msg[2])));
+        }
+        let speed = match msg[7] {
+            0xe1 => Speed::Low,
+            0xeb => Speed::Med,
+            0xf5 => Speed::High,
+            n => return Err(Error::ParsePacket(format!("invalid speed {n:#x}"))),
+        };
+        let direction = Direction::from(msg[8] as i32);
+        if direction as u8 != msg[8] {
+            return Err(Error::ParsePacket(format!("invalid direction {}", msg[8])));
+        }
+
+        Ok(Self {
+            mode,
+            zone,
+            colour1: Colour {
+                r: msg[4],
+                g: msg[5],
+                b: msg[6],
+            },
+            colour2: Colour {
+                r: msg[10],
+                g: msg[11],
+                b: msg[12],
+            },
+            speed,
+            direction,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -691,4 +742,71 @@ mod tests {
             capture[..9]
         );
     }
+
+    #[test]
+    fn led_packet_round_trip() {
+        let modes = [
+            AuraModeNum::Static,
+            AuraModeNum::Breathe,
+            AuraModeNum::RainbowCycle,
+            AuraModeNum::RainbowWave,
+            AuraModeNum::Star,
+            AuraModeNum::Rain,
+            AuraModeNum::Highlight,
+            AuraModeNum::Laser,
+            AuraModeNum::Ripple,
+            AuraModeNum::Pulse,
+            AuraModeNum::Comet,
+            AuraModeNum::Flash,
+        ];
+        let mut n: u8 = 0;
+        for mode in modes {
+            for zone in 0..=7 {
+                for speed in [
+                    Speed::Low,
+                    Speed::Med,
+                    Speed::High,
+                ] {
+                    for direction in 0..=3 {
+                        n = n.wrapping_add(37);
+                        let effect = AuraEffect {
+                            mode,
+                            zone: AuraZone::from(zone),
+                            colour1: Colour {
+                                r: n,
+                                g: n.wrapping_mul(3),
+                                b: !n,
+                            },
+                            colour2: Colour {
+                                r: !n,
+                                g: n,
+                                b: n.wrapping_mul(5),
+                            },
+                            speed,
+                            direction: Direction::from(direction),
+                        };
+                        let bytes = <[u8; AURA_LAPTOP_LED_MSG_LEN]>::from(&effect);
+                        assert_eq!(AuraEffect::try_from(bytes.as_slice()).unwrap(), effect);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn led_packet_decode_invalid() {
+        let bytes = <[u8; AURA_LAPTOP_LED_MSG_LEN]>::from(&AuraEffect::default());
+        assert!(AuraEffect::try_from(&bytes[..5]).is_err());
+        for (idx, value) in [
+            (1, 0xb4),
+            (2, 8),
+            (3, 9),
+            (7, 0x00),
+            (8, 4),
+        ] {
+            let mut bad = bytes;
+            bad[idx] = value;
+            assert!(AuraEffect::try_from(bad.as_slice()).is_err(), "byte {idx}");
+        }
+    }
 }
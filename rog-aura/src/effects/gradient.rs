@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use super::{gradient_colour, key_progress, EffectState};
+use crate::keyboard::{KeyLayout, LedCode};
+use crate::{Colour, Direction};
+
+/// A static gradient across the keyboard, starting at the first colour on the
+/// edge `direction` starts from and ending with the last
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Gradient {
+    led: LedCode,
+    /// The gradient stops, spaced evenly
+    colours: Vec<Colour>,
+    direction: Direction,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    colour: Option<Colour>,
+}
+
+impl Gradient {
+    pub fn new(address: LedCode, colours: Vec<Colour>, direction: Direction) -> Self {
+        Self {
+            led: address,
+            colours,
+            direction,
+            colour: None,
+        }
+    }
+}
+
+impl EffectState for Gradient {
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        if self.colour.is_none() {
+            let position = key_progress(layout, self.led, self.direction);
+            self.colour = Some(gradient_colour(&self.colours, position, false));
+        }
+    }
+
+    fn get_colour(&self) -> Colour {
+        self.colour
+            .unwrap_or_else(|| gradient_colour(&self.colours, 0.0, false))
+    }
+
+    fn get_led(&self) -> LedCode {
+        self.led
+    }
+
+    fn set_led(&mut self, address: LedCode) {
+        self.led = address;
+        self.colour = None;
+    }
+}
@@ -12,8 +12,23 @@ pub use breathe::*;
 mod static_;
 pub use static_::*;
 
+mod wave;
+pub use wave::*;
+
+mod ripple;
+pub use ripple::*;
+
+mod gradient;
+pub use gradient::*;
+
+mod starlight;
+pub use starlight::*;
+
+mod reactive;
+pub use reactive::*;
+
 use crate::keyboard::{AuraLaptopUsbPackets, KeyLayout, LedCode, LedUsbPackets};
-use crate::Colour;
+use crate::{Colour, Direction, Speed};
 
 // static mut RNDINDEX: usize = 0;
 static mut PRNDINDEX: usize = 0;
@@ -42,6 +57,82 @@ pub fn p_random() -> i32 {
     }
 }
 
+/// The centre of `led` in the layout, in the same units as `KeyShape`. `None`
+/// if the layout doesn't have the key.
+pub(crate) fn key_centre(layout: &KeyLayout, led: LedCode) -> Option<(f32, f32)> {
+    let key = layout.key_positions().into_iter().find(|k| k.led == led)?;
+    Some((key.x + key.width / 2.0, key.y + key.height / 2.0))
+}
+
+/// How far along `direction` the centre of `led` is, from 0.0 at the edge of
+/// the keyboard the direction starts at to 1.0 at the opposite edge
+pub(crate) fn key_progress(layout: &KeyLayout, led: LedCode, direction: Direction) -> f32 {
+    let Some((x, y)) = key_centre(layout, led) else {
+        return 0.0;
+    };
+    let x = (x / layout.max_width().max(f32::EPSILON)).clamp(0.0, 1.0);
+    let y = (y / layout.max_height().max(f32::EPSILON)).clamp(0.0, 1.0);
+    match direction {
+        Direction::Right => x,
+        Direction::Left => 1.0 - x,
+        Direction::Down => y,
+        Direction::Up => 1.0 - y,
+    }
+}
+
+/// The fraction of the keyboard a moving effect covers each frame
+pub(crate) fn speed_step(speed: Speed) -> f32 {
+    match speed {
+        Speed::Low => 0.005,
+        Speed::Med => 0.01,
+        Speed::High => 0.02,
+    }
+}
+
+/// Mix `from` with `to`, `amount` is 0.0 for all `from` to 1.0 for all `to`
+pub(crate) fn blend(from: Colour, to: Colour, amount: f32) -> Colour {
+    let amount = amount.clamp(0.0, 1.0);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Colour {
+        r: mix(from.r, to.r),
+        g: mix(from.g, to.g),
+        b: mix(from.b, to.b),
+    }
+}
+
+/// The colour at `position` (0.0 to 1.0) along evenly spaced `stops`. If
+/// `wrap` is set the last stop blends back in to the first so the gradient
+/// can repeat seamlessly.
+pub(crate) fn gradient_colour(stops: &[Colour], position: f32, wrap: bool) -> Colour {
+    match stops {
+        [] => Colour { r: 0, g: 0, b: 0 },
+        [colour] => *colour,
+        _ => {
+            let segments = if wrap { stops.len() } else { stops.len() - 1 };
+            let scaled = position.clamp(0.0, 1.0) * segments as f32;
+            let index = (scaled as usize).min(segments - 1);
+            let next = (index + 1) % stops.len();
+            blend(stops[index], stops[next], scaled - index as f32)
+        }
+    }
+}
+
+/// A fully saturated colour for `hue` from 0.0 to 1.0
+pub(crate) fn hue_colour(hue: f32) -> Colour {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let rise = ((hue % 1.0) * 255.0) as u8;
+    let fall = 255 - rise;
+    let (r, g, b) = match hue as u8 {
+        0 => (255, rise, 0),
+        1 => (fall, 255, 0),
+        2 => (0, 255, rise),
+        3 => (0, fall, 255),
+        4 => (rise, 0, 255),
+        _ => (255, 0, fall),
+    };
+    Colour { r, g, b }
+}
+
 pub trait InputForEffect {
     /// Calculate the next colour state
     fn next_colour_state(&mut self);
@@ -62,9 +153,17 @@ pub(crate) trait EffectState {
     fn get_led(&self) -> LedCode;
 
     fn set_led(&mut self, address: LedCode);
+
+    /// Called with each LED of a key when it is pressed
+    fn key_pressed(&mut self, _key: LedCode) {}
+
+    /// If the effect uses `key_pressed()`
+    fn is_reactive(&self) -> bool {
+        false
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct AdvancedEffects {
     effects: Vec<Effect>,
     zoned: bool,
@@ -100,12 +199,39 @@ impl AdvancedEffects {
         None
     }
 
+    /// Push an effect for every LED in the layout, such as a `Wave` which
+    /// needs one effect per key to cover the keyboard
+    pub fn push_for_layout(
+        &mut self,
+        layout: &KeyLayout,
+        mut effect: impl FnMut(LedCode) -> Effect,
+    ) {
+        for key in layout.key_positions() {
+            self.effects.push(effect(key.led));
+        }
+    }
+
     pub fn next_state(&mut self, layout: &KeyLayout) {
         for effect in &mut self.effects {
             effect.next_state(layout);
         }
     }
 
+    /// Pass a key press to every effect, `key_code` is the Linux input key
+    /// code as read from evdev
+    pub fn key_pressed(&mut self, key_code: u16) {
+        for led in LedCode::from_key_code(key_code) {
+            for effect in &mut self.effects {
+                effect.key_pressed(*led);
+            }
+        }
+    }
+
+    /// If any effect reacts to key presses, so input needs to be read
+    pub fn is_reactive(&self) -> bool {
+        self.effects.iter().any(|e| e.is_reactive())
+    }
+
     pub fn create_packets(&self) -> AuraLaptopUsbPackets {
         let mut usb_packets = if self.zoned {
             // TODO: figure out if that single byte difference for multizone actually
@@ -173,6 +299,20 @@ macro_rules! effect_impl {
                     $(Effect::$effect(c) => c.get_colour(),)*
                 }
             }
+
+            /// Tell the effect a key was pressed, used by the reactive effects
+            pub fn key_pressed(&mut self, key: $crate::keyboard::LedCode) {
+                match self {
+                    $(Effect::$effect(c) => c.key_pressed(key),)*
+                }
+            }
+
+            /// If the effect reacts to key presses
+            pub fn is_reactive(&self) -> bool {
+                match self {
+                    $(Effect::$effect(c) => c.is_reactive(),)*
+                }
+            }
         }
     };
 }
@@ -187,6 +327,14 @@ pub enum Effect {
     Breathe(Breathe),
     DoomFlicker(DoomFlicker),
     DoomLightFlash(DoomLightFlash),
+    Wave(Wave),
+    Ripple(Ripple),
+    RainbowPerKey(RainbowPerKey),
+    Gradient(Gradient),
+    Starlight(Starlight),
+    Reactive(Reactive),
+    ReactiveRipple(ReactiveRipple),
+    Heatmap(Heatmap),
 }
 
 impl Default for Effect {
@@ -195,13 +343,42 @@ impl Default for Effect {
     }
 }
 
-effect_impl!(Static, Breathe, DoomFlicker, DoomLightFlash);
+effect_impl!(
+    Static, Breathe, DoomFlicker, DoomLightFlash, Wave, Ripple, RainbowPerKey, Gradient, Starlight,
+    Reactive, ReactiveRipple, Heatmap
+);
 
 #[cfg(test)]
 mod tests {
-    use crate::effects::{AdvancedEffects, Breathe, DoomFlicker, Effect, Static};
+    use std::path::PathBuf;
+
+    use crate::effects::{
+        gradient_colour, hue_colour, AdvancedEffects, Breathe, DoomFlicker, Effect, Gradient,
+        Heatmap, RainbowPerKey, Reactive, ReactiveRipple, Ripple, Static, Wave,
+    };
     use crate::keyboard::{KeyLayout, LedCode};
-    use crate::{Colour, Speed};
+    use crate::{Colour, Direction, Speed};
+
+    const RED: Colour = Colour { r: 255, g: 0, b: 0 };
+    const BLUE: Colour = Colour { r: 0, g: 0, b: 255 };
+    const BLACK: Colour = Colour { r: 0, g: 0, b: 0 };
+    const STOPS: [Colour; 2] = [
+        RED, BLUE,
+    ];
+
+    fn per_key_layout() -> KeyLayout {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("data/layouts/g513i-per-key_US.ron");
+        KeyLayout::from_file(&path).unwrap()
+    }
+
+    fn colour_of(seq: &AdvancedEffects, led: LedCode) -> Colour {
+        seq.effects
+            .iter()
+            .find(|e| e.led() == led)
+            .unwrap()
+            .colour()
+    }
 
     #[test]
     fn single_key_next_state_then_create() {
@@ -302,4 +479,196 @@ mod tests {
         assert_eq!(packets[5][34], 87);
         assert_eq!(packets[5][35], 40);
     }
+
+    #[test]
+    fn gradient_stops() {
+        assert_eq!(gradient_colour(&[], 0.5, false), BLACK);
+        assert_eq!(gradient_colour(&[RED], 0.5, true), RED);
+        assert_eq!(gradient_colour(&STOPS, 0.0, false), RED);
+        assert_eq!(gradient_colour(&STOPS, 1.0, false), BLUE);
+        assert_eq!(gradient_colour(&STOPS, 0.5, false), Colour {
+            r: 128,
+            g: 0,
+            b: 128
+        });
+        // Wrapping blends back to the first stop
+        assert_eq!(gradient_colour(&STOPS, 0.5, true), BLUE);
+        assert_eq!(gradient_colour(&STOPS, 1.0, true), RED);
+
+        assert_eq!(hue_colour(0.0), RED);
+        assert_eq!(hue_colour(1.0 / 3.0), Colour { r: 0, g: 255, b: 0 });
+        assert_eq!(hue_colour(2.0 / 3.0), BLUE);
+    }
+
+    #[test]
+    fn gradient_across_keys() {
+        let layout = per_key_layout();
+        let mut seq = AdvancedEffects::new(false);
+        seq.push_for_layout(&layout, |led| {
+            Effect::Gradient(Gradient::new(led, STOPS.to_vec(), Direction::Right))
+        });
+        seq.next_state(&layout);
+
+        let left = colour_of(&seq, LedCode::Esc);
+        let right = colour_of(&seq, LedCode::Backspace);
+        assert!(left.r > left.b);
+        assert!(right.b > right.r);
+        // Static, so further frames change nothing
+        seq.next_state(&layout);
+        assert_eq!(colour_of(&seq, LedCode::Esc), left);
+    }
+
+    #[test]
+    fn wave_moves() {
+        let layout = per_key_layout();
+        let mut seq = AdvancedEffects::new(false);
+        seq.push_for_layout(&layout, |led| {
+            Effect::Wave(Wave::new(
+                led,
+                STOPS.to_vec(),
+                Direction::Right,
+                Speed::High,
+            ))
+        });
+        seq.next_state(&layout);
+        let esc = colour_of(&seq, LedCode::Esc);
+        assert_ne!(esc, colour_of(&seq, LedCode::G));
+
+        seq.next_state(&layout);
+        assert_ne!(colour_of(&seq, LedCode::Esc), esc);
+        // A full cycle brings the colour back around
+        for _ in 0..49 {
+            seq.next_state(&layout);
+        }
+        let looped = colour_of(&seq, LedCode::Esc);
+        assert!(looped.r.abs_diff(esc.r) <= 1 && looped.b.abs_diff(esc.b) <= 1);
+    }
+
+    #[test]
+    fn rainbow_per_key() {
+        let layout = per_key_layout();
+        let mut seq = AdvancedEffects::new(false);
+        seq.push_for_layout(&layout, |led| {
+            Effect::RainbowPerKey(RainbowPerKey::new(led, Direction::Down, Speed::Med))
+        });
+        seq.next_state(&layout);
+        // Keys in the same column share a colour when moving down
+        assert_ne!(
+            colour_of(&seq, LedCode::Esc),
+            colour_of(&seq, LedCode::Spacebar)
+        );
+        let packets = seq.create_packets();
+        assert_eq!(packets[0][0], 0x5d);
+    }
+
+    #[test]
+    fn ripple_spreads_from_origin() {
+        let layout = per_key_layout();
+        let mut seq = AdvancedEffects::new(false);
+        seq.push_for_layout(&layout, |led| {
+            Effect::Ripple(Ripple::new(led, LedCode::G, RED, Speed::High))
+        });
+        seq.next_state(&layout);
+        assert!(colour_of(&seq, LedCode::G).r > 200);
+        assert_eq!(colour_of(&seq, LedCode::Esc), BLACK);
+
+        // The ring reaches Esc after the origin has gone dark
+        let mut esc_lit = false;
+        for _ in 0..50 {
+            seq.next_state(&layout);
+            if colour_of(&seq, LedCode::Esc).r > 200 {
+                esc_lit = true;
+                assert_eq!(colour_of(&seq, LedCode::G), BLACK);
+            }
+        }
+        assert!(esc_lit);
+    }
+
+    /// Linux input key codes
+    const KEY_ESC: u16 = 1;
+    const KEY_A: u16 = 30;
+    const KEY_G: u16 = 34;
+
+    #[test]
+    fn reactive_key_fades() {
+        let layout = per_key_layout();
+        let mut seq = AdvancedEffects::new(false);
+        seq.push_for_layout(&layout, |led| {
+            Effect::Reactive(Reactive::new(led, RED, BLACK, Speed::High))
+        });
+        assert!(seq.is_reactive());
+
+        seq.key_pressed(KEY_A);
+        seq.next_state(&layout);
+        assert_eq!(colour_of(&seq, LedCode::A), RED);
+        assert_eq!(colour_of(&seq, LedCode::S), BLACK);
+
+        seq.next_state(&layout);
+        let fading = colour_of(&seq, LedCode::A);
+        assert!(fading.r < 255 && fading.r > 0);
+        for _ in 0..12 {
+            seq.next_state(&layout);
+        }
+        assert_eq!(colour_of(&seq, LedCode::A), BLACK);
+    }
+
+    #[test]
+    fn reactive_ripple_from_pressed_key() {
+        let layout = per_key_layout();
+        let mut seq = AdvancedEffects::new(false);
+        seq.push_for_layout(&layout, |led| {
+            Effect::ReactiveRipple(ReactiveRipple::new(led, RED, BLACK, Speed::High))
+        });
+
+        seq.next_state(&layout);
+        assert_eq!(colour_of(&seq, LedCode::G), BLACK);
+        seq.key_pressed(KEY_G);
+        seq.next_state(&layout);
+        assert_eq!(colour_of(&seq, LedCode::G), RED);
+        assert_eq!(colour_of(&seq, LedCode::Esc), BLACK);
+
+        let mut esc_lit = false;
+        for _ in 0..50 {
+            seq.next_state(&layout);
+            esc_lit |= colour_of(&seq, LedCode::Esc).r > 200;
+        }
+        assert!(esc_lit);
+        // Every ring has passed, so the keyboard is dark again
+        assert!(seq.effects.iter().all(|e| e.colour() == BLACK));
+    }
+
+    #[test]
+    fn heatmap_builds_with_presses() {
+        let layout = per_key_layout();
+        let mut seq = AdvancedEffects::new(false);
+        seq.push_for_layout(&layout, |led| {
+            Effect::Heatmap(Heatmap::new(led, BLUE, RED, 4, Speed::Med))
+        });
+        seq.key_pressed(KEY_A);
+        seq.key_pressed(KEY_A);
+        seq.key_pressed(KEY_ESC);
+        seq.next_state(&layout);
+        let a = colour_of(&seq, LedCode::A);
+        let esc = colour_of(&seq, LedCode::Esc);
+        assert!(a.r > esc.r && esc.r > 0);
+        assert_eq!(colour_of(&seq, LedCode::S), BLUE);
+
+        for _ in 0..4 {
+            seq.key_pressed(KEY_A);
+        }
+        seq.next_state(&layout);
+        assert_eq!(colour_of(&seq, LedCode::A), RED);
+    }
+
+    #[test]
+    fn key_codes() {
+        assert_eq!(LedCode::from_key_code(KEY_ESC), &[LedCode::Esc]);
+        assert_eq!(LedCode::from_key_code(KEY_A), &[LedCode::A]);
+        assert!(LedCode::from_key_code(57).contains(&LedCode::Spacebar));
+        assert!(LedCode::from_key_code(0).is_empty());
+
+        let mut seq = AdvancedEffects::new(false);
+        seq.push(Effect::Static(Static::new(LedCode::A, RED)));
+        assert!(!seq.is_reactive());
+    }
 }
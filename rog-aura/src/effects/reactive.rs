@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+
+use super::{blend, key_centre, speed_step, EffectState, RING_WIDTH};
+use crate::keyboard::{KeyLayout, LedCode};
+use crate::{Colour, Speed};
+
+/// Rings spreading from pressed keys are dropped beyond this many
+const MAX_RINGS: usize = 8;
+
+/// How much brightness a lit key loses each frame
+fn fade_step(speed: Speed) -> f32 {
+    match speed {
+        Speed::Low => 0.02,
+        Speed::Med => 0.04,
+        Speed::High => 0.08,
+    }
+}
+
+/// The key lights up in `key_colour` when pressed then fades back to
+/// `background`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Reactive {
+    led: LedCode,
+    key_colour: Colour,
+    background: Colour,
+    /// How fast the key fades
+    speed: Speed,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    brightness: f32,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl Reactive {
+    pub fn new(address: LedCode, colour: Colour, background: Colour, speed: Speed) -> Self {
+        Self {
+            led: address,
+            key_colour: colour,
+            background,
+            speed,
+            brightness: 0.0,
+            colour: background,
+        }
+    }
+}
+
+impl EffectState for Reactive {
+    fn next_colour_state(&mut self, _layout: &KeyLayout) {
+        self.colour = blend(self.background, self.key_colour, self.brightness);
+        self.brightness = (self.brightness - fade_step(self.speed)).max(0.0);
+    }
+
+    fn get_colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn get_led(&self) -> LedCode {
+        self.led
+    }
+
+    fn set_led(&mut self, address: LedCode) {
+        self.led = address;
+    }
+
+    fn key_pressed(&mut self, key: LedCode) {
+        if key == self.led {
+            self.brightness = 1.0;
+        }
+    }
+
+    fn is_reactive(&self) -> bool {
+        true
+    }
+}
+
+/// A ring of `colour` spreading out from every pressed key
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReactiveRipple {
+    led: LedCode,
+    ring_colour: Colour,
+    background: Colour,
+    speed: Speed,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    centre: Option<Option<(f32, f32)>>,
+    /// Keys pressed since the last frame
+    #[serde(skip)]
+    pressed: Vec<LedCode>,
+    /// The distance from this key to the origin of each ring, and the ring
+    /// radius
+    #[serde(skip)]
+    rings: Vec<(f32, f32)>,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl ReactiveRipple {
+    pub fn new(address: LedCode, colour: Colour, background: Colour, speed: Speed) -> Self {
+        Self {
+            led: address,
+            ring_colour: colour,
+            background,
+            speed,
+            centre: None,
+            pressed: Vec::new(),
+            rings: Vec::new(),
+            colour: background,
+        }
+    }
+}
+
+impl EffectState for ReactiveRipple {
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let centre = *self
+            .centre
+            .get_or_insert_with(|| key_centre(layout, self.led));
+        let Some((x, y)) = centre else {
+            self.pressed.clear();
+            return;
+        };
+        for key in self.pressed.drain(..) {
+            // Keys with several LEDs only have some of them in a layout
+            if let Some((ox, oy)) = key_centre(layout, key) {
+                self.rings.push(((x - ox).hypot(y - oy), 0.0));
+            }
+        }
+        if self.rings.len() > MAX_RINGS {
+            self.rings.drain(..self.rings.len() - MAX_RINGS);
+        }
+
+        let amount = self
+            .rings
+            .iter()
+            .map(|(distance, radius)| 1.0 - (distance - radius).abs() / RING_WIDTH)
+            .fold(0.0, f32::max);
+        self.colour = blend(self.background, self.ring_colour, amount);
+
+        let step = speed_step(self.speed) * layout.max_width().max(1.0);
+        for (_, radius) in &mut self.rings {
+            *radius += step;
+        }
+        // Done with a ring once it has passed this key
+        self.rings
+            .retain(|(distance, radius)| *radius < distance + RING_WIDTH);
+    }
+
+    fn get_colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn get_led(&self) -> LedCode {
+        self.led
+    }
+
+    fn set_led(&mut self, address: LedCode) {
+        self.led = address;
+        self.centre = None;
+        self.rings.clear();
+    }
+
+    fn key_pressed(&mut self, key: LedCode) {
+        self.pressed.push(key);
+    }
+
+    fn is_reactive(&self) -> bool {
+        true
+    }
+}
+
+/// Colours the key by how often it is pressed, from `cold` for unused keys to
+/// `hot` for the most used. Heat builds with each press and slowly cools.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Heatmap {
+    led: LedCode,
+    cold: Colour,
+    hot: Colour,
+    /// Presses in quick succession to reach `hot`
+    presses: u16,
+    /// How fast the key cools
+    speed: Speed,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    heat: f32,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl Heatmap {
+    pub fn new(address: LedCode, cold: Colour, hot: Colour, presses: u16, speed: Speed) -> Self {
+        Self {
+            led: address,
+            cold,
+            hot,
+            presses: presses.max(1),
+            speed,
+            heat: 0.0,
+            colour: cold,
+        }
+    }
+}
+
+impl EffectState for Heatmap {
+    fn next_colour_state(&mut self, _layout: &KeyLayout) {
+        self.colour = blend(self.cold, self.hot, self.heat);
+        let cooling = match self.speed {
+            Speed::Low => 0.0005,
+            Speed::Med => 0.001,
+            Speed::High => 0.002,
+        };
+        self.heat = (self.heat - cooling).max(0.0);
+    }
+
+    fn get_colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn get_led(&self) -> LedCode {
+        self.led
+    }
+
+    fn set_led(&mut self, address: LedCode) {
+        self.led = address;
+        self.heat = 0.0;
+    }
+
+    fn key_pressed(&mut self, key: LedCode) {
+        if key == self.led {
+            self.heat = (self.heat + 1.0 / self.presses as f32).min(1.0);
+        }
+    }
+
+    fn is_reactive(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use super::{blend, key_centre, speed_step, EffectState};
+use crate::keyboard::{KeyLayout, LedCode};
+use crate::{Colour, Speed};
+
+/// How wide the ring of a ripple is, in the same units as `KeyShape`
+pub(crate) const RING_WIDTH: f32 = 1.5;
+
+/// A ring of colour spreading out from the key `origin` across the keyboard,
+/// starting again once it has passed every key
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Ripple {
+    led: LedCode,
+    origin: LedCode,
+    /// The colour of the ring, keys outside it are off
+    ring_colour: Colour,
+    speed: Speed,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    distance: Option<f32>,
+    #[serde(skip)]
+    radius: f32,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl Ripple {
+    pub fn new(address: LedCode, origin: LedCode, colour: Colour, speed: Speed) -> Self {
+        Self {
+            led: address,
+            origin,
+            ring_colour: colour,
+            speed,
+            distance: None,
+            radius: 0.0,
+            colour: Colour { r: 0, g: 0, b: 0 },
+        }
+    }
+
+    /// Change the key the ripple starts from
+    pub fn set_origin(&mut self, origin: LedCode) {
+        self.origin = origin;
+        self.distance = None;
+        self.radius = 0.0;
+    }
+}
+
+impl EffectState for Ripple {
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let distance = *self.distance.get_or_insert_with(|| {
+            match (
+                key_centre(layout, self.led),
+                key_centre(layout, self.origin),
+            ) {
+                (Some((x, y)), Some((ox, oy))) => (x - ox).hypot(y - oy),
+                _ => f32::MAX,
+            }
+        });
+        // Every key uses the same limit so the ripple restarts on all of them
+        // in the same frame
+        let width = layout.max_width();
+        let limit = width.hypot(layout.max_height()) + RING_WIDTH;
+        let amount = 1.0 - (distance - self.radius).abs() / RING_WIDTH;
+        self.colour = blend(Colour { r: 0, g: 0, b: 0 }, self.ring_colour, amount);
+        self.radius += speed_step(self.speed) * width.max(1.0);
+        if self.radius > limit {
+            self.radius = 0.0;
+        }
+    }
+
+    fn get_colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn get_led(&self) -> LedCode {
+        self.led
+    }
+
+    fn set_led(&mut self, address: LedCode) {
+        self.led = address;
+        self.distance = None;
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+use super::{blend, p_random, EffectState};
+use crate::keyboard::{KeyLayout, LedCode};
+use crate::{effect_state_impl, Colour, Speed};
+
+/// A key which randomly twinkles from `background` up to `star_colour` and back
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Starlight {
+    led: LedCode,
+    star_colour: Colour,
+    background: Colour,
+    /// The chance out of 256 that an unlit key starts to twinkle each frame
+    density: u8,
+    speed: Speed,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    brightness: f32,
+    #[serde(skip)]
+    rising: bool,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl Starlight {
+    pub fn new(
+        address: LedCode,
+        colour: Colour,
+        background: Colour,
+        density: u8,
+        speed: Speed,
+    ) -> Self {
+        Self {
+            led: address,
+            star_colour: colour,
+            background,
+            density,
+            speed,
+            brightness: 0.0,
+            rising: false,
+            colour: background,
+        }
+    }
+}
+
+impl EffectState for Starlight {
+    effect_state_impl!();
+
+    fn next_colour_state(&mut self, _layout: &KeyLayout) {
+        let step = match self.speed {
+            Speed::Low => 0.05,
+            Speed::Med => 0.1,
+            Speed::High => 0.2,
+        };
+        if self.rising {
+            self.brightness += step;
+            if self.brightness >= 1.0 {
+                self.brightness = 1.0;
+                self.rising = false;
+            }
+        } else if self.brightness > 0.0 {
+            self.brightness = (self.brightness - step).max(0.0);
+        } else if p_random() < self.density as i32 {
+            self.rising = true;
+        }
+        self.colour = blend(self.background, self.star_colour, self.brightness);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn twinkle_fades() {
+        let layout = KeyLayout::default_layout();
+        let background = Colour { r: 0, g: 0, b: 32 };
+        let star_colour = Colour { r: 255, g: 0, b: 0 };
+        let mut star = Starlight::new(LedCode::A, star_colour, background, 8, Speed::High);
+        // Start a twinkle directly, the random table is shared with other tests
+        star.rising = true;
+
+        let mut peak = background;
+        for _ in 0..5 {
+            star.next_colour_state(&layout);
+            assert!(star.get_colour().r >= peak.r);
+            peak = star.get_colour();
+        }
+        assert_eq!(peak, star_colour);
+        for _ in 0..5 {
+            star.next_colour_state(&layout);
+        }
+        assert_eq!(star.get_colour(), background);
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use super::{gradient_colour, hue_colour, key_progress, speed_step, EffectState};
+use crate::keyboard::{KeyLayout, LedCode};
+use crate::{Colour, Direction, Speed};
+
+/// A repeating band of colours moving across the keyboard in `direction`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Wave {
+    led: LedCode,
+    /// The colours of the band, blended evenly across the keyboard
+    colours: Vec<Colour>,
+    direction: Direction,
+    speed: Speed,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    position: Option<f32>,
+    #[serde(skip)]
+    offset: f32,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl Wave {
+    pub fn new(address: LedCode, colours: Vec<Colour>, direction: Direction, speed: Speed) -> Self {
+        Self {
+            led: address,
+            colour: gradient_colour(&colours, 0.0, true),
+            colours,
+            direction,
+            speed,
+            position: None,
+            offset: 0.0,
+        }
+    }
+}
+
+impl EffectState for Wave {
+    fn get_colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn get_led(&self) -> LedCode {
+        self.led
+    }
+
+    fn set_led(&mut self, address: LedCode) {
+        self.led = address;
+        self.position = None;
+    }
+
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let position = *self
+            .position
+            .get_or_insert_with(|| key_progress(layout, self.led, self.direction));
+        self.offset = (self.offset + speed_step(self.speed)).fract();
+        self.colour = gradient_colour(
+            &self.colours,
+            (position - self.offset).rem_euclid(1.0),
+            true,
+        );
+    }
+}
+
+/// The full hue spectrum sweeping across the keyboard in `direction`, each key
+/// showing the hue at its position
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RainbowPerKey {
+    led: LedCode,
+    direction: Direction,
+    speed: Speed,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    position: Option<f32>,
+    #[serde(skip)]
+    offset: f32,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl RainbowPerKey {
+    pub fn new(address: LedCode, direction: Direction, speed: Speed) -> Self {
+        Self {
+            led: address,
+            direction,
+            speed,
+            position: None,
+            offset: 0.0,
+            colour: hue_colour(0.0),
+        }
+    }
+}
+
+impl EffectState for RainbowPerKey {
+    fn get_colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn get_led(&self) -> LedCode {
+        self.led
+    }
+
+    fn set_led(&mut self, address: LedCode) {
+        self.led = address;
+        self.position = None;
+    }
+
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let position = *self
+            .position
+            .get_or_insert_with(|| key_progress(layout, self.led, self.direction));
+        self.offset = (self.offset + speed_step(self.speed)).fract();
+        self.colour = hue_colour(position - self.offset);
+    }
+}
@@ -6,6 +6,7 @@ pub enum Error {
     ParseSpeed,
     ParseDirection,
     ParseBrightness,
+    ParsePacket(String),
     IoPath(String, std::io::Error),
     Ron(ron::Error),
     RonParse(ron::error::SpannedError),
@@ -19,6 +20,7 @@ impl fmt::Display for Error {
             Error::ParseSpeed => write!(f, "Could not parse speed"),
             Error::ParseDirection => write!(f, "Could not parse direction"),
             Error::ParseBrightness => write!(f, "Could not parse brightness"),
+            Error::ParsePacket(e) => write!(f, "Could not parse packet: {e}"),
             Error::IoPath(path, io) => write!(f, "IO Error: {path}, {io}"),
             Error::Ron(e) => write!(f, "RON Parse Error: {e}"),
             Error::RonParse(e) => write!(f, "RON Parse Error: {e}"),
@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "dbus")]
 use zbus::zvariant::Type;
 
+use crate::error::Error;
+use crate::Colour;
+
 // TODO: GZ301Z
 // 5dbcd0010300000a00ff0000ff0000ff0000ff0000ff0000ff0000ff0000ff0000ff0000ff0000
 
@@ -168,6 +171,145 @@ pub enum LedCode {
 }
 
 impl LedCode {
+    /// Every variant, in declaration order
+    pub const ALL: [LedCode; 135] = [
+        Self::VolUp,
+        Self::VolDown,
+        Self::MicMute,
+        Self::RogApp,
+        Self::RogFan,
+        Self::Esc,
+        Self::F1,
+        Self::F2,
+        Self::F3,
+        Self::F4,
+        Self::F5,
+        Self::F6,
+        Self::F7,
+        Self::F8,
+        Self::F9,
+        Self::F10,
+        Self::F11,
+        Self::F12,
+        Self::Del,
+        Self::Tilde,
+        Self::N1,
+        Self::N2,
+        Self::N3,
+        Self::N4,
+        Self::N5,
+        Self::N6,
+        Self::N7,
+        Self::N8,
+        Self::N9,
+        Self::N0,
+        Self::Hyphen,
+        Self::Equals,
+        Self::Backspace,
+        Self::Backspace3_1,
+        Self::Backspace3_2,
+        Self::Backspace3_3,
+        Self::Home,
+        Self::Tab,
+        Self::Q,
+        Self::W,
+        Self::E,
+        Self::R,
+        Self::T,
+        Self::Y,
+        Self::U,
+        Self::I,
+        Self::O,
+        Self::P,
+        Self::LBracket,
+        Self::RBracket,
+        Self::BackSlash,
+        Self::PgUp,
+        Self::Caps,
+        Self::A,
+        Self::S,
+        Self::D,
+        Self::F,
+        Self::G,
+        Self::H,
+        Self::J,
+        Self::K,
+        Self::L,
+        Self::SemiColon,
+        Self::Quote,
+        Self::Return,
+        Self::Return3_1,
+        Self::Return3_2,
+        Self::Return3_3,
+        Self::PgDn,
+        Self::LShift,
+        Self::LShift3_1,
+        Self::LShift3_2,
+        Self::LShift3_3,
+        Self::Z,
+        Self::X,
+        Self::C,
+        Self::V,
+        Self::B,
+        Self::N,
+        Self::M,
+        Self::Comma,
+        Self::Period,
+        Self::FwdSlash,
+        Self::Star,
+        Self::NumPadDel,
+        Self::NumPadPlus,
+        Self::NumPadEnter,
+        Self::NumPadPause,
+        Self::NumPadPrtSc,
+        Self::NumPadHome,
+        Self::NumLock,
+        Self::Rshift,
+        Self::Rshift3_1,
+        Self::Rshift3_2,
+        Self::Rshift3_3,
+        Self::End,
+        Self::LCtrl,
+        Self::LFn,
+        Self::Meta,
+        Self::LAlt,
+        Self::Spacebar,
+        Self::Spacebar5_1,
+        Self::Spacebar5_2,
+        Self::Spacebar5_3,
+        Self::Spacebar5_4,
+        Self::Spacebar5_5,
+        Self::Pause,
+        Self::RAlt,
+        Self::PrtSc,
+        Self::RCtrl,
+        Self::Up,
+        Self::Down,
+        Self::Left,
+        Self::Right,
+        Self::RFn,
+        Self::MediaPlay,
+        Self::MediaStop,
+        Self::MediaNext,
+        Self::MediaPrev,
+        Self::LidLogo,
+        Self::LidLeft,
+        Self::LidRight,
+        Self::LightbarRight,
+        Self::LightbarRightCorner,
+        Self::LightbarRightBottom,
+        Self::LightbarLeftBottom,
+        Self::LightbarLeftCorner,
+        Self::LightbarLeft,
+        Self::SingleZone,
+        Self::ZonedKbLeft,
+        Self::ZonedKbLeftMid,
+        Self::ZonedKbRightMid,
+        Self::ZonedKbRight,
+        Self::Spacing,
+        Self::Blocking,
+    ];
+
     pub fn is_placeholder(&self) -> bool {
         matches!(self, Self::Spacing | Self::Blocking)
     }
@@ -304,6 +446,21 @@ impl LedUsbPackets {
     /// Indexing is different for `zoned` and assumes that only one packet is
     /// generated for all the zones
     fn rgb_for_led_code(&mut self, led_code: LedCode) -> Option<&mut [u8]> {
+        let (row, col) = self.address_for_led_code(led_code)?;
+        if self.zoned && row > 0 {
+            warn!(
+                "LedCode {led_code:?} for zoned is not correct or out of Zone range. Setting to 0",
+            );
+            return None;
+        }
+        self.usb_packets
+            .get_mut(row)
+            .map(|pkt| &mut pkt[col..=col + 2])
+    }
+
+    /// The (packet, byte) index of the R,G,B values of `led_code`. This does
+    /// not check that the packet exists
+    fn address_for_led_code(&self, led_code: LedCode) -> Option<(usize, usize)> {
         let zoned = self.zoned;
         // Tuples are indexes in to array
         #[allow(clippy::match_same_arms)]
@@ -458,14 +615,63 @@ impl LedUsbPackets {
             | LedCode::Backspace => return None,
         };
 
+        Some((row, col))
+    }
+
+    /// Parse packets as built by `new_per_key()` or `new_zoned()`, the
+    /// inverse of `get()`. The init message is skipped if present. Per-key
+    /// packets are placed by their key group so may be in any order.
+    pub fn from_packets(packets: &[Vec<u8>]) -> Result<Self, Error> {
+        let init = Self::get_init_msg();
+        let mut packets: Vec<&Vec<u8>> = packets.iter().filter(|p| **p != init).collect();
+        if packets
+            .iter()
+            .any(|p| p.len() != 64 || p[0] != 0x5d || p[1] != 0xbc)
+        {
+            return Err(Error::ParsePacket(
+                "not a per-key or zoned packet".to_owned(),
+            ));
+        }
+
+        if packets.len() == 1 && packets[0][2] == 0x01 {
+            return Ok(Self {
+                usb_packets: vec![packets.remove(0).clone()],
+                zoned: true,
+            });
+        }
+
+        let mut this = Self::new_per_key();
+        for pkt in packets {
+            let group = (pkt[6] >> 4) as usize;
+            match this.usb_packets.get_mut(group) {
+                Some(row) => row.copy_from_slice(pkt),
+                None => return Err(Error::ParsePacket(format!("invalid key group {group}"))),
+            }
+        }
+        Ok(this)
+    }
+
+    /// Get the RGB colour of an `LedCode`
+    pub fn get_colour(&self, key: LedCode) -> Option<Colour> {
+        let (row, col) = self.address_for_led_code(key)?;
         if self.zoned && row > 0 {
-            warn!(
-                "LedCode {led_code:?} for zoned is not correct or out of Zone range. Setting to 0",
-            );
             return None;
         }
+        self.usb_packets.get(row).map(|pkt| Colour {
+            r: pkt[col],
+            g: pkt[col + 1],
+            b: pkt[col + 2],
+        })
+    }
 
-        Some(&mut self.usb_packets[row][col..=col + 2])
+    /// The colour of every `LedCode` that is addressable in these packets.
+    /// Some codes share an address (such as `LShift` and `LShift3_1`) and so
+    /// will have the same colour.
+    pub fn colours(&self) -> Vec<(LedCode, Colour)> {
+        LedCode::ALL
+            .iter()
+            .filter_map(|key| self.get_colour(*key).map(|c| (*key, c)))
+            .collect()
     }
 
     #[inline]
@@ -641,6 +847,7 @@ impl From<&LedCode> for &str {
 #[cfg(test)]
 mod tests {
     use crate::keyboard::{AuraLaptopUsbPackets, LedCode, LedUsbPackets};
+    use crate::Colour;
 
     macro_rules! colour_check_zoned {
         ($zone:expr, $pkt_idx_start:expr) => {
@@ -725,4 +932,81 @@ mod tests {
         assert_eq!(pkt[7][14], 0xff); // M
         assert_eq!(pkt[7][15], 0x00); // M
     }
+
+    fn round_trip(mut packets: LedUsbPackets) {
+        let mut expected = Vec::new();
+        for (i, key) in LedCode::ALL.iter().enumerate() {
+            let colour = Colour {
+                r: i as u8,
+                g: 0xff - i as u8,
+                b: (i as u8).wrapping_mul(7),
+            };
+            packets.set(*key, colour.r, colour.g, colour.b);
+            if let Some(address) = packets.address_for_led_code(*key) {
+                expected.retain(|(a, _)| *a != address);
+                expected.push((address, colour));
+            }
+        }
+
+        let mut raw = packets.get();
+        raw.reverse();
+        raw.insert(0, LedUsbPackets::get_init_msg().to_vec());
+        let decoded = LedUsbPackets::from_packets(&raw).unwrap();
+        assert_eq!(decoded.get(), packets.get());
+        assert_eq!(decoded.zoned, packets.zoned);
+
+        let colours = decoded.colours();
+        assert!(!colours.is_empty());
+        for (key, colour) in colours {
+            let address = decoded.address_for_led_code(key).unwrap();
+            let (_, want) = expected.iter().find(|(a, _)| *a == address).unwrap();
+            assert_eq!(colour, *want, "{key:?}");
+        }
+    }
+
+    #[test]
+    fn per_key_round_trip() {
+        round_trip(LedUsbPackets::new_per_key());
+    }
+
+    #[test]
+    fn zoned_round_trip() {
+        round_trip(LedUsbPackets::new_zoned(true));
+        round_trip(LedUsbPackets::new_zoned(false));
+    }
+
+    #[test]
+    fn decode_bad_packets() {
+        assert!(LedUsbPackets::from_packets(&[vec![
+            0x5d, 0xb3
+        ]])
+        .is_err());
+        let mut pkt = LedUsbPackets::new_per_key().get();
+        pkt[3][6] = 0xf0;
+        assert!(LedUsbPackets::from_packets(&pkt).is_err());
+    }
+
+    #[test]
+    fn led_code_all_is_complete() {
+        use serde::de::value::{Error, StrDeserializer, U32Deserializer};
+        use serde::Deserialize;
+
+        // The derived `Deserialize` knows every variant by index and by name,
+        // so a variant missing from `ALL` shows up as an extra index here
+        let by_index: Vec<LedCode> = (0..=u8::MAX as u32)
+            .map_while(|i| LedCode::deserialize(U32Deserializer::<Error>::new(i)).ok())
+            .collect();
+        assert_eq!(by_index, LedCode::ALL);
+        assert!(
+            LedCode::deserialize(U32Deserializer::<Error>::new(LedCode::ALL.len() as u32)).is_err()
+        );
+
+        for code in LedCode::ALL {
+            let name = format!("{code:?}");
+            assert_eq!(
+                LedCode::deserialize(StrDeserializer::<Error>::new(&name)).unwrap(),
+                code
+            );
+        }
+    }
 }
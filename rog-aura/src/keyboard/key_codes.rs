@@ -0,0 +1,140 @@
+use super::LedCode;
+
+impl LedCode {
+    /// The LEDs lit by a key, `code` being the Linux input key code
+    /// (`KEY_*` in `linux/input-event-codes.h`) as read from evdev. Keys with
+    /// several LEDs return all of them, layouts will only have some.
+    pub fn from_key_code(code: u16) -> &'static [LedCode] {
+        use LedCode::*;
+        match code {
+            1 => &[Esc],
+            2 => &[N1],
+            3 => &[N2],
+            4 => &[N3],
+            5 => &[N4],
+            6 => &[N5],
+            7 => &[N6],
+            8 => &[N7],
+            9 => &[N8],
+            10 => &[N9],
+            11 => &[N0],
+            12 => &[Hyphen],
+            13 => &[Equals],
+            14 => &[
+                Backspace, Backspace3_1, Backspace3_2, Backspace3_3,
+            ],
+            15 => &[Tab],
+            16 => &[Q],
+            17 => &[W],
+            18 => &[E],
+            19 => &[R],
+            20 => &[T],
+            21 => &[Y],
+            22 => &[U],
+            23 => &[I],
+            24 => &[O],
+            25 => &[P],
+            26 => &[LBracket],
+            27 => &[RBracket],
+            28 => &[
+                Return, Return3_1, Return3_2, Return3_3,
+            ],
+            29 => &[LCtrl],
+            30 => &[A],
+            31 => &[S],
+            32 => &[D],
+            33 => &[F],
+            34 => &[G],
+            35 => &[H],
+            36 => &[J],
+            37 => &[K],
+            38 => &[L],
+            39 => &[SemiColon],
+            40 => &[Quote],
+            41 => &[Tilde],
+            42 => &[
+                LShift, LShift3_1, LShift3_2, LShift3_3,
+            ],
+            43 => &[BackSlash],
+            44 => &[Z],
+            45 => &[X],
+            46 => &[C],
+            47 => &[V],
+            48 => &[B],
+            49 => &[N],
+            50 => &[M],
+            51 => &[Comma],
+            52 => &[Period],
+            53 => &[FwdSlash],
+            54 => &[
+                Rshift, Rshift3_1, Rshift3_2, Rshift3_3,
+            ],
+            // KEY_KPASTERISK
+            55 => &[Star],
+            56 => &[LAlt],
+            57 => &[
+                Spacebar, Spacebar5_1, Spacebar5_2, Spacebar5_3, Spacebar5_4, Spacebar5_5,
+            ],
+            58 => &[Caps],
+            59 => &[F1],
+            60 => &[F2],
+            61 => &[F3],
+            62 => &[F4],
+            63 => &[F5],
+            64 => &[F6],
+            65 => &[F7],
+            66 => &[F8],
+            67 => &[F9],
+            68 => &[F10],
+            69 => &[NumLock],
+            // The numpad shares LEDs with the number row
+            71 => &[N7],
+            72 => &[N8],
+            73 => &[N9],
+            74 => &[Hyphen],
+            75 => &[N4],
+            76 => &[N5],
+            77 => &[N6],
+            78 => &[NumPadPlus],
+            79 => &[N1],
+            80 => &[N2],
+            81 => &[N3],
+            82 => &[N0],
+            83 => &[NumPadDel],
+            87 => &[F11],
+            88 => &[F12],
+            96 => &[NumPadEnter],
+            97 => &[RCtrl],
+            98 => &[FwdSlash],
+            99 => &[
+                PrtSc, NumPadPrtSc,
+            ],
+            100 => &[RAlt],
+            102 => &[
+                Home, NumPadHome,
+            ],
+            103 => &[Up],
+            104 => &[PgUp],
+            105 => &[Left],
+            106 => &[Right],
+            107 => &[End],
+            108 => &[Down],
+            109 => &[PgDn],
+            111 => &[Del],
+            114 => &[VolDown],
+            115 => &[VolUp],
+            119 => &[
+                Pause, NumPadPause,
+            ],
+            125 => &[Meta],
+            // KEY_PROG1, the ROG key on most models
+            148 => &[RogApp],
+            163 => &[MediaNext],
+            164 => &[MediaPlay],
+            165 => &[MediaPrev],
+            166 => &[MediaStop],
+            248 => &[MicMute],
+            _ => &[],
+        }
+    }
+}
@@ -163,6 +163,17 @@ impl KeyRow {
     }
 }
 
+/// An LED key as placed by `KeyLayout::key_positions()`, in the same units as
+/// `KeyShape`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyPosition {
+    pub led: LedCode,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct KeyLayout {
     /// Localization of this keyboard layout
@@ -189,7 +200,8 @@ pub struct KeyLayout {
 }
 
 impl KeyLayout {
-    fn from_file(path: &Path) -> Result<Self, Error> {
+    /// Load a single layout file, such as those in `rog-aura/data/layouts`
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
         let buf: String = std::fs::read_to_string(path)
             .map_err(|e| Error::IoPath(path.to_string_lossy().to_string(), e))?;
         if buf.is_empty() {
@@ -266,6 +278,42 @@ impl KeyLayout {
         height
     }
 
+    /// Place every LED key, rows are stacked downwards from y=0 and each key
+    /// follows the previous in the row. Keys fit within `max_width()` and
+    /// `max_height()`, lightbars may use negative padding to wrap around the
+    /// keyboard so can fall outside it.
+    pub fn key_positions(&self) -> Vec<KeyPosition> {
+        let mut keys = Vec::new();
+        let mut y = 0.0;
+        for row in &self.key_rows {
+            let mut x = 0.0;
+            for (led, shape) in &row.built_row {
+                match shape {
+                    KeyShape::Led {
+                        width,
+                        height,
+                        pad_left,
+                        pad_right,
+                        pad_top,
+                        ..
+                    } => {
+                        keys.push(KeyPosition {
+                            led: *led,
+                            x: x + pad_left,
+                            y: y + pad_top,
+                            width: *width,
+                            height: *height,
+                        });
+                        x += width + pad_left + pad_right;
+                    }
+                    KeyShape::Blank { width, .. } => x += width,
+                }
+            }
+            y += row.height();
+        }
+        keys
+    }
+
     pub fn max_width(&self) -> f32 {
         let mut width = 0.0;
         for r in &self.key_rows {
@@ -435,7 +483,25 @@ mod tests {
     use std::path::PathBuf;
 
     use crate::aura_detection::LedSupportFile;
-    use crate::keyboard::KeyLayout;
+    use crate::keyboard::{KeyLayout, LedCode};
+
+    #[test]
+    fn key_positions() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("data/layouts/g513i-per-key_US.ron");
+        let layout = KeyLayout::from_file(&path).unwrap();
+        let keys = layout.key_positions();
+
+        let find = |led: LedCode| *keys.iter().find(|k| k.led == led).unwrap();
+        let (q, w, a) = (find(LedCode::Q), find(LedCode::W), find(LedCode::A));
+        assert_eq!(q.y, w.y);
+        assert!(w.x > q.x + q.width);
+        assert!(a.y > q.y + q.height);
+        for key in keys.iter().filter(|k| !k.led.is_lightbar_zone()) {
+            assert!(key.x >= 0.0 && key.x + key.width <= layout.max_width());
+            assert!(key.y >= 0.0 && key.y + key.height <= layout.max_height());
+        }
+    }
 
     #[test]
     fn check_parse_all() {
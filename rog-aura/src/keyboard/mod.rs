@@ -2,6 +2,9 @@
 mod advanced;
 pub use advanced::*;
 
+/// Mapping of evdev key codes to LEDs
+mod key_codes;
+
 /// Helpers for consructing keyboard layouts for UI use and effects
 mod layouts;
 pub use layouts::*;
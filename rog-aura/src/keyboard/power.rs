@@ -9,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use zbus::zvariant::{OwnedValue, Type, Value};
 
 use crate::aura_detection::LedSupportData;
+use crate::error::Error;
 use crate::{AuraDeviceType, PowerZones};
 
 /// Meaning of this struct depends on the laptop generation.
@@ -102,6 +103,73 @@ impl AuraPowerState {
         ]
     }
 
+    fn tuf_from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 5 || bytes[0] != 1 {
+            return Err(Error::ParsePacket(format!(
+                "not a TUF power packet: {bytes:02x?}"
+            )));
+        }
+        Ok(Self {
+            zone: PowerZones::Keyboard,
+            boot: bytes[1] != 0,
+            awake: bytes[2] != 0,
+            sleep: bytes[3] != 0,
+            shutdown: false,
+        })
+    }
+
+    /// The inverse of `old_to_bytes()`. Boot and shutdown share bits
+    fn old_from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 3 {
+            return Err(Error::ParsePacket(format!(
+                "not enough bytes for power state: {bytes:02x?}"
+            )));
+        }
+        let a = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        let has = |bits: OldAuraPower| a & bits as u32 == bits as u32;
+        let zone = match (has(OldAuraPower::Keyboard), has(OldAuraPower::Lightbar)) {
+            (true, true) => PowerZones::KeyboardAndLightbar,
+            (true, false) => PowerZones::Keyboard,
+            (false, true) => PowerZones::Lightbar,
+            (false, false) => PowerZones::None,
+        };
+        Ok(Self {
+            zone,
+            boot: has(OldAuraPower::Boot),
+            awake: has(OldAuraPower::Awake),
+            sleep: has(OldAuraPower::Sleep),
+            shutdown: has(OldAuraPower::Boot),
+        })
+    }
+
+    /// The inverse of `new_to_byte()` for a single zone
+    pub fn new_from_byte(zone: PowerZones, a: u32) -> Self {
+        let (boot, awake, sleep, shutdown) = match zone {
+            PowerZones::Logo => (0, 2, 4, 6),
+            PowerZones::Ally => (0, 1, 2, 3),
+            PowerZones::Keyboard => (1, 3, 5, 7),
+            PowerZones::Lightbar => (7 + 2, 7 + 3, 7 + 4, 7 + 5),
+            PowerZones::Lid => (15 + 1, 15 + 2, 15 + 3, 15 + 4),
+            PowerZones::RearGlow => (23 + 1, 23 + 2, 23 + 3, 23 + 4),
+            PowerZones::None | PowerZones::KeyboardAndLightbar => {
+                return Self {
+                    zone,
+                    boot: false,
+                    awake: false,
+                    sleep: false,
+                    shutdown: false,
+                }
+            }
+        };
+        Self {
+            zone,
+            boot: a & (1 << boot) != 0,
+            awake: a & (1 << awake) != 0,
+            sleep: a & (1 << sleep) != 0,
+            shutdown: a & (1 << shutdown) != 0,
+        }
+    }
+
     pub fn new_to_byte(&self) -> u32 {
         match self.zone {
             PowerZones::Logo => {
@@ -222,6 +290,53 @@ impl LaptopAuraPower {
         }
     }
 
+    /// Parse the output of `to_bytes()` back in to power states. The 2021+
+    /// format does not record which zones a laptop has, so a state for every
+    /// zone is returned.
+    pub fn from_bytes(aura_type: AuraDeviceType, bytes: &[u8]) -> Result<Self, Error> {
+        const ALLY_PREFIX: [u8; 4] = [
+            0x5d, 0xd1, 0x09, 0x01,
+        ];
+        if bytes.len() >= 5 && bytes[..4] == ALLY_PREFIX {
+            return Ok(Self {
+                states: vec![AuraPowerState::new_from_byte(PowerZones::Ally, bytes[4] as u32)],
+            });
+        }
+        match aura_type {
+            AuraDeviceType::LaptopKeyboard2021 | AuraDeviceType::Ally | AuraDeviceType::Unknown => {
+                if bytes.len() < 4 {
+                    return Err(Error::ParsePacket(format!(
+                        "not enough bytes for power state: {bytes:02x?}"
+                    )));
+                }
+                let a = u32::from_le_bytes([
+                    bytes[0], bytes[1], bytes[2], bytes[3],
+                ]);
+                Ok(Self {
+                    states: [
+                        PowerZones::Logo,
+                        PowerZones::Keyboard,
+                        PowerZones::Lightbar,
+                        PowerZones::Lid,
+                        PowerZones::RearGlow,
+                    ]
+                    .iter()
+                    .map(|zone| AuraPowerState::new_from_byte(*zone, a))
+                    .collect(),
+                })
+            }
+            AuraDeviceType::LaptopKeyboardPre2021 => Ok(Self {
+                states: vec![AuraPowerState::old_from_bytes(bytes)?],
+            }),
+            AuraDeviceType::LaptopKeyboardTuf => Ok(Self {
+                states: vec![AuraPowerState::tuf_from_bytes(bytes)?],
+            }),
+            AuraDeviceType::ScsiExtDisk | AuraDeviceType::AnimeOrSlash => Err(Error::ParsePacket(
+                format!("{aura_type:?} has no power packet"),
+            )),
+        }
+    }
+
     pub fn to_bytes(&self, aura_type: AuraDeviceType) -> Vec<u8> {
         if let Some(stuff) = self.states.first() {
             if stuff.zone == PowerZones::Ally {
@@ -280,6 +395,7 @@ impl LaptopAuraPower {
 /// | 0011, 0000 | 0000, 1000 | 0000, 0100 | Sleep    | 30,08,04 |
 /// | 1111, 1111 | 0001, 1111 | 0000, 1111 | all on   |          |
 #[repr(u32)]
+#[derive(Clone, Copy)]
 enum OldAuraPower {
     Awake = 0x000002,
     Boot = 0xc31209,
@@ -659,4 +775,102 @@ mod test {
         });
         assert_eq!(byte1, "11111111, 00011110, 00001111, 00001111");
     }
+
+    fn states_from_bits(zone: PowerZones, bits: u8) -> AuraPowerState {
+        AuraPowerState {
+            zone,
+            boot: bits & 1 != 0,
+            awake: bits & 2 != 0,
+            sleep: bits & 4 != 0,
+            shutdown: bits & 8 != 0,
+        }
+    }
+
+    #[test]
+    fn power_round_trip_post2021() {
+        let zones = [
+            PowerZones::Logo,
+            PowerZones::Keyboard,
+            PowerZones::Lightbar,
+            PowerZones::Lid,
+            PowerZones::RearGlow,
+        ];
+        // Every combination of states for each zone, while the other zones
+        // cycle through theirs
+        for (i, zone) in zones.iter().enumerate() {
+            for bits in 0..16u8 {
+                let power = LaptopAuraPower {
+                    states: zones
+                        .iter()
+                        .enumerate()
+                        .map(|(j, z)| {
+                            if *z == *zone {
+                                states_from_bits(*z, bits)
+                            } else {
+                                states_from_bits(*z, bits.wrapping_add((i + j) as u8) % 16)
+                            }
+                        })
+                        .collect(),
+                };
+                let bytes = power.to_bytes(AuraDeviceType::LaptopKeyboard2021);
+                let decoded =
+                    LaptopAuraPower::from_bytes(AuraDeviceType::LaptopKeyboard2021, &bytes)
+                        .unwrap();
+                assert_eq!(decoded, power);
+            }
+        }
+    }
+
+    #[test]
+    fn power_round_trip_ally() {
+        for bits in 0..16u8 {
+            let power = LaptopAuraPower {
+                states: vec![states_from_bits(PowerZones::Ally, bits)],
+            };
+            let bytes = power.to_bytes(AuraDeviceType::Ally);
+            assert_eq!(
+                LaptopAuraPower::from_bytes(AuraDeviceType::Ally, &bytes).unwrap(),
+                power
+            );
+        }
+    }
+
+    #[test]
+    fn power_round_trip_pre2021() {
+        for zone in [
+            PowerZones::Keyboard,
+            PowerZones::Lightbar,
+            PowerZones::KeyboardAndLightbar,
+        ] {
+            for bits in 0..8u8 {
+                // Shutdown is the same bits as boot
+                let mut state = states_from_bits(zone, bits);
+                state.shutdown = state.boot;
+                let power = LaptopAuraPower {
+                    states: vec![state],
+                };
+                let bytes = power.to_bytes(AuraDeviceType::LaptopKeyboardPre2021);
+                assert_eq!(
+                    LaptopAuraPower::from_bytes(AuraDeviceType::LaptopKeyboardPre2021, &bytes)
+                        .unwrap(),
+                    power
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn power_round_trip_tuf() {
+        for bits in 0..8u8 {
+            let power = LaptopAuraPower {
+                states: vec![states_from_bits(PowerZones::Keyboard, bits)],
+            };
+            let bytes = power.to_bytes(AuraDeviceType::LaptopKeyboardTuf);
+            assert_eq!(
+                LaptopAuraPower::from_bytes(AuraDeviceType::LaptopKeyboardTuf, &bytes).unwrap(),
+                power
+            );
+        }
+        assert!(LaptopAuraPower::from_bytes(AuraDeviceType::LaptopKeyboardTuf, &[0, 1]).is_err());
+    }
 }
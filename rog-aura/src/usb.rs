@@ -1,3 +1,7 @@
+use crate::error::Error;
+use crate::keyboard::LaptopAuraPower;
+use crate::{AuraDeviceType, AuraEffect};
+
 // Only these two packets must be 17 bytes
 pub const AURA_LAPTOP_LED_APPLY: [u8; 17] = [
     0x5d, 0xb4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -5,3 +9,41 @@ pub const AURA_LAPTOP_LED_APPLY: [u8; 17] = [
 pub const AURA_LAPTOP_LED_SET: [u8; 17] = [
     0x5d, 0xb5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
+
+/// A report written to a laptop keyboard, decoded by type
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuraLaptopReport {
+    /// A builtin mode, zoned or unzoned
+    Effect(AuraEffect),
+    /// `AURA_LAPTOP_LED_SET`
+    Set,
+    /// `AURA_LAPTOP_LED_APPLY`
+    Apply,
+    /// The combined power states of all zones
+    Power(LaptopAuraPower),
+    /// A per-key or init packet
+    PerKey(Vec<u8>),
+    /// Any report not recognised
+    Unknown(Vec<u8>),
+}
+
+impl AuraLaptopReport {
+    /// Decode a single report as written to the hidraw node of a device of
+    /// type `aura_type`
+    pub fn decode(aura_type: AuraDeviceType, report: &[u8]) -> Result<Self, Error> {
+        if report.len() < 2 || report[0] != 0x5d {
+            return Ok(Self::Unknown(report.to_vec()));
+        }
+        Ok(match report[1] {
+            0xb3 => Self::Effect(AuraEffect::try_from(report)?),
+            0xb4 => Self::Apply,
+            0xb5 => Self::Set,
+            0xbc => Self::PerKey(report.to_vec()),
+            0xbd if report.get(2) == Some(&0x01) => {
+                Self::Power(LaptopAuraPower::from_bytes(aura_type, &report[3..])?)
+            }
+            0xd1 => Self::Power(LaptopAuraPower::from_bytes(aura_type, report)?),
+            _ => Self::Unknown(report.to_vec()),
+        })
+    }
+}
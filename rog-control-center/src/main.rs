@@ -6,7 +6,6 @@ use std::thread::{self, sleep};
 use std::time::Duration;
 
 use config_traits::{StdConfig, StdConfigLoad1};
-use dmi_id::DMIID;
 use gumdrop::Options;
 use log::{debug, info, warn, LevelFilter};
 use rog_control_center::cli_options::CliStart;
@@ -20,6 +19,7 @@ use rog_control_center::zbus_proxies::{
     AppState, ROGCCZbus, ROGCCZbusProxyBlocking, ZBUS_IFACE, ZBUS_PATH,
 };
 use rog_control_center::{print_versions, MainWindow};
+use rog_platform::sysfs::SysfsRoot;
 use tokio::runtime::Runtime;
 
 #[tokio::main]
@@ -98,7 +98,7 @@ async fn main() -> Result<()> {
             err
         })?;
 
-    let dmi = DMIID::new().unwrap_or_default();
+    let dmi = SysfsRoot::get().dmi_id().unwrap_or_default();
     let board_name = dmi.board_name;
     let prod_family = dmi.product_family;
     info!("Running on {board_name}, product: {prod_family}");
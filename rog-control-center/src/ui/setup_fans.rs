@@ -1,14 +1,24 @@
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 
 use log::error;
 use rog_dbus::zbus_fan_curves::FanCurvesProxy;
 use rog_dbus::zbus_platform::PlatformProxy;
 use rog_platform::platform::PlatformProfile;
+use rog_profiles::calibration::FanCalibration;
+use rog_profiles::curve_points::{CurvePoints, CurvePreset};
 use rog_profiles::fan_curve_set::CurveData;
-use slint::{ComponentHandle, Model, Weak};
+use rog_profiles::telemetry::FanTelemetry;
+use rog_profiles::FanCurvePU;
+use slint::{ComponentHandle, Model, ModelRc, Weak};
 
 use crate::config::Config;
-use crate::{FanPageData, FanType, MainWindow, Node};
+use crate::{FanCalibrationData, FanLiveData, FanPageData, FanType, MainWindow, Node, Profile};
+
+/// RPM samples kept for the history plot
+const RPM_HISTORY_LEN: usize = 60;
+/// Telemetry is off in asusd until something asks for it
+const TELEMETRY_INTERVAL_MS: u32 = 2000;
 
 pub fn update_fan_data(
     handle: Weak<MainWindow>,
@@ -96,6 +106,86 @@ pub fn update_fan_data(
         .ok();
 }
 
+/// An SVG path of the RPM history in a 100x100 viewbox, scaled to `max`
+fn rpm_history_path(history: &VecDeque<u32>, max: u32) -> String {
+    let step = 100.0 / (RPM_HISTORY_LEN - 1) as f32;
+    let max = max.max(1) as f32;
+    let mut path = String::new();
+    for (i, rpm) in history.iter().enumerate() {
+        let cmd = if i == 0 { 'M' } else { 'L' };
+        let x = i as f32 * step;
+        let y = 100.0 - *rpm as f32 / max * 100.0;
+        path.push_str(&format!("{cmd}{x:.1} {y:.1} "));
+    }
+    path
+}
+
+fn update_fan_live(
+    handle: Weak<MainWindow>,
+    samples: &[FanTelemetry],
+    history: &mut HashMap<FanCurvePU, VecDeque<u32>>,
+) {
+    let mut live = Vec::with_capacity(samples.len());
+    for sample in samples {
+        let rpms = history.entry(sample.fan).or_default();
+        if rpms.len() == RPM_HISTORY_LEN {
+            rpms.pop_front();
+        }
+        rpms.push_back(sample.rpm);
+        let max_rpm = rpms.iter().copied().max().unwrap_or_default();
+        live.push((sample.fan, FanLiveData {
+            available: true,
+            temp: sample.temp as f32,
+            rpm: sample.rpm as i32,
+            pwm: sample.pwm as i32,
+            rpm_history: rpm_history_path(rpms, max_rpm).into(),
+            max_rpm: max_rpm as i32,
+        }));
+    }
+    handle
+        .upgrade_in_event_loop(move |handle| {
+            let global = handle.global::<FanPageData>();
+            for (fan, data) in live {
+                match fan {
+                    FanCurvePU::CPU => global.set_cpu_live(data),
+                    FanCurvePU::GPU => global.set_gpu_live(data),
+                    FanCurvePU::MID => global.set_mid_live(data),
+                }
+            }
+        })
+        .map_err(|e| error!("update_fan_live: upgrade_in_event_loop: {e:?}"))
+        .ok();
+}
+
+/// Show RPM on the curve editor for each calibrated fan
+fn update_fan_calibration(handle: Weak<MainWindow>, calibration: Vec<FanCalibration>) {
+    let tables: Vec<(FanCurvePU, i32, Vec<i32>)> = calibration
+        .iter()
+        .map(|c| {
+            let rpm = (0..=u8::MAX).map(|pwm| c.rpm_at(pwm) as i32).collect();
+            (c.fan, c.spin_up_pwm().unwrap_or_default() as i32, rpm)
+        })
+        .collect();
+    handle
+        .upgrade_in_event_loop(move |handle| {
+            let global = handle.global::<FanPageData>();
+            for (fan, spin_up, rpm) in tables {
+                let data = FanCalibrationData {
+                    available: true,
+                    spin_up,
+                    rpm: rpm.as_slice().into(),
+                };
+                match fan {
+                    FanCurvePU::CPU => global.set_cpu_calibration(data),
+                    FanCurvePU::GPU => global.set_gpu_calibration(data),
+                    FanCurvePU::MID => global.set_mid_calibration(data),
+                }
+            }
+        })
+        .map_err(|e| error!("update_fan_calibration: upgrade_in_event_loop: {e:?}"))
+        .ok();
+}
+
 pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
     let handle = ui.as_weak();
 
@@ -153,12 +243,12 @@ pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
 
         // TODO: the fan curve stuff was written donkeys ago with the expectation that
         // only 3 profiles existed
-        let profile = if platform_profile_choices.contains(&PlatformProfile::Quiet) {
+        let quiet_profile = if platform_profile_choices.contains(&PlatformProfile::Quiet) {
             PlatformProfile::Quiet
         } else {
             PlatformProfile::LowPower
         };
-        let quiet = match fans.fan_curve_data(profile).await {
+        let quiet = match fans.fan_curve_data(quiet_profile).await {
             Ok(data) => data,
             Err(e) => {
                 error!("Couldn't get quiet data: {e:}");
@@ -166,7 +256,52 @@ pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
             }
         };
 
-        update_fan_data(handle, balanced, perf, quiet);
+        update_fan_data(handle.clone(), balanced, perf, quiet);
+        if let Ok(calibration) = fans.fan_calibration().await {
+            update_fan_calibration(handle, calibration);
+        }
+
+        let handle_calibration = handle_copy.clone();
+        let fans_calibration = fans.clone();
+        tokio::spawn(async move {
+            let mut x = fans_calibration.receive_calibrating_changed().await;
+            use futures_util::StreamExt;
+            while let Some(e) = x.next().await {
+                let Ok(calibrating) = e.get().await else {
+                    continue;
+                };
+                handle_calibration
+                    .upgrade_in_event_loop(move |handle| {
+                        handle.global::<FanPageData>().set_calibrating(calibrating);
+                    })
+                    .ok();
+                if !calibrating {
+                    if let Ok(calibration) = fans_calibration.fan_calibration().await {
+                        update_fan_calibration(handle_calibration.clone(), calibration);
+                    }
+                }
+            }
+        });
+
+        let handle_live = handle_copy.clone();
+        let fans_live = fans.clone();
+        tokio::spawn(async move {
+            let mut history = HashMap::new();
+            if fans_live.telemetry_interval().await.is_ok_and(|i| i == 0) {
+                fans_live
+                    .set_telemetry_interval(TELEMETRY_INTERVAL_MS)
+                    .await
+                    .map_err(|e| error!("{e:}"))
+                    .ok();
+            }
+            let mut x = fans_live.receive_fan_telemetry_changed().await;
+            use futures_util::StreamExt;
+            while let Some(e) = x.next().await {
+                if let Ok(samples) = e.get().await {
+                    update_fan_live(handle_live.clone(), &samples, &mut history);
+                }
+            }
+        });
 
         let handle_next1 = handle_copy.clone();
         if let Err(e) = handle_copy.upgrade_in_event_loop(move |handle| {
@@ -194,7 +329,7 @@ pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
                         return;
                     };
                     let Ok(quiet) = fans
-                        .fan_curve_data(PlatformProfile::Quiet)
+                        .fan_curve_data(quiet_profile)
                         .await
                         .map_err(|e| error!("{e:}"))
                     else {
@@ -203,12 +338,93 @@ pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
                     update_fan_data(handle_next, balanced, perf, quiet);
                 });
             });
+            let fans2 = fans.clone();
+            global.on_calibrate(move || {
+                let fans = fans2.clone();
+                tokio::spawn(async move {
+                    fans.calibrate_fans(&[])
+                        .await
+                        .map_err(|e| error!("{e:}"))
+                        .ok()
+                });
+            });
+            let handle_points = handle.as_weak();
+            global.on_add_point(move |fan, profile, node| {
+                let Some(handle) = handle_points.upgrade() else {
+                    return;
+                };
+                let global = handle.global::<FanPageData>();
+                let Some(nodes) = curve_nodes(&global, fan, profile) else {
+                    return;
+                };
+                let mut nodes: Vec<Node> = nodes.iter().collect();
+                let temp = node.x.round();
+                if nodes.iter().any(|n| n.x.round() == temp) {
+                    return;
+                }
+                let index = nodes
+                    .iter()
+                    .position(|n| n.x > node.x)
+                    .unwrap_or(nodes.len());
+                nodes.insert(index, Node {
+                    x: temp,
+                    y: node.y.clamp(0.0, u8::MAX as f32),
+                });
+                set_curve_nodes(&global, fan, profile, nodes.as_slice().into());
+            });
+            let handle_points = handle.as_weak();
+            global.on_remove_point(move |fan, profile, index| {
+                let Some(handle) = handle_points.upgrade() else {
+                    return;
+                };
+                let global = handle.global::<FanPageData>();
+                let Some(nodes) = curve_nodes(&global, fan, profile) else {
+                    return;
+                };
+                let mut nodes: Vec<Node> = nodes.iter().collect();
+                if nodes.len() > 2 && (index as usize) < nodes.len() {
+                    nodes.remove(index as usize);
+                    set_curve_nodes(&global, fan, profile, nodes.as_slice().into());
+                }
+            });
+            let fans3 = fans.clone();
+            let handle_points = handle.as_weak();
+            global.on_set_preset(move |fan, profile, preset| {
+                let Ok(preset) = preset.parse::<CurvePreset>() else {
+                    return;
+                };
+                let fans = fans3.clone();
+                let handle = handle_points.clone();
+                tokio::spawn(async move {
+                    let Ok(points) = fans
+                        .curve_preset(preset, fan.into())
+                        .await
+                        .map_err(|e| error!("{e:}"))
+                    else {
+                        return;
+                    };
+                    handle
+                        .upgrade_in_event_loop(move |handle| {
+                            let nodes: Vec<Node> = points
+                                .points
+                                .iter()
+                                .map(|(temp, pwm)| Node {
+                                    x: *temp as f32,
+                                    y: *pwm as f32,
+                                })
+                                .collect();
+                            let global = handle.global::<FanPageData>();
+                            set_curve_nodes(&global, fan, profile, nodes.as_slice().into());
+                        })
+                        .ok();
+                });
+            });
             global.on_set_fan_data(move |fan, profile, enabled, data| {
                 let fans = fans.clone();
                 let data: Vec<Node> = data.iter().collect();
-                let data = fan_data_for(fan, enabled, data);
+                let points = curve_points_for(fan, enabled, data);
                 tokio::spawn(async move {
-                    fans.set_fan_curve(profile.into(), data)
+                    fans.set_fan_curve_points(profile.into(), points)
                         .await
                         .map_err(|e| error!("{e:}"))
                         .ok()
@@ -220,23 +436,42 @@ pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
     });
 }
 
-fn fan_data_for(fan: FanType, enabled: bool, data: Vec<Node>) -> CurveData {
-    let mut temp = [0u8; 8];
-    let mut pwm = [0u8; 8];
-    for (i, n) in data.iter().enumerate() {
-        if i == 8 {
-            break;
-        }
-        temp[i] = n.x as u8;
-        pwm[i] = n.y as u8;
-    }
+/// The control points being edited for a fan and profile
+fn curve_nodes(global: &FanPageData, fan: FanType, profile: Profile) -> Option<ModelRc<Node>> {
+    Some(match (profile, fan) {
+        (Profile::Balanced, FanType::CPU) => global.get_balanced_cpu(),
+        (Profile::Balanced, FanType::Middle) => global.get_balanced_mid(),
+        (Profile::Balanced, FanType::GPU) => global.get_balanced_gpu(),
+        (Profile::Performance, FanType::CPU) => global.get_performance_cpu(),
+        (Profile::Performance, FanType::Middle) => global.get_performance_mid(),
+        (Profile::Performance, FanType::GPU) => global.get_performance_gpu(),
+        (Profile::Quiet | Profile::LowPower, FanType::CPU) => global.get_quiet_cpu(),
+        (Profile::Quiet | Profile::LowPower, FanType::Middle) => global.get_quiet_mid(),
+        (Profile::Quiet | Profile::LowPower, FanType::GPU) => global.get_quiet_gpu(),
+        (Profile::Custom, _) => return None,
+    })
+}
 
-    dbg!(&fan, enabled);
+fn set_curve_nodes(global: &FanPageData, fan: FanType, profile: Profile, nodes: ModelRc<Node>) {
+    match (profile, fan) {
+        (Profile::Balanced, FanType::CPU) => global.set_balanced_cpu(nodes),
+        (Profile::Balanced, FanType::Middle) => global.set_balanced_mid(nodes),
+        (Profile::Balanced, FanType::GPU) => global.set_balanced_gpu(nodes),
+        (Profile::Performance, FanType::CPU) => global.set_performance_cpu(nodes),
+        (Profile::Performance, FanType::Middle) => global.set_performance_mid(nodes),
+        (Profile::Performance, FanType::GPU) => global.set_performance_gpu(nodes),
+        (Profile::Quiet | Profile::LowPower, FanType::CPU) => global.set_quiet_cpu(nodes),
+        (Profile::Quiet | Profile::LowPower, FanType::Middle) => global.set_quiet_mid(nodes),
+        (Profile::Quiet | Profile::LowPower, FanType::GPU) => global.set_quiet_gpu(nodes),
+        (Profile::Custom, _) => {}
+    }
+}
 
-    CurveData {
+/// Any number of nodes, asusd resamples them to the 8 the firmware takes
+fn curve_points_for(fan: FanType, enabled: bool, data: Vec<Node>) -> CurvePoints {
+    CurvePoints {
         fan: fan.into(),
-        pwm,
-        temp,
+        points: data.iter().map(|n| (n.x as u8, n.y as u8)).collect(),
         enabled,
     }
 }
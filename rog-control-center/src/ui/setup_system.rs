@@ -4,15 +4,19 @@ use concat_idents::concat_idents;
 use log::{debug, error};
 use rog_dbus::asus_armoury::AsusArmouryProxy;
 use rog_dbus::zbus_backlight::BacklightProxy;
+use rog_dbus::zbus_battery::BatteryProxy;
 use rog_dbus::zbus_platform::{PlatformProxy, PlatformProxyBlocking};
-use rog_platform::asus_armoury::FirmwareAttribute;
+use rog_platform::asus_armoury::{ChargeMode, FirmwareAttribute};
 use rog_platform::platform::Properties;
+use rog_platform::power::BatterySample;
 use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
 
 use super::show_toast;
 use crate::config::Config;
 use crate::zbus_proxies::find_iface_async;
-use crate::{set_ui_callbacks, set_ui_props_async, AttrMinMax, MainWindow, SystemPageData};
+use crate::{
+    set_ui_callbacks, set_ui_props_async, AttrMinMax, BatteryData, MainWindow, SystemPageData,
+};
 
 const MINMAX: AttrMinMax = AttrMinMax {
     min: 0,
@@ -38,6 +42,7 @@ pub fn setup_system_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
     ui.global::<SystemPageData>()
         .set_charge_control_enabled(false);
     ui.global::<SystemPageData>().set_platform_profile(-1);
+    ui.global::<SystemPageData>().set_charge_mode(-1);
     ui.global::<SystemPageData>().set_panel_overdrive(-1);
     ui.global::<SystemPageData>().set_boot_sound(-1);
     ui.global::<SystemPageData>().set_mini_led_mode(-1);
@@ -68,6 +73,138 @@ pub fn setup_system_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
     }
 }
 
+/// An SVG path of a percentage over the history in a 100x100 viewbox, placed
+/// by the time of each sample
+fn battery_history_path(history: &[BatterySample], value: fn(&BatterySample) -> u8) -> String {
+    let (Some(first), Some(last)) = (history.first(), history.last()) else {
+        return String::new();
+    };
+    let span = last.time.saturating_sub(first.time).max(1) as f32;
+    let mut path = String::new();
+    for (i, sample) in history.iter().enumerate() {
+        let cmd = if i == 0 { 'M' } else { 'L' };
+        let x = sample.time.saturating_sub(first.time) as f32 / span * 100.0;
+        let y = 100.0 - value(sample).min(100) as f32;
+        path.push_str(&format!("{cmd}{x:.1} {y:.1} "));
+    }
+    path
+}
+
+fn update_battery_data(
+    handle: slint::Weak<MainWindow>,
+    sample: BatterySample,
+    history: &[BatterySample],
+) {
+    let oldest = history.first().unwrap_or(&sample);
+    let data = BatteryData {
+        available: true,
+        capacity: sample.capacity as i32,
+        health: sample.health as f32,
+        health_change: (sample.health - oldest.health) as f32,
+        cycle_count: sample.cycle_count as i32,
+        rate: sample.rate as f32,
+        voltage: sample.voltage as f32,
+        temp: sample.temp as f32,
+        capacity_history: battery_history_path(history, |s| s.capacity).into(),
+        limit_history: battery_history_path(history, |s| s.charge_limit).into(),
+        history_days: (sample.time.saturating_sub(oldest.time) / (24 * 60 * 60)) as i32,
+    };
+    handle
+        .upgrade_in_event_loop(move |handle| handle.global::<SystemPageData>().set_battery(data))
+        .map_err(|e| error!("update_battery_data: upgrade_in_event_loop: {e:?}"))
+        .ok();
+}
+
+/// Show only the charge modes the firmware supports. The dropdown positions map
+/// to `ChargeMode` values by `charge_mode_indexes`.
+async fn setup_charge_mode(handle: slint::Weak<MainWindow>, platform: PlatformProxy<'static>) {
+    let (Ok(modes), Ok(mode), Ok(mode_on_ac)) = (
+        platform.charge_mode_choices().await,
+        platform.charge_mode().await,
+        platform.charge_mode_on_ac().await,
+    ) else {
+        return;
+    };
+    if modes.is_empty() {
+        return;
+    }
+    let indexes: Vec<i32> = modes.iter().map(|m| *m as i32).collect();
+    let position = |indexes: &[i32], mode: ChargeMode| {
+        indexes
+            .iter()
+            .position(|&i| i == mode as i32)
+            .unwrap_or_default() as i32
+    };
+    let (current, current_on_ac) = (position(&indexes, mode), position(&indexes, mode_on_ac));
+
+    let platform_copy = platform.clone();
+    let indexes_copy = indexes.clone();
+    handle
+        .upgrade_in_event_loop(move |handle| {
+            let data = handle.global::<SystemPageData>();
+            let translate: Vec<SharedString> = data.get_charge_mode_choices().iter().collect();
+            let strings: Vec<SharedString> = indexes_copy
+                .iter()
+                .filter_map(|&i| translate.get(i as usize).cloned())
+                .collect();
+            data.set_charge_mode_choices(ModelRc::new(VecModel::from(strings)));
+            data.set_charge_mode_indexes(ModelRc::from(indexes_copy.as_slice()));
+            data.set_charge_mode(current);
+            data.set_charge_mode_on_ac(current_on_ac);
+
+            let handle_copy = handle.as_weak();
+            let proxy_copy = platform_copy.clone();
+            data.on_cb_charge_mode(move |value| {
+                let proxy_copy = proxy_copy.clone();
+                let handle_copy = handle_copy.clone();
+                tokio::spawn(async move {
+                    let Ok(mode) = ChargeMode::try_from(value) else {
+                        return;
+                    };
+                    show_toast(
+                        format!("Charge mode set to {mode}").into(),
+                        "Setting charge mode failed".into(),
+                        handle_copy,
+                        proxy_copy.set_charge_mode(mode).await,
+                    );
+                });
+            });
+
+            let handle_copy = handle.as_weak();
+            let proxy_copy = platform_copy.clone();
+            data.on_cb_charge_mode_on_ac(move |value| {
+                let proxy_copy = proxy_copy.clone();
+                let handle_copy = handle_copy.clone();
+                tokio::spawn(async move {
+                    let Ok(mode) = ChargeMode::try_from(value) else {
+                        return;
+                    };
+                    show_toast(
+                        format!("Charge mode on AC set to {mode}").into(),
+                        "Setting charge mode on AC failed".into(),
+                        handle_copy,
+                        proxy_copy.set_charge_mode_on_ac(mode).await,
+                    );
+                });
+            });
+        })
+        .ok();
+
+    // The mode is also changed by the AC/battery hooks
+    let mut x = platform.receive_charge_mode_changed().await;
+    use futures_util::StreamExt;
+    while let Some(e) = x.next().await {
+        if let Ok(out) = e.get().await {
+            let current = position(&indexes, out);
+            handle
+                .upgrade_in_event_loop(move |handle| {
+                    handle.global::<SystemPageData>().set_charge_mode(current);
+                })
+                .ok();
+        }
+    }
+}
+
 macro_rules! convert_value {
     (bool, $value:expr) => {
         $value == 1
@@ -306,6 +443,26 @@ pub fn setup_system_page_callbacks(ui: &MainWindow, _states: Arc<Mutex<Config>>)
             charge_control_end_threshold
         );
 
+        if let Ok(battery) = BatteryProxy::builder(&conn).build().await {
+            let handle_copy = handle.clone();
+            tokio::spawn(async move {
+                if let (Ok(sample), Ok(history)) = (battery.sample().await, battery.history().await)
+                {
+                    update_battery_data(handle_copy.clone(), sample, &history);
+                }
+                let mut x = battery.receive_sample_changed().await;
+                use futures_util::StreamExt;
+                while let Some(e) = x.next().await {
+                    if let (Ok(sample), Ok(history)) = (e.get().await, battery.history().await) {
+                        update_battery_data(handle_copy.clone(), sample, &history);
+                    }
+                }
+            });
+        }
+
+        tokio::spawn(setup_charge_mode(handle.clone(), platform.clone()));
+        set_ui_props_async!(handle, platform, SystemPageData, change_charge_mode_on_ac);
+
         let platform_copy = platform.clone();
         if let Ok(mut value) = platform.platform_profile_choices().await {
             handle
@@ -504,6 +661,12 @@ pub fn setup_system_page_callbacks(ui: &MainWindow, _states: Arc<Mutex<Config>>)
                     "Throttle policy linked to EPP: {}",
                     "Setting Throttle policy linked to EPP failed"
                 );
+                set_ui_callbacks!(handle,
+                    SystemPageData(as bool),
+                    platform_copy.change_charge_mode_on_ac(.into()),
+                    "Charge mode on AC enabled: {}",
+                    "Setting charge mode on AC failed"
+                );
                 set_ui_callbacks!(handle,
                     SystemPageData(as i32),
                     platform_copy.platform_profile_on_ac(.into()),
@@ -6,8 +6,10 @@ pub mod scsi_aura;
 pub mod zbus_anime;
 pub mod zbus_aura;
 pub mod zbus_backlight;
+pub mod zbus_battery;
 pub mod zbus_fan_curves;
 pub mod zbus_platform;
+pub mod zbus_profile_rules;
 pub mod zbus_slash;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
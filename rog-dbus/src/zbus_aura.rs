@@ -41,6 +41,25 @@ pub trait Aura {
     /// DirectAddressingRaw method
     fn direct_addressing_raw(&self, data: AuraLaptopUsbPackets) -> zbus::Result<()>;
 
+    /// EffectLoad method
+    fn effect_load(&self, program: &str) -> zbus::Result<()>;
+
+    /// EffectStart method
+    fn effect_start(&self) -> zbus::Result<()>;
+
+    /// EffectStop method
+    fn effect_stop(&self) -> zbus::Result<()>;
+
+    /// EffectFrameRate property
+    #[zbus(property)]
+    fn effect_frame_rate(&self) -> zbus::Result<u8>;
+    #[zbus(property)]
+    fn set_effect_frame_rate(&self, value: u8) -> zbus::Result<()>;
+
+    /// EffectRunning property
+    #[zbus(property)]
+    fn effect_running(&self) -> zbus::Result<bool>;
+
     /// Brightness property
     #[zbus(property)]
     fn brightness(&self) -> zbus::Result<LedBrightness>;
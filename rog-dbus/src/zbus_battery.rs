@@ -0,0 +1,21 @@
+//! # `DBus` interface proxy for: `xyz.ljones.Battery`
+//!
+//! Written by hand to match the interface in asusd, it was not generated by
+//! `zbus-xmlgen`.
+
+use rog_platform::power::BatterySample;
+use zbus::proxy;
+
+#[proxy(
+    interface = "xyz.ljones.Battery",
+    default_service = "xyz.ljones.Asusd",
+    default_path = "/xyz/ljones"
+)]
+pub trait Battery {
+    /// History method
+    fn history(&self) -> zbus::Result<Vec<BatterySample>>;
+
+    /// Sample property
+    #[zbus(property)]
+    fn sample(&self) -> zbus::Result<BatterySample>;
+}
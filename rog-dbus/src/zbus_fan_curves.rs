@@ -21,7 +21,11 @@
 //! …consequently `zbus-xmlgen` did not generate code for the above interfaces.
 
 use rog_platform::platform::PlatformProfile;
+use rog_profiles::calibration::FanCalibration;
+use rog_profiles::curve_points::{CurvePoints, CurvePreset};
 use rog_profiles::fan_curve_set::CurveData;
+use rog_profiles::software_curve::SoftwareCurve;
+use rog_profiles::telemetry::FanTelemetry;
 use rog_profiles::FanCurvePU;
 use zbus::proxy;
 
@@ -48,6 +52,41 @@ pub trait FanCurves {
     /// currently in if profile == None. Will also activate the fan curve.
     fn set_fan_curve(&self, profile: PlatformProfile, curve: CurveData) -> zbus::Result<()>;
 
+    /// Get the fan curves of a profile for AC or battery
+    fn fan_curve_data_for_power(
+        &self,
+        profile: PlatformProfile,
+        power_plugged: bool,
+    ) -> zbus::Result<Vec<CurveData>>;
+
+    /// Set the fan curve of a profile for AC or battery
+    fn set_fan_curve_for_power(
+        &self,
+        profile: PlatformProfile,
+        power_plugged: bool,
+        curve: CurveData,
+    ) -> zbus::Result<()>;
+
+    /// Set the fan curve for the profile from any number of control points,
+    /// resampled to the 8 the firmware takes
+    fn set_fan_curve_points(
+        &self,
+        profile: PlatformProfile,
+        points: CurvePoints,
+    ) -> zbus::Result<()>;
+
+    /// Set the fan curve of a profile for AC or battery from any number of
+    /// control points
+    fn set_fan_curve_points_for_power(
+        &self,
+        profile: PlatformProfile,
+        power_plugged: bool,
+        points: CurvePoints,
+    ) -> zbus::Result<()>;
+
+    /// The control points of a preset for a fan on this model
+    fn curve_preset(&self, preset: CurvePreset, fan: FanCurvePU) -> zbus::Result<CurvePoints>;
+
     /// Set a profile fan curve enabled status. Will also activate a fan curve.
     fn set_fan_curves_enabled(&self, profile: PlatformProfile, enabled: bool) -> zbus::Result<()>;
 
@@ -59,4 +98,58 @@ pub trait FanCurves {
         fan: FanCurvePU,
         enabled: bool,
     ) -> zbus::Result<()>;
+
+    /// Export the fan curves of the profiles and fans, or all if empty, as a
+    /// RON document
+    fn export_fan_curves(
+        &self,
+        profiles: &[PlatformProfile],
+        fans: &[FanCurvePU],
+    ) -> zbus::Result<String>;
+
+    /// Import a document from `export_fan_curves`. A document from another
+    /// model is refused unless `force` is set. Returns any warnings.
+    fn import_fan_curves(&self, document: &str, force: bool) -> zbus::Result<Vec<String>>;
+
+    /// Get the curves used by software fan control
+    fn software_fan_curves(&self) -> zbus::Result<Vec<SoftwareCurve>>;
+
+    /// Set the software fan control curve for the fan in the curve
+    fn set_software_fan_curve(&self, curve: SoftwareCurve) -> zbus::Result<()>;
+
+    /// Measure the RPM of the fans, or all if empty, across their pwm range.
+    /// Runs in the background while `Calibrating` is true.
+    fn calibrate_fans(&self, fans: &[FanCurvePU]) -> zbus::Result<()>;
+
+    /// Stop a running calibration
+    fn cancel_fan_calibration(&self) -> zbus::Result<()>;
+
+    /// The pwm to RPM table of each calibrated fan
+    fn fan_calibration(&self) -> zbus::Result<Vec<FanCalibration>>;
+
+    /// BatteryFanCurves property
+    #[zbus(property)]
+    fn battery_fan_curves(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_battery_fan_curves(&self, value: bool) -> zbus::Result<()>;
+
+    /// Calibrating property
+    #[zbus(property)]
+    fn calibrating(&self) -> zbus::Result<bool>;
+
+    /// SoftwareFanControl property
+    #[zbus(property)]
+    fn software_fan_control(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_software_fan_control(&self, value: bool) -> zbus::Result<()>;
+
+    /// FanTelemetry property
+    #[zbus(property)]
+    fn fan_telemetry(&self) -> zbus::Result<Vec<FanTelemetry>>;
+
+    /// TelemetryInterval property
+    #[zbus(property)]
+    fn telemetry_interval(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_telemetry_interval(&self, value: u32) -> zbus::Result<()>;
 }
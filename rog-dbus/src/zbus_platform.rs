@@ -20,6 +20,7 @@
 //!
 //! …consequently `zbus-xmlgen` did not generate code for the above interfaces.
 
+use rog_platform::asus_armoury::{ChargeMode, PowerLimits};
 use rog_platform::cpu::CPUEPP;
 use rog_platform::platform::{PlatformProfile, Properties};
 use zbus::proxy;
@@ -39,6 +40,27 @@ pub trait Platform {
     /// SupportedProperties method
     fn supported_properties(&self) -> zbus::Result<Vec<Properties>>;
 
+    /// PowerLimits method
+    fn power_limits(&self) -> zbus::Result<PowerLimits>;
+
+    /// ApplyPowerLimits method
+    fn apply_power_limits(&self, limits: PowerLimits) -> zbus::Result<()>;
+
+    /// PowerLimitPresets method
+    fn power_limit_presets(&self) -> zbus::Result<Vec<String>>;
+
+    /// PowerLimitPreset method
+    fn power_limit_preset(&self, name: &str) -> zbus::Result<PowerLimits>;
+
+    /// SavePowerLimitPreset method
+    fn save_power_limit_preset(&self, name: &str, limits: PowerLimits) -> zbus::Result<()>;
+
+    /// ApplyPowerLimitPreset method
+    fn apply_power_limit_preset(&self, name: &str) -> zbus::Result<()>;
+
+    /// RemovePowerLimitPreset method
+    fn remove_power_limit_preset(&self, name: &str) -> zbus::Result<()>;
+
     /// ChargeControlEndThreshold property
     #[zbus(property)]
     fn charge_control_end_threshold(&self) -> zbus::Result<u8>;
@@ -48,6 +70,26 @@ pub trait Platform {
     // Toggle one-shot charge to 100%
     fn one_shot_full_charge(&self) -> zbus::Result<()>;
 
+    /// ChargeScheduleEnabled property
+    #[zbus(property)]
+    fn charge_schedule_enabled(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_charge_schedule_enabled(&self, value: bool) -> zbus::Result<()>;
+
+    /// AdaptiveCharging property
+    #[zbus(property)]
+    fn adaptive_charging(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_adaptive_charging(&self, value: bool) -> zbus::Result<()>;
+
+    /// LearnedChargeTargets property
+    #[zbus(property)]
+    fn learned_charge_targets(&self) -> zbus::Result<Vec<String>>;
+
+    /// NextChargeAction property
+    #[zbus(property)]
+    fn next_charge_action(&self) -> zbus::Result<String>;
+
     /// ThrottleBalancedEpp property
     #[zbus(property)]
     fn profile_balanced_epp(&self) -> zbus::Result<CPUEPP>;
@@ -66,6 +108,28 @@ pub trait Platform {
     #[zbus(property)]
     fn set_platform_profile_linked_epp(&self, value: bool) -> zbus::Result<()>;
 
+    /// ChargeMode property
+    #[zbus(property)]
+    fn charge_mode(&self) -> zbus::Result<ChargeMode>;
+    #[zbus(property)]
+    fn set_charge_mode(&self, mode: ChargeMode) -> zbus::Result<()>;
+
+    /// ChargeModeChoices property
+    #[zbus(property)]
+    fn charge_mode_choices(&self) -> zbus::Result<Vec<ChargeMode>>;
+
+    /// ChargeModeOnAc property
+    #[zbus(property)]
+    fn charge_mode_on_ac(&self) -> zbus::Result<ChargeMode>;
+    #[zbus(property)]
+    fn set_charge_mode_on_ac(&self, mode: ChargeMode) -> zbus::Result<()>;
+
+    /// ChangeChargeModeOnAc property
+    #[zbus(property)]
+    fn change_charge_mode_on_ac(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_change_charge_mode_on_ac(&self, change: bool) -> zbus::Result<()>;
+
     /// ThrottlePolicyOnAc property
     #[zbus(property)]
     fn platform_profile_on_ac(&self) -> zbus::Result<PlatformProfile>;
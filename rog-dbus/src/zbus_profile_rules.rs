@@ -0,0 +1,27 @@
+//! # `DBus` interface proxy for: `xyz.ljones.ProfileRules`
+//!
+//! Written by hand to match the interface in asusd, it was not generated by
+//! `zbus-xmlgen`.
+
+use rog_platform::profile_rules::ProfileRule;
+use zbus::proxy;
+
+#[proxy(
+    interface = "xyz.ljones.ProfileRules",
+    default_service = "xyz.ljones.Asusd",
+    default_path = "/xyz/ljones"
+)]
+pub trait ProfileRules {
+    /// Rules method
+    fn rules(&self) -> zbus::Result<Vec<ProfileRule>>;
+
+    /// AddRule method
+    fn add_rule(&self, rule: &ProfileRule) -> zbus::Result<()>;
+
+    /// RemoveRule method
+    fn remove_rule(&self, name: &str) -> zbus::Result<()>;
+
+    /// ActiveRule property
+    #[zbus(property)]
+    fn active_rule(&self) -> zbus::Result<String>;
+}
@@ -2,15 +2,16 @@ use std::fs::{read_dir, File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
-use log::{debug, error};
+use log::{debug, error, warn};
 use serde::{Deserialize, Serialize};
 use zbus::zvariant::{OwnedValue, Type, Value};
 
 use crate::error::PlatformError;
+use crate::sysfs::SysfsRoot;
 
-/// The root sysfs path. This path should never change in kernel so
-/// using udev to find it *should* not be required.
-const BASE_DIR: &str = "/sys/class/firmware-attributes/asus-armoury/attributes/";
+/// The sysfs path relative to the sysfs root. This path should never change in
+/// kernel so using udev to find it *should* not be required.
+const BASE_DIR: &str = "class/firmware-attributes/asus-armoury/attributes/";
 
 fn read_i32(path: &Path) -> Result<i32, PlatformError> {
     if let Ok(mut f) = File::open(path) {
@@ -201,7 +202,7 @@ pub struct FirmwareAttributes {
 impl FirmwareAttributes {
     pub fn new() -> Self {
         let mut attrs = Vec::new();
-        if let Ok(dir) = read_dir(BASE_DIR) {
+        if let Ok(dir) = read_dir(SysfsRoot::get().join(BASE_DIR)) {
             for entry in dir.flatten() {
                 let base_path = entry.path();
                 let name = base_path.file_name().unwrap().to_string_lossy().to_string();
@@ -400,6 +401,323 @@ impl From<FirmwareAttribute> for &str {
     }
 }
 
+/// The values of the `charge_mode` attribute, how the battery is used while
+/// AC is plugged in. The firmware lists the modes it supports in
+/// `possible_values`.
+#[repr(u32)]
+#[derive(
+    Deserialize,
+    Serialize,
+    Default,
+    Type,
+    Value,
+    OwnedValue,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+)]
+#[zvariant(signature = "u")]
+pub enum ChargeMode {
+    /// Run from AC and charge the battery up to the charge limit
+    #[default]
+    Normal = 0,
+    /// Run from AC without charging or discharging the battery
+    Bypass = 1,
+    /// Run from AC only, the battery is not used at all
+    AcOnly = 2,
+}
+
+impl ChargeMode {
+    /// The modes in the attribute's `possible_values`, or all of them if it
+    /// has none
+    pub fn choices(attr: &Attribute) -> Vec<Self> {
+        match attr.possible_values() {
+            AttrValue::EnumInt(values) => values
+                .iter()
+                .filter_map(|v| Self::try_from(*v).ok())
+                .collect(),
+            _ => vec![
+                Self::Normal,
+                Self::Bypass,
+                Self::AcOnly,
+            ],
+        }
+    }
+
+    pub fn read(attr: &Attribute) -> Result<Self, PlatformError> {
+        match attr.current_value()? {
+            AttrValue::Integer(v) => Self::try_from(v),
+            _ => Err(PlatformError::InvalidValue),
+        }
+    }
+
+    /// Write the mode if the firmware supports it
+    pub fn write(self, attr: &Attribute) -> Result<(), PlatformError> {
+        if !Self::choices(attr).contains(&self) {
+            warn!("charge_mode: {self} is not supported");
+            return Err(PlatformError::NotSupported);
+        }
+        attr.set_current_value(&AttrValue::Integer(self as i32))
+    }
+}
+
+impl TryFrom<i32> for ChargeMode {
+    type Error = PlatformError;
+
+    fn try_from(num: i32) -> Result<Self, Self::Error> {
+        match num {
+            0 => Ok(Self::Normal),
+            1 => Ok(Self::Bypass),
+            2 => Ok(Self::AcOnly),
+            _ => Err(PlatformError::InvalidValue),
+        }
+    }
+}
+
+impl From<ChargeMode> for &str {
+    fn from(mode: ChargeMode) -> Self {
+        match mode {
+            ChargeMode::Normal => "normal",
+            ChargeMode::Bypass => "bypass",
+            ChargeMode::AcOnly => "ac-only",
+        }
+    }
+}
+
+impl std::fmt::Display for ChargeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", <&str>::from(*self))
+    }
+}
+
+impl std::str::FromStr for ChargeMode {
+    type Err = PlatformError;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_ascii_lowercase().trim() {
+            "normal" => Ok(Self::Normal),
+            "bypass" => Ok(Self::Bypass),
+            "ac-only" | "aconly" => Ok(Self::AcOnly),
+            _ => Err(PlatformError::InvalidValue),
+        }
+    }
+}
+
+/// A set of PPT/TDP power limits that are checked and applied together. A limit
+/// of `-1` is not set, as for the armoury attributes over D-Bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Value, OwnedValue)]
+#[serde(default)]
+pub struct PowerLimits {
+    pub ppt_pl1_spl: i32,
+    pub ppt_pl2_sppt: i32,
+    pub ppt_pl3_fppt: i32,
+    pub ppt_fppt: i32,
+    pub ppt_apu_sppt: i32,
+    pub ppt_platform_sppt: i32,
+    pub nv_dynamic_boost: i32,
+    pub nv_temp_target: i32,
+    pub dgpu_tgp: i32,
+}
+
+impl Default for PowerLimits {
+    fn default() -> Self {
+        Self {
+            ppt_pl1_spl: -1,
+            ppt_pl2_sppt: -1,
+            ppt_pl3_fppt: -1,
+            ppt_fppt: -1,
+            ppt_apu_sppt: -1,
+            ppt_platform_sppt: -1,
+            nv_dynamic_boost: -1,
+            nv_temp_target: -1,
+            dgpu_tgp: -1,
+        }
+    }
+}
+
+impl PowerLimits {
+    /// Each pair must be in order, PL1 <= PL2 <= PL3 (or FPPT)
+    const ORDER: [(FirmwareAttribute, FirmwareAttribute); 3] = [
+        (FirmwareAttribute::PptPl1Spl, FirmwareAttribute::PptPl2Sppt),
+        (FirmwareAttribute::PptPl2Sppt, FirmwareAttribute::PptPl3Fppt),
+        (FirmwareAttribute::PptPl2Sppt, FirmwareAttribute::PptFppt),
+    ];
+
+    /// All the limits and their attributes, the sustained to fast limits first
+    fn values(&self) -> [(FirmwareAttribute, i32); 9] {
+        [
+            (FirmwareAttribute::PptPl1Spl, self.ppt_pl1_spl),
+            (FirmwareAttribute::PptPl2Sppt, self.ppt_pl2_sppt),
+            (FirmwareAttribute::PptPl3Fppt, self.ppt_pl3_fppt),
+            (FirmwareAttribute::PptFppt, self.ppt_fppt),
+            (FirmwareAttribute::PptApuSppt, self.ppt_apu_sppt),
+            (FirmwareAttribute::PptPlatformSppt, self.ppt_platform_sppt),
+            (FirmwareAttribute::NvDynamicBoost, self.nv_dynamic_boost),
+            (FirmwareAttribute::NvTempTarget, self.nv_temp_target),
+            (FirmwareAttribute::DgpuTgp, self.dgpu_tgp),
+        ]
+    }
+
+    /// If the attribute is one of the limits
+    pub fn contains(attr: FirmwareAttribute) -> bool {
+        Self::default().values().iter().any(|(a, _)| *a == attr)
+    }
+
+    /// The limit for the attribute, `None` if it isn't set
+    pub fn get(&self, attr: FirmwareAttribute) -> Option<i32> {
+        self.iter().find(|(a, _)| *a == attr).map(|(_, v)| v)
+    }
+
+    /// Set a limit, `-1` unsets it
+    pub fn set(&mut self, attr: FirmwareAttribute, value: i32) -> Result<(), PlatformError> {
+        let field = match attr {
+            FirmwareAttribute::PptPl1Spl => &mut self.ppt_pl1_spl,
+            FirmwareAttribute::PptPl2Sppt => &mut self.ppt_pl2_sppt,
+            FirmwareAttribute::PptPl3Fppt => &mut self.ppt_pl3_fppt,
+            FirmwareAttribute::PptFppt => &mut self.ppt_fppt,
+            FirmwareAttribute::PptApuSppt => &mut self.ppt_apu_sppt,
+            FirmwareAttribute::PptPlatformSppt => &mut self.ppt_platform_sppt,
+            FirmwareAttribute::NvDynamicBoost => &mut self.nv_dynamic_boost,
+            FirmwareAttribute::NvTempTarget => &mut self.nv_temp_target,
+            FirmwareAttribute::DgpuTgp => &mut self.dgpu_tgp,
+            _ => {
+                return Err(PlatformError::PowerLimit(format!(
+                    "{} is not a power limit",
+                    <&str>::from(attr)
+                )))
+            }
+        };
+        *field = value;
+        Ok(())
+    }
+
+    /// The limits that are set
+    pub fn iter(&self) -> impl Iterator<Item = (FirmwareAttribute, i32)> {
+        self.values().into_iter().filter(|(_, v)| *v != -1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    fn attribute(
+        attrs: &FirmwareAttributes,
+        attr: FirmwareAttribute,
+    ) -> Result<&Attribute, PlatformError> {
+        let name = <&str>::from(attr);
+        attrs
+            .attributes()
+            .iter()
+            .find(|a| a.name() == name)
+            .ok_or_else(|| PlatformError::AttrNotFound(name.to_owned()))
+    }
+
+    /// Check each limit against the `min_value`, `max_value` and
+    /// `scalar_increment` of its attribute, and that PL1 <= PL2 <= PL3 using
+    /// the current values of the limits that aren't set
+    pub fn check(&self, attrs: &FirmwareAttributes) -> Result<(), PlatformError> {
+        for (attr, value) in self.iter() {
+            let name = <&str>::from(attr);
+            let attribute = Self::attribute(attrs, attr)?;
+            let min = match attribute.min_value() {
+                AttrValue::Integer(min) => *min,
+                _ => 0,
+            };
+            if value < min {
+                return Err(PlatformError::PowerLimit(format!(
+                    "{name} {value} is below the minimum of {min}"
+                )));
+            }
+            if let AttrValue::Integer(max) = attribute.max_value() {
+                if value > *max {
+                    return Err(PlatformError::PowerLimit(format!(
+                        "{name} {value} is above the maximum of {max}"
+                    )));
+                }
+            }
+            if let AttrValue::Integer(step) = attribute.scalar_increment() {
+                if *step > 1 && (value - min) % step != 0 {
+                    return Err(PlatformError::PowerLimit(format!(
+                        "{name} {value} is not in steps of {step} from {min}"
+                    )));
+                }
+            }
+        }
+
+        let value = |attr: FirmwareAttribute| {
+            self.get(attr).or_else(|| {
+                match Self::attribute(attrs, attr).and_then(|a| a.current_value()) {
+                    Ok(AttrValue::Integer(v)) => Some(v),
+                    _ => None,
+                }
+            })
+        };
+        for (low, high) in Self::ORDER {
+            if let (Some(low_value), Some(high_value)) = (value(low), value(high)) {
+                if low_value > high_value {
+                    return Err(PlatformError::PowerLimit(format!(
+                        "{} {low_value} is above {} {high_value}",
+                        <&str>::from(low),
+                        <&str>::from(high)
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check and write all the limits that are set. The limits being raised
+    /// are written from the fastest down and then those being lowered from
+    /// the sustained up, so PL1 <= PL2 <= PL3 holds after each write. If a
+    /// write fails the limits already written are put back, so nothing is
+    /// written if a current value can't be read.
+    pub fn write(&self, attrs: &FirmwareAttributes) -> Result<(), PlatformError> {
+        self.check(attrs)?;
+        let mut raise = Vec::new();
+        let mut lower = Vec::new();
+        for (attr, value) in self.iter() {
+            let attribute = Self::attribute(attrs, attr)?;
+            match attribute.current_value() {
+                Ok(AttrValue::Integer(current)) if current == value => {}
+                Ok(AttrValue::Integer(current)) if current < value => {
+                    raise.push((attribute, value, current))
+                }
+                Ok(AttrValue::Integer(current)) => lower.push((attribute, value, current)),
+                _ => {
+                    return Err(PlatformError::PowerLimit(format!(
+                        "the current {} can't be read",
+                        attribute.name()
+                    )))
+                }
+            }
+        }
+
+        let mut written: Vec<(&Attribute, i32)> = Vec::new();
+        for (attribute, value, previous) in raise.into_iter().rev().chain(lower) {
+            if let Err(e) = attribute.set_current_value(&AttrValue::Integer(value)) {
+                error!(
+                    "Failed to set {}: {e}, restoring power limits",
+                    attribute.name()
+                );
+                for (attribute, previous) in written.into_iter().rev() {
+                    attribute
+                        .set_current_value(&AttrValue::Integer(previous))
+                        .map_err(|e| error!("Failed to restore {}: {e}", attribute.name()))
+                        .ok();
+                }
+                return Err(e);
+            }
+            written.push((attribute, previous));
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -480,4 +798,98 @@ mod tests {
         }
         attr.set_current_value(&val).unwrap();
     }
+
+    #[test]
+    fn charge_mode_names() {
+        for mode in [
+            ChargeMode::Normal,
+            ChargeMode::Bypass,
+            ChargeMode::AcOnly,
+        ] {
+            assert_eq!(mode.to_string().parse::<ChargeMode>().unwrap(), mode);
+            assert_eq!(ChargeMode::try_from(mode as i32).unwrap(), mode);
+        }
+        assert!("fast".parse::<ChargeMode>().is_err());
+        assert!(ChargeMode::try_from(3).is_err());
+
+        let attr = Attribute {
+            possible_values: AttrValue::EnumInt(vec![0, 1, 4]),
+            ..Default::default()
+        };
+        assert_eq!(ChargeMode::choices(&attr), vec![
+            ChargeMode::Normal,
+            ChargeMode::Bypass
+        ]);
+        assert!(ChargeMode::AcOnly.write(&attr).is_err());
+    }
+
+    #[test]
+    fn power_limits_check_and_write() {
+        let dir = std::env::temp_dir().join(format!("rog-power-limits-{}", std::process::id()));
+        let attr = |name: &str, current: i32, min: i32, max: i32, step: i32| {
+            let base_path = dir.join(name);
+            std::fs::create_dir_all(&base_path).unwrap();
+            std::fs::write(base_path.join("current_value"), current.to_string()).unwrap();
+            Attribute {
+                name: name.to_owned(),
+                min_value: AttrValue::Integer(min),
+                max_value: AttrValue::Integer(max),
+                scalar_increment: AttrValue::Integer(step),
+                base_path,
+                ..Default::default()
+            }
+        };
+        let mut attrs = FirmwareAttributes {
+            attrs: vec![
+                attr("ppt_pl1_spl", 45, 15, 80, 1),
+                attr("ppt_pl2_sppt", 65, 15, 80, 1),
+                attr("ppt_pl3_fppt", 80, 15, 100, 1),
+                attr("nv_dynamic_boost", 5, 5, 25, 5),
+            ],
+        };
+        let current = |attrs: &FirmwareAttributes| {
+            attrs
+                .attributes()
+                .iter()
+                .filter_map(|a| a.current_value().ok())
+                .collect::<Vec<_>>()
+        };
+
+        let mut limits = PowerLimits::default();
+        assert!(limits.is_empty());
+        // Above PL2 as it is now
+        limits.set(FirmwareAttribute::PptPl1Spl, 70).unwrap();
+        assert!(limits.check(&attrs).is_err());
+        limits.set(FirmwareAttribute::PptPl2Sppt, 75).unwrap();
+        limits.check(&attrs).unwrap();
+        limits.set(FirmwareAttribute::PptPl3Fppt, 110).unwrap();
+        assert!(limits.check(&attrs).is_err());
+        limits.set(FirmwareAttribute::PptPl3Fppt, 90).unwrap();
+        limits.set(FirmwareAttribute::NvDynamicBoost, 12).unwrap();
+        assert!(limits.check(&attrs).is_err());
+        limits.set(FirmwareAttribute::NvDynamicBoost, 15).unwrap();
+        assert!(limits.set(FirmwareAttribute::BootSound, 1).is_err());
+
+        limits.write(&attrs).unwrap();
+        assert_eq!(current(&attrs), vec![
+            AttrValue::Integer(70),
+            AttrValue::Integer(75),
+            AttrValue::Integer(90),
+            AttrValue::Integer(15),
+        ]);
+
+        // Nothing is written if a current value can't be read
+        attrs.attrs[0].base_path = dir.join("missing");
+        let mut lower = PowerLimits::default();
+        lower.set(FirmwareAttribute::PptPl1Spl, 30).unwrap();
+        lower.set(FirmwareAttribute::PptPl2Sppt, 40).unwrap();
+        lower.set(FirmwareAttribute::PptPl3Fppt, 95).unwrap();
+        assert!(lower.write(&attrs).is_err());
+        assert_eq!(current(&attrs)[..2], [
+            AttrValue::Integer(75),
+            AttrValue::Integer(90)
+        ]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -1,8 +1,9 @@
 use std::path::PathBuf;
 
-use log::{info, warn};
+use log::info;
 
 use crate::error::{PlatformError, Result};
+use crate::sysfs::SysfsRoot;
 use crate::{attr_num, to_device};
 
 /// The "backlight" device provides access to screen brightness control
@@ -26,19 +27,7 @@ impl Backlight {
     attr_num!("bl_power", path, i32);
 
     pub fn new(device_type: BacklightType) -> Result<Self> {
-        let mut enumerator = udev::Enumerator::new().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("enumerator failed".into(), err)
-        })?;
-        enumerator.match_subsystem("backlight").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-
-        for device in enumerator.scan_devices().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("scan_devices failed".into(), err)
-        })? {
+        for device in SysfsRoot::get().scan_subsystem("backlight")? {
             info!("Backlight: Checking {:?}", device.syspath());
             match device_type {
                 BacklightType::Primary => {
@@ -1,11 +1,12 @@
 use std::path::PathBuf;
 
-use log::{info, warn};
+use log::info;
 use serde::{Deserialize, Serialize};
 use zbus::zvariant::{OwnedValue, Type, Value};
 
 use crate::error::{PlatformError, Result};
 use crate::platform::PlatformProfile;
+use crate::sysfs::SysfsRoot;
 use crate::{read_attr_string, to_device};
 
 const ATTR_AVAILABLE_GOVERNORS: &str = "cpufreq/scaling_available_governors";
@@ -23,21 +24,9 @@ pub struct CPUControl {
 
 impl CPUControl {
     pub fn new() -> Result<Self> {
-        let mut enumerator = udev::Enumerator::new().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("enumerator failed".into(), err)
-        })?;
-        enumerator.match_subsystem("cpu").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-
         let mut supported = false;
         let mut cpu = CPUControl { paths: Vec::new() };
-        for device in enumerator.scan_devices().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("CPU: scan_devices failed".into(), err)
-        })? {
+        for device in SysfsRoot::get().scan_subsystem("cpu")? {
             if !supported {
                 info!(
                     "Found CPU support at {:?}, checking supported items",
@@ -23,6 +23,8 @@ pub enum PlatformError {
     NoAuraKeyboard,
     NoAuraNode,
     CPU(String),
+    ParseRule(String),
+    PowerLimit(String),
 }
 
 impl fmt::Display for PlatformError {
@@ -53,6 +55,8 @@ impl fmt::Display for PlatformError {
             PlatformError::NoAuraKeyboard => write!(f, "No supported Aura keyboard"),
             PlatformError::NoAuraNode => write!(f, "No Aura keyboard node found"),
             PlatformError::CPU(s) => write!(f, "CPU control: {s}"),
+            PlatformError::ParseRule(s) => write!(f, "Profile rule: {s}"),
+            PlatformError::PowerLimit(s) => write!(f, "Power limit: {s}"),
         }
     }
 }
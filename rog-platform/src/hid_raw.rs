@@ -2,6 +2,7 @@ use std::cell::RefCell;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use log::{info, warn};
 use udev::Device;
@@ -19,7 +20,46 @@ pub struct HidRaw {
     prod_id: String,
     _device_bcd: u32,
     /// Retaining a handle to the file for the duration of `HidRaw`
-    file: RefCell<File>,
+    file: RefCell<Endpoint>,
+}
+
+#[derive(Debug)]
+enum Endpoint {
+    Device(File),
+    Virtual(VirtualHidRaw),
+}
+
+/// A stand-in for a hidraw device node which records every report written
+/// to it, for use in tests. Clones share the same record so one can be given
+/// to `HidRaw::new_virtual()` and the other kept to inspect the reports.
+#[derive(Debug, Default, Clone)]
+pub struct VirtualHidRaw {
+    reports: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl VirtualHidRaw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All reports written so far, in order
+    pub fn reports(&self) -> Vec<Vec<u8>> {
+        self.reports.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+
+    /// Remove and return all reports written so far
+    pub fn take_reports(&self) -> Vec<Vec<u8>> {
+        self.reports
+            .lock()
+            .map(|mut r| std::mem::take(&mut *r))
+            .unwrap_or_default()
+    }
+
+    fn write(&self, message: &[u8]) {
+        if let Ok(mut reports) = self.reports.lock() {
+            reports.push(message.to_vec());
+        }
+    }
 }
 
 impl HidRaw {
@@ -57,7 +97,9 @@ impl HidRaw {
                             );
                         }
                         return Ok(Self {
-                            file: RefCell::new(OpenOptions::new().write(true).open(dev_node)?),
+                            file: RefCell::new(Endpoint::Device(
+                                OpenOptions::new().write(true).open(dev_node)?,
+                            )),
                             devfs_path: dev_node.to_owned(),
                             prod_id: this_id_product.to_string_lossy().into(),
                             syspath: endpoint.syspath().into(),
@@ -89,7 +131,9 @@ impl HidRaw {
             if let Some(dev_node) = endpoint.devnode() {
                 if let Some(id_product) = parent.attribute_value("idProduct") {
                     return Ok(Self {
-                        file: RefCell::new(OpenOptions::new().write(true).open(dev_node)?),
+                        file: RefCell::new(Endpoint::Device(
+                            OpenOptions::new().write(true).open(dev_node)?,
+                        )),
                         devfs_path: dev_node.to_owned(),
                         prod_id: id_product.to_string_lossy().into(),
                         syspath: endpoint.syspath().into(),
@@ -108,6 +152,17 @@ impl HidRaw {
         ))
     }
 
+    /// Make a `HidRaw` which writes to `endpoint` instead of a device node
+    pub fn new_virtual(id_product: &str, endpoint: &VirtualHidRaw) -> Self {
+        Self {
+            devfs_path: PathBuf::from(format!("virtual-hidraw-{id_product}")),
+            syspath: PathBuf::new(),
+            prod_id: id_product.to_owned(),
+            _device_bcd: 0,
+            file: RefCell::new(Endpoint::Virtual(endpoint.clone())),
+        }
+    }
+
     pub fn prod_id(&self) -> &str {
         &self.prod_id
     }
@@ -115,10 +170,13 @@ impl HidRaw {
     /// Write an array of raw bytes to the device using the hidraw interface
     pub fn write_bytes(&self, message: &[u8]) -> Result<()> {
         if let Ok(mut file) = self.file.try_borrow_mut() {
-            // TODO: re-get the file if error?
-            file.write_all(message).map_err(|e| {
-                PlatformError::IoPath(self.devfs_path.to_string_lossy().to_string(), e)
-            })?;
+            match &mut *file {
+                // TODO: re-get the file if error?
+                Endpoint::Device(file) => file.write_all(message).map_err(|e| {
+                    PlatformError::IoPath(self.devfs_path.to_string_lossy().to_string(), e)
+                })?,
+                Endpoint::Virtual(endpoint) => endpoint.write(message),
+            }
         }
         Ok(())
     }
@@ -126,6 +184,9 @@ impl HidRaw {
     /// This method was added for certain devices like AniMe to prevent them
     /// waking the laptop
     pub fn set_wakeup_disabled(&self) -> Result<()> {
+        if matches!(*self.file.borrow(), Endpoint::Virtual(_)) {
+            return Ok(());
+        }
         let mut dev = Device::from_syspath(&self.syspath)?;
         Ok(dev.set_attribute_value("power/wakeup", "disabled")?)
     }
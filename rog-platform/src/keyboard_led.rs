@@ -1,8 +1,9 @@
 use std::path::PathBuf;
 
-use log::{info, warn};
+use log::info;
 
 use crate::error::{PlatformError, Result};
+use crate::sysfs::SysfsRoot;
 use crate::{attr_num, has_attr, set_attr_u8_array, to_device};
 
 /// The sysfs control for backlight levels. This is only for the 3-step
@@ -33,20 +34,7 @@ impl KeyboardBacklight {
     );
 
     pub fn new() -> Result<Self> {
-        let mut enumerator = udev::Enumerator::new().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("enumerator failed".into(), err)
-        })?;
-
-        enumerator.match_subsystem("leds").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-
-        for device in enumerator.scan_devices().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("scan_devices failed".into(), err)
-        })? {
+        for device in SysfsRoot::get().scan_subsystem("leds")? {
             let sys = device.sysname().to_string_lossy();
             if sys.contains("kbd_backlight") || sys.contains("ally:rgb:gamepad") {
                 info!("Found keyboard LED controls at {:?}", device.sysname());
@@ -10,6 +10,9 @@ pub mod keyboard_led;
 pub(crate) mod macros;
 pub mod platform;
 pub mod power;
+pub mod profile_rules;
+pub mod snapshot;
+pub mod sysfs;
 pub mod usb_raw;
 
 use std::path::Path;
@@ -17,25 +20,19 @@ use std::path::Path;
 use error::{PlatformError, Result};
 use log::warn;
 use platform::PlatformProfile;
-use udev::Device;
+use sysfs::{SysfsDevice, SysfsRoot};
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub(crate) fn to_device(sys_path: &Path) -> Result<Device> {
-    Device::from_syspath(sys_path)
-        .map_err(|e| PlatformError::Udev("Couldn't transform syspath to device".to_owned(), e))
+pub(crate) fn to_device(sys_path: &Path) -> Result<SysfsDevice> {
+    SysfsRoot::get().device(sys_path)
 }
 
-pub fn has_attr(device: &Device, attr_name: &str) -> bool {
-    for attr in device.attributes() {
-        if attr.name() == attr_name {
-            return true;
-        }
-    }
-    false
+pub fn has_attr(device: &SysfsDevice, attr_name: &str) -> bool {
+    device.has_attribute(attr_name)
 }
 
-pub fn read_attr_bool(device: &Device, attr_name: &str) -> Result<bool> {
+pub fn read_attr_bool(device: &SysfsDevice, attr_name: &str) -> Result<bool> {
     if let Some(value) = device.attribute_value(attr_name) {
         let tmp = value.to_string_lossy();
         if tmp.trim() == "0" {
@@ -46,7 +43,7 @@ pub fn read_attr_bool(device: &Device, attr_name: &str) -> Result<bool> {
     Err(PlatformError::AttrNotFound(attr_name.to_owned()))
 }
 
-pub fn write_attr_bool(device: &mut Device, attr: &str, value: bool) -> Result<()> {
+pub fn write_attr_bool(device: &mut SysfsDevice, attr: &str, value: bool) -> Result<()> {
     let value = if value { 1 } else { 0 };
     device
         .set_attribute_value(attr, value.to_string())
@@ -56,7 +53,7 @@ pub fn write_attr_bool(device: &mut Device, attr: &str, value: bool) -> Result<(
         })
 }
 
-pub fn read_attr_num<T>(device: &Device, attr_name: &str) -> Result<T>
+pub fn read_attr_num<T>(device: &SysfsDevice, attr_name: &str) -> Result<T>
 where
     T: std::str::FromStr,
     <T as std::str::FromStr>::Err: std::fmt::Debug,
@@ -68,7 +65,7 @@ where
     Err(PlatformError::AttrNotFound(attr_name.to_owned()))
 }
 
-pub fn write_attr_num<T>(device: &mut Device, attr_name: &str, value: T) -> Result<()>
+pub fn write_attr_num<T>(device: &mut SysfsDevice, attr_name: &str, value: T) -> Result<()>
 where
     T: std::fmt::Display,
 {
@@ -81,7 +78,7 @@ where
     Ok(())
 }
 
-pub fn read_attr_u8_array(device: &Device, attr_name: &str) -> Result<Vec<u8>> {
+pub fn read_attr_u8_array(device: &SysfsDevice, attr_name: &str) -> Result<Vec<u8>> {
     if let Some(value) = device.attribute_value(attr_name) {
         let tmp = value.to_string_lossy();
         let tmp = tmp
@@ -93,7 +90,7 @@ pub fn read_attr_u8_array(device: &Device, attr_name: &str) -> Result<Vec<u8>> {
     Err(PlatformError::AttrNotFound(attr_name.to_owned()))
 }
 
-pub fn write_attr_u8_array(device: &mut Device, attr: &str, values: &[u8]) -> Result<()> {
+pub fn write_attr_u8_array(device: &mut SysfsDevice, attr: &str, values: &[u8]) -> Result<()> {
     let mut tmp = String::new();
     for n in values {
         tmp.push_str(&n.to_string());
@@ -105,7 +102,7 @@ pub fn write_attr_u8_array(device: &mut Device, attr: &str, values: &[u8]) -> Re
         .map_err(|e| PlatformError::IoPath(attr.into(), e))
 }
 
-pub fn read_attr_string(device: &Device, attr_name: &str) -> Result<String> {
+pub fn read_attr_string(device: &SysfsDevice, attr_name: &str) -> Result<String> {
     if let Some(value) = device.attribute_value(attr_name) {
         let tmp = value.to_string_lossy().to_string();
         return Ok(tmp);
@@ -113,14 +110,17 @@ pub fn read_attr_string(device: &Device, attr_name: &str) -> Result<String> {
     Err(PlatformError::AttrNotFound(attr_name.to_owned()))
 }
 
-pub fn write_attr_string(device: &mut Device, attr: &str, value: &str) -> Result<()> {
+pub fn write_attr_string(device: &mut SysfsDevice, attr: &str, value: &str) -> Result<()> {
     let tmp = value.trim();
     device
         .set_attribute_value(attr, tmp)
         .map_err(|e| PlatformError::IoPath(attr.into(), e))
 }
 
-pub fn read_attr_string_array(device: &Device, attr_name: &str) -> Result<Vec<PlatformProfile>> {
+pub fn read_attr_string_array(
+    device: &SysfsDevice,
+    attr_name: &str,
+) -> Result<Vec<PlatformProfile>> {
     if let Some(value) = device.attribute_value(attr_name) {
         let tmp: Vec<PlatformProfile> = value
             .to_string_lossy()
@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 use zbus::zvariant::{OwnedValue, Type, Value};
 
 use crate::error::{PlatformError, Result};
+use crate::sysfs::SysfsRoot;
 use crate::{attr_string, attr_string_array, to_device};
 
 /// The "platform" device provides access to things like:
@@ -37,29 +38,15 @@ impl RogPlatform {
     );
 
     pub fn new() -> Result<Self> {
-        let mut enumerator = udev::Enumerator::new().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("enumerator failed".into(), err)
-        })?;
-        enumerator.match_subsystem("platform").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-        enumerator.match_sysname("asus-nb-wmi").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-
-        if let Some(device) = (enumerator.scan_devices().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("scan_devices failed".into(), err)
-        })?)
-        .next()
-        {
+        let root = SysfsRoot::get();
+        for device in root.scan_subsystem("platform")? {
+            if device.sysname() != "asus-nb-wmi" {
+                continue;
+            }
             info!("Found platform support at {:?}", device.sysname());
             return Ok(Self {
                 path: device.syspath().to_owned(),
-                pp_path: PathBuf::from_str("/sys/firmware/acpi").unwrap(),
+                pp_path: root.join("firmware/acpi"),
             });
         }
         Err(PlatformError::MissingFunction(
@@ -315,4 +302,5 @@ pub enum Properties {
     MiniLedMode,
     EgpuEnable,
     ThrottlePolicy,
+    ChargeMode,
 }
@@ -1,9 +1,73 @@
 use std::path::PathBuf;
 
-use log::{info, warn};
+use log::info;
+use serde::{Deserialize, Serialize};
+use zbus::zvariant::{OwnedValue, Type, Value};
 
 use crate::error::{PlatformError, Result};
-use crate::{attr_num, to_device};
+use crate::sysfs::{SysfsDevice, SysfsRoot};
+use crate::{attr_num, get_attr_num, has_attr, read_attr_num, read_attr_string, to_device};
+
+/// One reading of the battery, for tracking wear and how the charge limit is
+/// holding over time
+#[derive(Deserialize, Serialize, Type, Value, OwnedValue, Debug, Default, Clone, PartialEq)]
+pub struct BatterySample {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    /// Charge in percent
+    pub capacity: u8,
+    /// `charge_control_end_threshold` when sampled, 100 if there is none
+    pub charge_limit: u8,
+    /// 0 if the battery doesn't count cycles
+    pub cycle_count: u32,
+    /// The full capacity as a percent of the design capacity, 0 if unknown
+    pub health: f64,
+    /// Watts, positive while charging and negative while discharging
+    pub rate: f64,
+    /// Volts, 0 if unknown
+    pub voltage: f64,
+    /// Degrees C, 0 if the battery has no sensor
+    pub temp: f64,
+}
+
+impl BatterySample {
+    /// Read a `power_supply` battery device. Only `capacity` is required, the
+    /// capacities are taken from the `energy_*` attributes or else `charge_*`.
+    pub fn read(battery: &SysfsDevice, time: u64) -> Result<Self> {
+        let num = |attr: &str| read_attr_num::<i64>(battery, attr).ok();
+        let ratio = |full: Option<i64>, design: Option<i64>| match (full, design) {
+            (Some(full), Some(design)) if design > 0 => Some(full as f64 * 100.0 / design as f64),
+            _ => None,
+        };
+        let health = ratio(num("energy_full"), num("energy_full_design"))
+            .or_else(|| ratio(num("charge_full"), num("charge_full_design")))
+            .unwrap_or_default();
+        let voltage = num("voltage_now").unwrap_or_default() as f64 / 1_000_000.0;
+        // Drivers differ in the sign of these so it comes from the status
+        let watts = num("power_now")
+            .map(|uw| uw.abs() as f64 / 1_000_000.0)
+            .or_else(|| num("current_now").map(|ua| ua.abs() as f64 / 1_000_000.0 * voltage))
+            .unwrap_or_default();
+        let rate = match read_attr_string(battery, "status")
+            .as_deref()
+            .map(str::trim)
+        {
+            Ok("Charging") => watts,
+            Ok("Discharging") => -watts,
+            _ => 0.0,
+        };
+        Ok(Self {
+            time,
+            capacity: read_attr_num(battery, "capacity")?,
+            charge_limit: read_attr_num(battery, "charge_control_end_threshold").unwrap_or(100),
+            cycle_count: num("cycle_count").unwrap_or_default().max(0) as u32,
+            health,
+            rate,
+            voltage,
+            temp: num("temp").unwrap_or_default() as f64 / 10.0,
+        })
+    }
+}
 
 /// The "platform" device provides access to things like:
 /// - `dgpu_disable`
@@ -24,6 +88,18 @@ impl AsusPower {
 
     attr_num!("online", mains, u8);
 
+    has_attr!("capacity" battery);
+
+    get_attr_num!(
+        /// Battery charge in percent
+        "capacity" battery u8
+    );
+
+    /// Sample the battery, see `BatterySample::read()`
+    pub fn battery_sample(&self, time: u64) -> Result<BatterySample> {
+        BatterySample::read(&to_device(&self.battery)?, time)
+    }
+
     /// When checking for battery this will look in order:
     /// - if attr `manufacturer` contains `asus`
     /// - if attr `charge_control_end_threshold` exists and `energy_full_design`
@@ -35,19 +111,7 @@ impl AsusPower {
         let mut battery = None;
         let mut usb = None;
 
-        let mut enumerator = udev::Enumerator::new().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("enumerator failed".into(), err)
-        })?;
-        enumerator.match_subsystem("power_supply").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-
-        for device in enumerator.scan_devices().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("scan_devices failed".into(), err)
-        })? {
+        for device in SysfsRoot::get().scan_subsystem("power_supply")? {
             if let Some(attr) = device.attribute_value("type") {
                 info!("Power: Checking {:?}", device.syspath());
                 match attr.to_string_lossy().to_ascii_lowercase().trim() {
@@ -106,3 +170,50 @@ impl AsusPower {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    #[test]
+    fn battery_sample_read() {
+        let dir = std::env::temp_dir().join(format!("rog-battery-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        for (attr, value) in [
+            ("capacity", "79"),
+            ("charge_control_end_threshold", "80"),
+            ("charge_full", "4000000"),
+            ("charge_full_design", "5000000"),
+            ("current_now", "-1500000"),
+            ("voltage_now", "16000000"),
+            ("cycle_count", "212"),
+            ("status", "Discharging"),
+        ] {
+            write(dir.join(attr), format!("{value}\n")).unwrap();
+        }
+
+        let battery = SysfsDevice::Path(dir.clone());
+        let sample = BatterySample::read(&battery, 1000).unwrap();
+        assert_eq!(sample.time, 1000);
+        assert_eq!(sample.capacity, 79);
+        assert_eq!(sample.charge_limit, 80);
+        assert_eq!(sample.cycle_count, 212);
+        assert_eq!(sample.health, 80.0);
+        assert_eq!(sample.voltage, 16.0);
+        assert_eq!(sample.rate, -24.0);
+        assert_eq!(sample.temp, 0.0);
+
+        // Energy is preferred over charge
+        write(dir.join("energy_full"), "45000000\n").unwrap();
+        write(dir.join("energy_full_design"), "50000000\n").unwrap();
+        write(dir.join("power_now"), "12000000\n").unwrap();
+        write(dir.join("status"), "Full\n").unwrap();
+        let sample = BatterySample::read(&battery, 1000).unwrap();
+        assert_eq!(sample.health, 90.0);
+        assert_eq!(sample.rate, 0.0);
+
+        remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,477 @@
+//! Rules which switch the platform profile and related settings when the
+//! laptop is in some state, such as on battery below 30% or while a game is
+//! running. Conditions and actions are written as short strings, e.g
+//! `battery<30` or `profile=quiet`, which is the same in the config file, on
+//! D-Bus and on the command line.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use zbus::zvariant::Type;
+
+use crate::cpu::CPUEPP;
+use crate::error::{PlatformError, Result};
+use crate::platform::PlatformProfile;
+
+/// The length `/proc/<pid>/comm` truncates process names to
+const COMM_LEN: usize = 15;
+
+/// Whether a name from `/proc/<pid>/comm` is the process `name`, which the
+/// kernel may have truncated
+pub fn process_matches(comm: &str, name: &str) -> bool {
+    comm == name || (comm.len() == COMM_LEN && name.starts_with(comm))
+}
+
+/// The names of the running processes from `/proc/<pid>/comm`
+pub fn running_processes() -> Vec<String> {
+    let Ok(dir) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    dir.flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+        })
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("comm")).ok())
+        .map(|comm| comm.trim_end().to_owned())
+        .collect()
+}
+
+/// The state of the laptop that rules are checked against
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RuleState {
+    pub power_plugged: bool,
+    /// Battery charge in percent
+    pub battery: u8,
+    /// Minutes past midnight, local time
+    pub minute: u16,
+    pub lid_closed: bool,
+    pub external_display: bool,
+    /// Names of the running processes as in `/proc/<pid>/comm`
+    pub processes: Vec<String>,
+}
+
+/// Something which must be true for a rule to be active
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(into = "String", try_from = "String")]
+#[zvariant(signature = "s")]
+pub enum RuleCondition {
+    /// `power=ac` or `power=battery`
+    PowerPlugged(bool),
+    /// `battery<N`, charge is below N percent
+    BatteryBelow(u8),
+    /// `battery>N`, charge is above N percent
+    BatteryAbove(u8),
+    /// `time=HH:MM-HH:MM` in minutes past midnight. The range spans midnight
+    /// if it starts after it ends.
+    TimeOfDay(u16, u16),
+    /// `lid=closed` or `lid=open`
+    LidClosed(bool),
+    /// `display=external` or `display=internal`
+    ExternalDisplay(bool),
+    /// `process=<name>`
+    ProcessRunning(String),
+}
+
+impl RuleCondition {
+    pub fn matches(&self, state: &RuleState) -> bool {
+        match self {
+            Self::PowerPlugged(plugged) => state.power_plugged == *plugged,
+            Self::BatteryBelow(percent) => state.battery < *percent,
+            Self::BatteryAbove(percent) => state.battery > *percent,
+            Self::TimeOfDay(start, end) => {
+                if start < end {
+                    (*start..*end).contains(&state.minute)
+                } else {
+                    state.minute >= *start || state.minute < *end
+                }
+            }
+            Self::LidClosed(closed) => state.lid_closed == *closed,
+            Self::ExternalDisplay(external) => state.external_display == *external,
+            Self::ProcessRunning(name) => state
+                .processes
+                .iter()
+                .any(|comm| process_matches(comm, name)),
+        }
+    }
+}
+
+fn parse_minute(time: &str) -> Result<u16> {
+    let (hour, minute) = time
+        .split_once(':')
+        .ok_or_else(|| PlatformError::ParseRule(format!("{time} is not HH:MM")))?;
+    let hour: u16 = hour.trim().parse().map_err(|_| PlatformError::ParseNum)?;
+    let minute: u16 = minute.trim().parse().map_err(|_| PlatformError::ParseNum)?;
+    if hour > 23 || minute > 59 {
+        return Err(PlatformError::ParseRule(format!("{time} is not a time")));
+    }
+    Ok(hour * 60 + minute)
+}
+
+fn parse_switch(value: &str, on: &str, off: &str) -> Result<bool> {
+    if value == on {
+        Ok(true)
+    } else if value == off {
+        Ok(false)
+    } else {
+        Err(PlatformError::ParseRule(format!(
+            "expected {on} or {off}, got {value}"
+        )))
+    }
+}
+
+fn parse_percent(value: &str) -> Result<u8> {
+    let percent: u8 = value.trim().parse().map_err(|_| PlatformError::ParseNum)?;
+    if percent > 100 {
+        return Err(PlatformError::ParseRule(format!("{percent}% is over 100%")));
+    }
+    Ok(percent)
+}
+
+impl FromStr for RuleCondition {
+    type Err = PlatformError;
+
+    fn from_str(condition: &str) -> Result<Self> {
+        let condition = condition.trim();
+        let lowercase = condition.to_ascii_lowercase();
+        if let Some(percent) = lowercase.strip_prefix("battery<") {
+            return Ok(Self::BatteryBelow(parse_percent(percent)?));
+        }
+        if let Some(percent) = lowercase.strip_prefix("battery>") {
+            return Ok(Self::BatteryAbove(parse_percent(percent)?));
+        }
+        let (key, name) = condition
+            .split_once('=')
+            .ok_or_else(|| PlatformError::ParseRule(format!("{condition} is not key=value")))?;
+        // Process names are case sensitive
+        let name = name.trim();
+        let value = name.to_ascii_lowercase();
+        let value = value.as_str();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "power" => Ok(Self::PowerPlugged(parse_switch(value, "ac", "battery")?)),
+            "time" => {
+                let (start, end) = value.split_once('-').ok_or_else(|| {
+                    PlatformError::ParseRule(format!("{value} is not HH:MM-HH:MM"))
+                })?;
+                let (start, end) = (parse_minute(start)?, parse_minute(end)?);
+                if start == end {
+                    return Err(PlatformError::ParseRule(format!(
+                        "{value} is an empty time range"
+                    )));
+                }
+                Ok(Self::TimeOfDay(start, end))
+            }
+            "lid" => Ok(Self::LidClosed(parse_switch(value, "closed", "open")?)),
+            "display" => Ok(Self::ExternalDisplay(parse_switch(
+                value, "external", "internal",
+            )?)),
+            "process" if !name.is_empty() => Ok(Self::ProcessRunning(name.to_owned())),
+            _ => Err(PlatformError::ParseRule(format!(
+                "unknown condition {condition}"
+            ))),
+        }
+    }
+}
+
+impl Display for RuleCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PowerPlugged(true) => write!(f, "power=ac"),
+            Self::PowerPlugged(false) => write!(f, "power=battery"),
+            Self::BatteryBelow(percent) => write!(f, "battery<{percent}"),
+            Self::BatteryAbove(percent) => write!(f, "battery>{percent}"),
+            Self::TimeOfDay(start, end) => write!(
+                f,
+                "time={:02}:{:02}-{:02}:{:02}",
+                start / 60,
+                start % 60,
+                end / 60,
+                end % 60
+            ),
+            Self::LidClosed(true) => write!(f, "lid=closed"),
+            Self::LidClosed(false) => write!(f, "lid=open"),
+            Self::ExternalDisplay(true) => write!(f, "display=external"),
+            Self::ExternalDisplay(false) => write!(f, "display=internal"),
+            Self::ProcessRunning(name) => write!(f, "process={name}"),
+        }
+    }
+}
+
+impl From<RuleCondition> for String {
+    fn from(condition: RuleCondition) -> Self {
+        condition.to_string()
+    }
+}
+
+impl TryFrom<String> for RuleCondition {
+    type Error = PlatformError;
+
+    fn try_from(condition: String) -> Result<Self> {
+        condition.parse()
+    }
+}
+
+/// A setting applied while a rule is active
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type)]
+#[serde(into = "String", try_from = "String")]
+#[zvariant(signature = "s")]
+pub enum RuleAction {
+    /// `profile=<platform profile>`
+    Profile(PlatformProfile),
+    /// `epp=<energy_performance_preference>`, used instead of the EPP linked
+    /// to the profile
+    Epp(CPUEPP),
+    /// `ppt=on` or `ppt=off`, use the PPT tuning group of the profile
+    PptGroup(bool),
+    /// `fan-curves=on` or `fan-curves=off`, use the custom fan curves of the
+    /// profile
+    FanCurves(bool),
+    /// `kbd-brightness=<0-3>`
+    KbdBrightness(u8),
+    /// `charge-limit=<20-100>`, the charge limit is restored once no rule
+    /// sets one
+    ChargeLimit(u8),
+}
+
+impl FromStr for RuleAction {
+    type Err = PlatformError;
+
+    fn from_str(action: &str) -> Result<Self> {
+        let action = action.trim().to_ascii_lowercase();
+        let (key, value) = action
+            .split_once('=')
+            .ok_or_else(|| PlatformError::ParseRule(format!("{action} is not key=value")))?;
+        let value = value.trim();
+        match key.trim() {
+            "profile" => Ok(Self::Profile(value.parse()?)),
+            "epp" => {
+                let epp = CPUEPP::from(value);
+                if String::from(epp) != value {
+                    return Err(PlatformError::ParseRule(format!("unknown EPP {value}")));
+                }
+                Ok(Self::Epp(epp))
+            }
+            "ppt" => Ok(Self::PptGroup(parse_switch(value, "on", "off")?)),
+            "fan-curves" => Ok(Self::FanCurves(parse_switch(value, "on", "off")?)),
+            "kbd-brightness" => match value.parse() {
+                Ok(level @ 0..=3) => Ok(Self::KbdBrightness(level)),
+                _ => Err(PlatformError::ParseRule(format!(
+                    "keyboard brightness {value} is not 0-3"
+                ))),
+            },
+            "charge-limit" => match value.parse() {
+                Ok(limit @ 20..=100) => Ok(Self::ChargeLimit(limit)),
+                _ => Err(PlatformError::ParseRule(format!(
+                    "charge limit {value} is not 20-100"
+                ))),
+            },
+            _ => Err(PlatformError::ParseRule(format!("unknown action {action}"))),
+        }
+    }
+}
+
+impl Display for RuleAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let switch = |on: bool| if on { "on" } else { "off" };
+        match self {
+            Self::Profile(profile) => write!(f, "profile={}", <&str>::from(profile)),
+            Self::Epp(epp) => write!(f, "epp={}", String::from(*epp)),
+            Self::PptGroup(on) => write!(f, "ppt={}", switch(*on)),
+            Self::FanCurves(on) => write!(f, "fan-curves={}", switch(*on)),
+            Self::KbdBrightness(level) => write!(f, "kbd-brightness={level}"),
+            Self::ChargeLimit(limit) => write!(f, "charge-limit={limit}"),
+        }
+    }
+}
+
+impl From<RuleAction> for String {
+    fn from(action: RuleAction) -> Self {
+        action.to_string()
+    }
+}
+
+impl TryFrom<String> for RuleAction {
+    type Error = PlatformError;
+
+    fn try_from(action: String) -> Result<Self> {
+        action.parse()
+    }
+}
+
+/// A set of conditions and the actions taken while they all match
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ProfileRule {
+    /// Adding a rule with the same name replaces it
+    pub name: String,
+    /// Of the rules which match, the highest priority is the active one
+    pub priority: u32,
+    pub enabled: bool,
+    /// A rule without conditions always matches
+    pub conditions: Vec<RuleCondition>,
+    pub actions: Vec<RuleAction>,
+}
+
+impl ProfileRule {
+    pub fn matches(&self, state: &RuleState) -> bool {
+        self.enabled && self.conditions.iter().all(|c| c.matches(state))
+    }
+
+    /// The platform profile the rule switches to
+    pub fn profile(&self) -> Option<PlatformProfile> {
+        self.actions.iter().find_map(|a| match a {
+            RuleAction::Profile(profile) => Some(*profile),
+            _ => None,
+        })
+    }
+
+    /// The EPP the rule sets instead of the one linked to the profile
+    pub fn epp(&self) -> Option<CPUEPP> {
+        self.actions.iter().find_map(|a| match a {
+            RuleAction::Epp(epp) => Some(*epp),
+            _ => None,
+        })
+    }
+
+    /// The charge limit the rule sets
+    pub fn charge_limit(&self) -> Option<u8> {
+        self.actions.iter().find_map(|a| match a {
+            RuleAction::ChargeLimit(limit) => Some(*limit),
+            _ => None,
+        })
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(PlatformError::ParseRule("a rule needs a name".to_owned()));
+        }
+        if self.actions.is_empty() {
+            return Err(PlatformError::ParseRule(format!(
+                "rule {} has no actions",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The rule which should be active, the highest priority of those that
+/// match. Of equal priorities the first is used.
+pub fn active_rule<'a>(rules: &'a [ProfileRule], state: &RuleState) -> Option<&'a ProfileRule> {
+    rules.iter().filter(|r| r.matches(state)).reduce(|best, r| {
+        if r.priority > best.priority {
+            r
+        } else {
+            best
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, priority: u32, conditions: &[&str], actions: &[&str]) -> ProfileRule {
+        ProfileRule {
+            name: name.to_owned(),
+            priority,
+            enabled: true,
+            conditions: conditions.iter().map(|c| c.parse().unwrap()).collect(),
+            actions: actions.iter().map(|a| a.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn rule_strings_round_trip() {
+        for condition in [
+            "power=ac", "power=battery", "battery<30", "battery>80", "time=22:00-06:30",
+            "lid=closed", "display=external", "process=steam",
+        ] {
+            let parsed: RuleCondition = condition.parse().unwrap();
+            assert_eq!(parsed.to_string(), condition);
+        }
+        for action in [
+            "profile=low-power", "epp=balance_power", "ppt=on", "fan-curves=off",
+            "kbd-brightness=2", "charge-limit=80",
+        ] {
+            let parsed: RuleAction = action.parse().unwrap();
+            assert_eq!(parsed.to_string(), action);
+        }
+        for bad in [
+            "battery<101", "time=25:00-06:00", "time=06:00-06:00", "lid=shut", "process=",
+            "speed=fast",
+        ] {
+            assert!(bad.parse::<RuleCondition>().is_err(), "{bad}");
+        }
+        for bad in [
+            "epp=fast", "kbd-brightness=4", "charge-limit=10", "profile",
+        ] {
+            assert!(bad.parse::<RuleAction>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn rule_conditions_match_state() {
+        let night: RuleCondition = "time=22:00-06:00".parse().unwrap();
+        let day: RuleCondition = "time=09:00-17:00".parse().unwrap();
+        let mut state = RuleState {
+            minute: 23 * 60,
+            ..Default::default()
+        };
+        assert!(night.matches(&state));
+        assert!(!day.matches(&state));
+        state.minute = 6 * 60;
+        assert!(!night.matches(&state));
+        state.minute = 12 * 60;
+        assert!(day.matches(&state));
+
+        // comm truncates names to 15 characters
+        state.processes = vec![
+            "steamwebhelper".to_owned(),
+            "MonsterHunterWi".to_owned(),
+        ];
+        assert!(RuleCondition::ProcessRunning("steamwebhelper".to_owned()).matches(&state));
+        assert!(RuleCondition::ProcessRunning("MonsterHunterWilds.exe".to_owned()).matches(&state));
+        assert!(!RuleCondition::ProcessRunning("steam".to_owned()).matches(&state));
+        let process: RuleCondition = "Process=MonsterHunterWilds.exe".parse().unwrap();
+        assert!(process.matches(&state));
+    }
+
+    #[test]
+    fn active_rule_by_priority() {
+        let mut rules = vec![
+            rule("battery", 10, &["power=battery"], &["profile=quiet"]),
+            rule(
+                "low",
+                20,
+                &[
+                    "power=battery", "battery<30",
+                ],
+                &[
+                    "profile=low-power", "kbd-brightness=0",
+                ],
+            ),
+            rule("fallback", 0, &[], &["profile=balanced"]),
+        ];
+        let mut state = RuleState {
+            battery: 50,
+            ..Default::default()
+        };
+        assert_eq!(active_rule(&rules, &state).unwrap().name, "battery");
+        state.battery = 20;
+        let active = active_rule(&rules, &state).unwrap();
+        assert_eq!(active.name, "low");
+        assert_eq!(active.profile(), Some(PlatformProfile::LowPower));
+        assert_eq!(active.charge_limit(), None);
+        state.power_plugged = true;
+        assert_eq!(active_rule(&rules, &state).unwrap().name, "fallback");
+        rules[2].enabled = false;
+        assert!(active_rule(&rules, &state).is_none());
+
+        assert!(rule("", 0, &[], &["ppt=on"]).check().is_err());
+        assert!(rule("empty", 0, &[], &[]).check().is_err());
+    }
+}
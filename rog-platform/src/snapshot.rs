@@ -0,0 +1,254 @@
+//! Capture of every sysfs attribute that asusd uses, for attaching to bug
+//! reports and replaying through `SysfsRoot`.
+
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_dir, read_to_string, write};
+use std::path::{Component, Path};
+
+use dmi_id::DMIID;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PlatformError, Result};
+use crate::sysfs::SysfsRoot;
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A directory of devices to capture. Each entry in `dir` which passes
+/// `filter` is either a file, which is captured, or a device directory, in
+/// which case its files and the files of each named `subdirs` are captured.
+struct Source {
+    dir: &'static str,
+    filter: fn(&str) -> bool,
+    subdirs: &'static [&'static str],
+}
+
+const SOURCES: &[Source] = &[
+    Source {
+        dir: "bus/platform/devices",
+        filter: |name| name == "asus-nb-wmi",
+        subdirs: &[],
+    },
+    Source {
+        dir: "firmware/acpi",
+        filter: |name| name.starts_with("platform_profile"),
+        subdirs: &[],
+    },
+    Source {
+        dir: "class/firmware-attributes/asus-armoury/attributes",
+        filter: |_| true,
+        subdirs: &[],
+    },
+    Source {
+        dir: "class/power_supply",
+        filter: |_| true,
+        subdirs: &[],
+    },
+    Source {
+        dir: "class/hwmon",
+        filter: |_| true,
+        subdirs: &[],
+    },
+    Source {
+        dir: "class/leds",
+        filter: |name| name.contains("kbd_backlight") || name.contains("ally:rgb:gamepad"),
+        subdirs: &[],
+    },
+    Source {
+        dir: "class/backlight",
+        filter: |_| true,
+        subdirs: &[],
+    },
+    Source {
+        dir: "bus/cpu/devices",
+        filter: |_| true,
+        subdirs: &["cpufreq"],
+    },
+    Source {
+        dir: "class/hidraw",
+        filter: |_| true,
+        subdirs: &["device"],
+    },
+    Source {
+        dir: "class/dmi",
+        filter: |name| name == "id",
+        subdirs: &[],
+    },
+];
+
+/// Attributes which identify the owner rather than the model
+fn is_private(name: &str) -> bool {
+    name.contains("serial") || name.contains("uuid")
+}
+
+/// A portable recording of a laptop's sysfs. `files` is keyed by the path
+/// relative to the sysfs root, e.g `class/power_supply/BAT0/type`, and holds
+/// the raw contents. Binary or unreadable attributes are skipped.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SysfsSnapshot {
+    pub version: u32,
+    pub rog_platform: String,
+    pub kernel: String,
+    pub dmi: DMIID,
+    pub files: BTreeMap<String, String>,
+}
+
+impl SysfsSnapshot {
+    /// Record all the attributes under `root` that asusd may use
+    pub fn capture(root: &SysfsRoot) -> Self {
+        let mut snapshot = Self {
+            version: SNAPSHOT_VERSION,
+            rog_platform: crate::VERSION.to_owned(),
+            kernel: read_to_string("/proc/sys/kernel/osrelease")
+                .map(|s| s.trim().to_owned())
+                .unwrap_or_default(),
+            dmi: root.dmi_id().unwrap_or_default(),
+            files: BTreeMap::new(),
+        };
+
+        for source in SOURCES {
+            let Ok(entries) = read_dir(root.join(source.dir)) else {
+                debug!("Snapshot: {} not found", source.dir);
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !(source.filter)(&name) {
+                    continue;
+                }
+                let path = entry.path();
+                if path.is_dir() {
+                    snapshot.capture_files(root, &path);
+                    for sub in source.subdirs {
+                        snapshot.capture_files(root, &path.join(sub));
+                    }
+                } else {
+                    snapshot.capture_file(root, &path);
+                }
+            }
+        }
+        snapshot
+    }
+
+    fn capture_files(&mut self, root: &SysfsRoot, dir: &Path) {
+        if let Ok(entries) = read_dir(dir) {
+            for entry in entries.flatten() {
+                // Only regular files, symlinks in sysfs lead back up the tree
+                if entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                    self.capture_file(root, &entry.path());
+                }
+            }
+        }
+    }
+
+    fn capture_file(&mut self, root: &SysfsRoot, path: &Path) {
+        let Some(name) = path.file_name() else {
+            return;
+        };
+        if is_private(&name.to_string_lossy()) {
+            return;
+        }
+        let Ok(relative) = path.strip_prefix(root.path()) else {
+            return;
+        };
+        // Write-only and binary attributes fail here and are left out
+        if let Ok(contents) = read_to_string(path) {
+            self.files
+                .insert(relative.to_string_lossy().to_string(), contents);
+        }
+    }
+
+    /// Write the recording out as a directory tree which can then be used as
+    /// a `SysfsRoot`, e.g `ROG_SYSFS_ROOT=<dir> asusd`
+    pub fn extract(&self, dir: &Path) -> Result<()> {
+        if self.version > SNAPSHOT_VERSION {
+            warn!(
+                "Snapshot version {} is newer than supported {SNAPSHOT_VERSION}",
+                self.version
+            );
+        }
+        for (relative, contents) in &self.files {
+            let relative = Path::new(relative);
+            if !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+            {
+                return Err(PlatformError::IoPath(
+                    relative.to_string_lossy().to_string(),
+                    std::io::ErrorKind::InvalidInput.into(),
+                ));
+            }
+            let path = dir.join(relative);
+            if let Some(parent) = path.parent() {
+                create_dir_all(parent)
+                    .map_err(|e| PlatformError::IoPath(parent.to_string_lossy().to_string(), e))?;
+            }
+            write(&path, contents)
+                .map_err(|e| PlatformError::IoPath(path.to_string_lossy().to_string(), e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_dir_all;
+
+    use super::*;
+
+    #[test]
+    fn capture_extract_round_trip() {
+        let base = std::env::temp_dir().join(format!("rog-snapshot-{}", std::process::id()));
+        let live = base.join("live");
+        let files = [
+            ("bus/platform/devices/asus-nb-wmi/panel_od", "0\n"),
+            ("bus/platform/devices/other/ignored", "1\n"),
+            ("firmware/acpi/platform_profile", "balanced\n"),
+            (
+                "class/power_supply/BAT0/charge_control_end_threshold",
+                "80\n",
+            ),
+            ("class/leds/asus::kbd_backlight/brightness", "2\n"),
+            ("class/leds/input3::capslock/brightness", "0\n"),
+            (
+                "bus/cpu/devices/cpu0/cpufreq/scaling_governor",
+                "powersave\n",
+            ),
+            ("class/dmi/id/board_name", "GA402X\n"),
+            ("class/dmi/id/product_serial", "secret\n"),
+        ];
+        for (path, contents) in files {
+            let path = live.join(path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, contents).unwrap();
+        }
+
+        let snapshot = SysfsSnapshot::capture(&SysfsRoot::new(&live));
+        assert_eq!(snapshot.dmi.board_name, "GA402X");
+        assert_eq!(
+            snapshot
+                .files
+                .get("class/leds/asus::kbd_backlight/brightness"),
+            Some(&"2\n".to_string())
+        );
+        assert!(!snapshot
+            .files
+            .contains_key("bus/platform/devices/other/ignored"));
+        assert!(!snapshot
+            .files
+            .contains_key("class/leds/input3::capslock/brightness"));
+        assert!(!snapshot.files.contains_key("class/dmi/id/product_serial"));
+        assert_eq!(snapshot.files.len(), 6);
+
+        let replay = base.join("replay");
+        snapshot.extract(&replay).unwrap();
+        let replayed = SysfsSnapshot::capture(&SysfsRoot::new(&replay));
+        assert_eq!(snapshot, replayed);
+
+        let mut bad = SysfsSnapshot::default();
+        bad.files.insert("../escape".to_owned(), String::new());
+        assert!(bad.extract(&replay).is_err());
+
+        remove_dir_all(&base).ok();
+    }
+}
@@ -0,0 +1,301 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::{read_dir, read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use dmi_id::DMIID;
+use log::{info, warn};
+
+use crate::error::{PlatformError, Result};
+
+/// The real sysfs mount point
+pub const LIVE_ROOT: &str = "/sys";
+/// Environment variable which, if set, points all sysfs access at a directory
+/// tree instead of the live `/sys`
+pub const SYSFS_ROOT_ENV: &str = "ROG_SYSFS_ROOT";
+
+static ROOT_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// The root that all sysfs paths are resolved against. This is `/sys` unless
+/// overridden by `SysfsRoot::set()` or the `ROG_SYSFS_ROOT` env var, in which
+/// case the tree is expected to mirror the layout of `/sys`, e.g:
+/// - `class/power_supply/BAT0/charge_control_end_threshold`
+/// - `bus/platform/devices/asus-nb-wmi/`
+/// - `firmware/acpi/platform_profile`
+///
+/// When not live, device discovery is done by directory listing instead of
+/// udev, and attributes are read and written as plain files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysfsRoot {
+    path: PathBuf,
+}
+
+impl SysfsRoot {
+    /// A root at `path`, this does not change the active root
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Fetch the currently active root
+    pub fn get() -> Self {
+        if let Some(path) = ROOT_OVERRIDE.read().ok().and_then(|p| p.clone()) {
+            return Self { path };
+        }
+        if let Some(path) = std::env::var_os(SYSFS_ROOT_ENV).filter(|p| !p.is_empty()) {
+            return Self { path: path.into() };
+        }
+        Self {
+            path: PathBuf::from(LIVE_ROOT),
+        }
+    }
+
+    /// Override the root for the whole process. Everything created after this
+    /// call will use the new root.
+    pub fn set(path: impl Into<PathBuf>) {
+        let path = path.into();
+        info!("Using sysfs root at {path:?}");
+        if let Ok(mut lock) = ROOT_OVERRIDE.write() {
+            *lock = Some(path);
+        }
+    }
+
+    /// Remove any override set by `SysfsRoot::set()`
+    pub fn reset() {
+        if let Ok(mut lock) = ROOT_OVERRIDE.write() {
+            *lock = None;
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// True if this is the real `/sys`
+    pub fn is_live(&self) -> bool {
+        self.path == Path::new(LIVE_ROOT)
+    }
+
+    /// The DMI ids under this root, read from udev if this is the live `/sys`
+    pub fn dmi_id(&self) -> std::result::Result<DMIID, String> {
+        if self.is_live() {
+            DMIID::new()
+        } else {
+            DMIID::from_dir(&self.join("class/dmi/id"))
+        }
+    }
+
+    /// Resolve a path relative to `/sys`, e.g `firmware/acpi`
+    pub fn join(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.path.join(relative)
+    }
+
+    /// Open the device at `syspath`
+    pub fn device(&self, syspath: &Path) -> Result<SysfsDevice> {
+        if self.is_live() {
+            return udev::Device::from_syspath(syspath)
+                .map(SysfsDevice::Udev)
+                .map_err(|e| {
+                    PlatformError::Udev("Couldn't transform syspath to device".to_owned(), e)
+                });
+        }
+        if syspath.is_dir() {
+            return Ok(SysfsDevice::Path(syspath.to_owned()));
+        }
+        Err(PlatformError::MissingFunction(format!(
+            "{syspath:?} is not a device directory"
+        )))
+    }
+
+    /// List all devices of a subsystem, the equivalent of a udev enumerator
+    /// with `match_subsystem()`. In a non-live root the devices are found in
+    /// `class/<subsystem>/` or `bus/<subsystem>/devices/`.
+    pub fn scan_subsystem(&self, subsystem: &str) -> Result<Vec<SysfsDevice>> {
+        if self.is_live() {
+            let mut enumerator = udev::Enumerator::new().map_err(|err| {
+                warn!("{}", err);
+                PlatformError::Udev("enumerator failed".into(), err)
+            })?;
+            enumerator.match_subsystem(subsystem).map_err(|err| {
+                warn!("{}", err);
+                PlatformError::Udev("match_subsystem failed".into(), err)
+            })?;
+            return Ok(enumerator
+                .scan_devices()
+                .map_err(|err| {
+                    warn!("{}", err);
+                    PlatformError::Udev("scan_devices failed".into(), err)
+                })?
+                .map(SysfsDevice::Udev)
+                .collect());
+        }
+
+        let mut devices = Vec::new();
+        for dir in [
+            self.join(format!("class/{subsystem}")),
+            self.join(format!("bus/{subsystem}/devices")),
+        ] {
+            if let Ok(entries) = read_dir(&dir) {
+                for entry in entries.flatten() {
+                    if entry.path().is_dir() {
+                        devices.push(SysfsDevice::Path(entry.path()));
+                    }
+                }
+            }
+        }
+        devices.sort_by(|a, b| a.syspath().cmp(b.syspath()));
+        Ok(devices)
+    }
+}
+
+impl Default for SysfsRoot {
+    fn default() -> Self {
+        Self::get()
+    }
+}
+
+/// A device in sysfs, backed by udev for the live root or by a plain
+/// directory for a sandbox root
+#[derive(Clone)]
+pub enum SysfsDevice {
+    Udev(udev::Device),
+    Path(PathBuf),
+}
+
+impl std::fmt::Debug for SysfsDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SysfsDevice({:?})", self.syspath())
+    }
+}
+
+impl SysfsDevice {
+    pub fn syspath(&self) -> &Path {
+        match self {
+            Self::Udev(dev) => dev.syspath(),
+            Self::Path(path) => path,
+        }
+    }
+
+    pub fn sysname(&self) -> &OsStr {
+        match self {
+            Self::Udev(dev) => dev.sysname(),
+            Self::Path(path) => path.file_name().unwrap_or_default(),
+        }
+    }
+
+    /// Read an attribute, with any trailing newline removed as udev does
+    pub fn attribute_value(&self, attr: &str) -> Option<OsString> {
+        match self {
+            Self::Udev(dev) => dev.attribute_value(attr).map(|v| v.to_owned()),
+            Self::Path(path) => read_to_string(path.join(attr))
+                .ok()
+                .map(|v| v.trim_end_matches('\n').into()),
+        }
+    }
+
+    pub fn set_attribute_value(
+        &mut self,
+        attr: &str,
+        value: impl AsRef<OsStr>,
+    ) -> std::io::Result<()> {
+        match self {
+            Self::Udev(dev) => dev.set_attribute_value(attr, value),
+            Self::Path(path) => write(path.join(attr), value.as_ref().as_encoded_bytes()),
+        }
+    }
+
+    /// The names of all attributes of the device
+    pub fn attribute_names(&self) -> Vec<String> {
+        match self {
+            Self::Udev(dev) => dev
+                .attributes()
+                .map(|a| a.name().to_string_lossy().to_string())
+                .collect(),
+            Self::Path(path) => read_dir(path)
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .filter(|e| e.path().is_file())
+                        .map(|e| e.file_name().to_string_lossy().to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn has_attribute(&self, attr: &str) -> bool {
+        match self {
+            Self::Udev(_) => self.attribute_names().iter().any(|a| a == attr),
+            Self::Path(path) => path.join(attr).is_file(),
+        }
+    }
+
+    /// Check if the device has a parent belonging to `subsystem`. A sandbox
+    /// tree is commonly flattened when captured, so without a `subsystem` link
+    /// to follow this is assumed true.
+    pub fn has_parent_with_subsystem(&self, subsystem: &str) -> Result<bool> {
+        match self {
+            Self::Udev(dev) => dev
+                .parent_with_subsystem(subsystem)
+                .map(|p| p.is_some())
+                .map_err(|e| PlatformError::Udev("parent_with_subsystem failed".into(), e)),
+            Self::Path(path) => {
+                let path = path.canonicalize().unwrap_or_else(|_| path.clone());
+                let mut found_link = false;
+                for parent in path.ancestors().skip(1) {
+                    if let Ok(link) = parent.join("subsystem").read_link() {
+                        found_link = true;
+                        if link.file_name() == Some(OsStr::new(subsystem)) {
+                            return Ok(true);
+                        }
+                    }
+                }
+                Ok(!found_link)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+
+    use super::*;
+    use crate::{read_attr_num, write_attr_num};
+
+    /// The root is only used through this `SysfsRoot`, the override for the
+    /// process is left alone as other tests run in parallel
+    #[test]
+    fn sandbox_power_supply() {
+        let dir = std::env::temp_dir().join(format!("rog-sysfs-{}", std::process::id()));
+        let bat = dir.join("class/power_supply/BAT0");
+        let ac = dir.join("class/power_supply/AC0");
+        create_dir_all(&bat).unwrap();
+        create_dir_all(&ac).unwrap();
+        write(bat.join("type"), "Battery\n").unwrap();
+        write(bat.join("charge_control_end_threshold"), "80\n").unwrap();
+        write(ac.join("type"), "Mains\n").unwrap();
+        write(ac.join("online"), "1\n").unwrap();
+
+        let root = SysfsRoot::new(&dir);
+        assert!(!root.is_live());
+        let devices = root.scan_subsystem("power_supply").unwrap();
+        let names: Vec<_> = devices.iter().map(|d| d.sysname().to_owned()).collect();
+        assert_eq!(names, ["AC0", "BAT0"]);
+        assert!(root.scan_subsystem("hwmon").unwrap().is_empty());
+
+        let mut battery = root.device(&bat).unwrap();
+        assert!(battery.has_attribute("charge_control_end_threshold"));
+        assert_eq!(
+            read_attr_num::<u8>(&battery, "charge_control_end_threshold").unwrap(),
+            80
+        );
+        assert_eq!(read_attr_num::<u8>(&devices[0], "online").unwrap(), 1);
+        write_attr_num(&mut battery, "charge_control_end_threshold", 60u8).unwrap();
+        assert_eq!(
+            read_to_string(bat.join("charge_control_end_threshold")).unwrap(),
+            "60"
+        );
+
+        remove_dir_all(&dir).ok();
+    }
+}
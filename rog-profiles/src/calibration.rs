@@ -0,0 +1,166 @@
+//! The RPM a fan reaches at each pwm, measured by stepping the fan through its
+//! duty range in manual mode. Below some duty a fan doesn't spin at all, a
+//! curve point there is a dead zone where the fan is off rather than slow.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "dbus")]
+use zbus::zvariant::Type;
+
+use crate::error::ProfileError;
+use crate::fan_curve_set::CurveData;
+use crate::{Device, FanCurvePU};
+
+/// The pwm added at each calibration step
+pub const CALIBRATION_STEP: u8 = 16;
+/// Anything below this is a fan reporting noise rather than spinning
+pub const MIN_SPIN_RPM: u32 = 100;
+
+/// The pwm of each calibration step, from off to full
+pub fn calibration_steps() -> Vec<u8> {
+    let mut steps: Vec<u8> = (0..=u8::MAX).step_by(CALIBRATION_STEP as usize).collect();
+    if steps.last() != Some(&u8::MAX) {
+        steps.push(u8::MAX);
+    }
+    steps
+}
+
+/// Read `fanN_input` from the `asus` hwmon device
+pub fn read_rpm(device: &Device, fan: FanCurvePU) -> Result<u32, ProfileError> {
+    let fan_num: char = fan.into();
+    let attr = format!("fan{fan_num}_input");
+    let value = device
+        .attribute_value(&attr)
+        .ok_or(ProfileError::NotFound(attr))?;
+    value
+        .to_string_lossy()
+        .trim()
+        .parse()
+        .map_err(ProfileError::ParseFanCurveDigit)
+}
+
+/// The measured pwm to RPM table of one fan
+#[cfg_attr(feature = "dbus", derive(Type))]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct FanCalibration {
+    pub fan: FanCurvePU,
+    /// (pwm 0-255, RPM) in order of pwm
+    pub points: Vec<(u8, u32)>,
+}
+
+impl FanCalibration {
+    /// The RPM expected at a pwm, interpolated between the measured points
+    pub fn rpm_at(&self, pwm: u8) -> u32 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 0;
+        };
+        if pwm <= first.0 {
+            return first.1;
+        }
+        for pair in self.points.windows(2) {
+            let ((p0, r0), (p1, r1)) = (pair[0], pair[1]);
+            if pwm <= p1 {
+                // Nothing is known between a stopped point and the next, so
+                // the fan is taken to be stopped until that point
+                if r0 < MIN_SPIN_RPM {
+                    return if pwm == p1 { r1 } else { r0 };
+                }
+                let amount = (pwm - p0) as f32 / (p1 - p0).max(1) as f32;
+                return (r0 as f32 + (r1 as f32 - r0 as f32) * amount).round() as u32;
+            }
+        }
+        last.1
+    }
+
+    /// The lowest measured pwm from which the fan spins at every higher point.
+    /// `None` if it never spins.
+    pub fn spin_up_pwm(&self) -> Option<u8> {
+        let mut spin_up = None;
+        for (pwm, rpm) in &self.points {
+            if *rpm < MIN_SPIN_RPM {
+                spin_up = None;
+            } else if spin_up.is_none() {
+                spin_up = Some(*pwm);
+            }
+        }
+        spin_up
+    }
+
+    /// The index of each curve point whose pwm asks for the fan to run but is
+    /// below the spin up pwm. A pwm of 0 is taken to mean off.
+    pub fn dead_zone_points(&self, curve: &CurveData) -> Vec<usize> {
+        let Some(spin_up) = self.spin_up_pwm() else {
+            return Vec::new();
+        };
+        curve
+            .pwm
+            .iter()
+            .enumerate()
+            .filter(|(_, pwm)| **pwm > 0 && **pwm < spin_up)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    fn calibration() -> FanCalibration {
+        FanCalibration {
+            fan: FanCurvePU::CPU,
+            points: vec![
+                (0, 0),
+                (32, 40),
+                (64, 0),
+                (96, 1800),
+                (255, 5000),
+            ],
+        }
+    }
+
+    #[test]
+    fn calibration_spin_up_and_dead_zone() {
+        let calibration = calibration();
+        // The blip at 32 isn't the fan spinning up
+        assert_eq!(calibration.spin_up_pwm(), Some(96));
+        assert_eq!(calibration.rpm_at(80), 0);
+        assert_eq!(calibration.rpm_at(96), 1800);
+        assert_eq!(calibration.rpm_at(255), 5000);
+        assert_eq!(calibration.rpm_at(175), 3390);
+
+        let curve = CurveData {
+            fan: FanCurvePU::CPU,
+            pwm: [
+                0, 40, 90, 100, 120, 150, 200, 255,
+            ],
+            temp: [
+                30, 40, 50, 60, 70, 80, 90, 100,
+            ],
+            enabled: true,
+        };
+        assert_eq!(calibration.dead_zone_points(&curve), vec![1, 2]);
+        assert!(FanCalibration::default()
+            .dead_zone_points(&curve)
+            .is_empty());
+
+        let steps = calibration_steps();
+        assert_eq!(steps.first(), Some(&0));
+        assert_eq!(steps.last(), Some(&255));
+        assert_eq!(steps.len(), 17);
+    }
+
+    #[test]
+    fn calibration_read_rpm() {
+        let dir = std::env::temp_dir().join(format!("rog-fan-rpm-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("fan3_input"), "2750\n").unwrap();
+
+        let device = Device::Path(dir.clone());
+        assert_eq!(read_rpm(&device, FanCurvePU::MID).unwrap(), 2750);
+        assert!(read_rpm(&device, FanCurvePU::CPU).is_err());
+
+        remove_dir_all(&dir).ok();
+    }
+}
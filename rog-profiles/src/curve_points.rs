@@ -0,0 +1,306 @@
+//! Fan curves with any number of control points. The firmware takes exactly 8
+//! points, so a `CurvePoints` is interpolated with a monotone spline and
+//! resampled to 8 evenly spaced temperatures when it is written.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "dbus")]
+use zbus::zvariant::Type;
+
+use crate::error::{CurveFault, ProfileError};
+use crate::fan_curve_set::{parse_points, CurveData, CurveLimits};
+use crate::FanCurvePU;
+
+/// The number of points the firmware takes
+pub const FIRMWARE_POINTS: usize = 8;
+
+/// A fan curve of any number of (temperature C, pwm 0-255) control points
+#[cfg_attr(feature = "dbus", derive(Type))]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct CurvePoints {
+    pub fan: FanCurvePU,
+    /// In order of temperature, at least 2
+    pub points: Vec<(u8, u8)>,
+    pub enabled: bool,
+}
+
+impl From<&CurveData> for CurvePoints {
+    fn from(curve: &CurveData) -> Self {
+        Self {
+            fan: curve.fan,
+            points: curve.temp.iter().copied().zip(curve.pwm).collect(),
+            enabled: curve.enabled,
+        }
+    }
+}
+
+impl std::str::FromStr for CurvePoints {
+    type Err = ProfileError;
+
+    /// Parse the same format as `CurveData` with any number of points. The
+    /// returned `CurvePoints` is not enabled.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let points = Self {
+            points: parse_points(input)?,
+            ..Default::default()
+        };
+        points.check()?;
+        Ok(points)
+    }
+}
+
+impl CurvePoints {
+    /// Check the points can be interpolated. Temperatures must rise and pwm
+    /// must not fall from one point to the next.
+    pub fn check(&self) -> Result<(), ProfileError> {
+        if self.points.len() < 2 {
+            return Err(ProfileError::NotEnoughPoints);
+        }
+        for (index, pair) in self.points.windows(2).enumerate() {
+            let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+            let fault = if t1 < t0 {
+                CurveFault::TempFalling(t0, t1)
+            } else if t1 == t0 {
+                CurveFault::TempRepeated(t1)
+            } else if p1 < p0 {
+                CurveFault::PwmFalling(p0, p1)
+            } else {
+                continue;
+            };
+            return Err(ProfileError::InvalidCurvePoint(self.fan, index + 1, fault));
+        }
+        Ok(())
+    }
+
+    /// The spline tangent at each point, limited so the curve never falls
+    /// between points (Fritsch-Carlson)
+    fn tangents(&self) -> Vec<f32> {
+        let slopes: Vec<f32> = self
+            .points
+            .windows(2)
+            .map(|pair| {
+                let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+                (p1 as f32 - p0 as f32) / (t1 as f32 - t0 as f32).max(1.0)
+            })
+            .collect();
+        let last = slopes.len();
+        let mut tangents: Vec<f32> = (0..=last)
+            .map(|i| match i {
+                0 => slopes[0],
+                i if i == last => slopes[last - 1],
+                i if slopes[i - 1] * slopes[i] <= 0.0 => 0.0,
+                i => (slopes[i - 1] + slopes[i]) / 2.0,
+            })
+            .collect();
+        for (i, slope) in slopes.iter().enumerate() {
+            if *slope == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+            let (a, b) = (tangents[i] / slope, tangents[i + 1] / slope);
+            let s = a * a + b * b;
+            if s > 9.0 {
+                let t = 3.0 / s.sqrt();
+                tangents[i] = t * a * slope;
+                tangents[i + 1] = t * b * slope;
+            }
+        }
+        tangents
+    }
+
+    /// The pwm at a temperature, held at the first and last points outside
+    /// of the curve
+    pub fn pwm_at(&self, temp: f32) -> u8 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 0;
+        };
+        if temp <= first.0 as f32 {
+            return first.1;
+        }
+        if temp >= last.0 as f32 {
+            return last.1;
+        }
+        let tangents = self.tangents();
+        for (i, pair) in self.points.windows(2).enumerate() {
+            let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+            if temp > t1 as f32 {
+                continue;
+            }
+            let h = (t1 as f32 - t0 as f32).max(1.0);
+            let t = (temp - t0 as f32) / h;
+            let (t2, t3) = (t * t, t * t * t);
+            let pwm = (2.0 * t3 - 3.0 * t2 + 1.0) * p0 as f32
+                + (t3 - 2.0 * t2 + t) * h * tangents[i]
+                + (-2.0 * t3 + 3.0 * t2) * p1 as f32
+                + (t3 - t2) * h * tangents[i + 1];
+            return pwm.round().clamp(0.0, u8::MAX as f32) as u8;
+        }
+        last.1
+    }
+
+    /// The 8 firmware points, evenly spaced from the first to the last control
+    /// point within the temperature limits
+    pub fn resample(&self, limits: &CurveLimits) -> Result<CurveData, ProfileError> {
+        self.check()?;
+        // check() makes sure there are at least 2 points
+        let first = self.points[0].0.clamp(limits.min_temp, limits.max_temp) as f32;
+        let last = self.points[self.points.len() - 1]
+            .0
+            .clamp(limits.min_temp, limits.max_temp) as f32;
+        let step = (last - first) / (FIRMWARE_POINTS - 1) as f32;
+
+        let mut curve = CurveData {
+            fan: self.fan,
+            enabled: self.enabled,
+            ..Default::default()
+        };
+        let mut pwm_prev = 0;
+        for i in 0..FIRMWARE_POINTS {
+            let temp = (first + step * i as f32).round();
+            curve.temp[i] = temp as u8;
+            // Rounding must not make the curve fall
+            pwm_prev = self.pwm_at(temp).max(pwm_prev);
+            curve.pwm[i] = pwm_prev;
+        }
+        Ok(curve)
+    }
+}
+
+/// Curves generated from the model's limits
+#[cfg_attr(feature = "dbus", derive(Type), zvariant(signature = "s"))]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurvePreset {
+    /// Fans off when cool and slow once hot
+    Silent,
+    Balanced,
+    /// Fans always on and at full speed before the maximum temperature
+    Aggressive,
+}
+
+impl std::str::FromStr for CurvePreset {
+    type Err = ProfileError;
+
+    fn from_str(preset: &str) -> Result<Self, Self::Err> {
+        match preset.to_ascii_lowercase().trim() {
+            "silent" => Ok(Self::Silent),
+            "balanced" => Ok(Self::Balanced),
+            "aggressive" => Ok(Self::Aggressive),
+            _ => Err(ProfileError::ParseProfileName),
+        }
+    }
+}
+
+impl CurvePreset {
+    /// The control points of the preset for a fan. Temperatures are spread
+    /// over the limits, and points from the hot temperature up meet its
+    /// minimum duty.
+    pub fn points(&self, fan: FanCurvePU, limits: &CurveLimits) -> CurvePoints {
+        let low = limits.min_temp.max(30);
+        let high = limits.max_temp.min(100).max(low.saturating_add(3));
+        let hot = limits.hot_temp.clamp(low.saturating_add(2), high - 1);
+        let warm = low + (hot - low) / 2;
+        let hot_pwm = |pwm: u8| pwm.max(limits.min_hot_pwm);
+        let points = match self {
+            Self::Silent => vec![
+                (low, 0),
+                (warm, 40),
+                (hot, hot_pwm(90)),
+                (high, hot_pwm(200)),
+            ],
+            Self::Balanced => vec![
+                (low, 25),
+                (warm, 70),
+                (hot, hot_pwm(140)),
+                (high, 255),
+            ],
+            Self::Aggressive => vec![
+                (low, 50),
+                (warm, 110),
+                (hot, hot_pwm(200)),
+                (high, 255),
+            ],
+        };
+        CurvePoints {
+            fan,
+            points,
+            enabled: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_points_resample() {
+        let points: CurvePoints = "30c:0%,50c:10%,60c:40%,70c:50%,80c:60%,90c:80%,100c:100%"
+            .parse()
+            .unwrap();
+        assert_eq!(points.points.len(), 7);
+        // Control points are kept and the curve never falls between them
+        assert_eq!(points.pwm_at(60.0), 102);
+        let mut prev = 0;
+        for temp in 30..=100 {
+            let pwm = points.pwm_at(temp as f32);
+            assert!(pwm >= prev);
+            prev = pwm;
+        }
+
+        let limits = CurveLimits::default();
+        let curve = points.resample(&limits).unwrap();
+        assert_eq!(curve.temp, [30, 40, 50, 60, 70, 80, 90, 100]);
+        assert_eq!(curve.pwm[0], 0);
+        assert_eq!(curve.pwm[3], 102);
+        assert_eq!(curve.pwm[7], 255);
+        curve.validate(&limits).unwrap();
+        assert_eq!(CurvePoints::from(&curve).points.len(), FIRMWARE_POINTS);
+
+        let repeated = CurvePoints {
+            points: vec![
+                (40, 10),
+                (40, 20),
+            ],
+            ..Default::default()
+        };
+        assert!(matches!(
+            repeated.check(),
+            Err(ProfileError::InvalidCurvePoint(
+                _,
+                1,
+                CurveFault::TempRepeated(40)
+            ))
+        ));
+    }
+
+    #[test]
+    fn curve_presets_meet_limits() {
+        for limits in [
+            CurveLimits::default(),
+            CurveLimits::for_board("GA403UV"),
+            CurveLimits {
+                min_temp: 40,
+                max_temp: 90,
+                hot_temp: 60,
+                min_hot_pwm: 150,
+            },
+        ] {
+            for preset in [
+                CurvePreset::Silent,
+                CurvePreset::Balanced,
+                CurvePreset::Aggressive,
+            ] {
+                let curve = preset
+                    .points(FanCurvePU::GPU, &limits)
+                    .resample(&limits)
+                    .unwrap();
+                assert_eq!(curve.fan, FanCurvePU::GPU);
+                curve.validate(&limits).unwrap();
+            }
+        }
+        assert_eq!(
+            "Silent".parse::<CurvePreset>().unwrap(),
+            CurvePreset::Silent
+        );
+    }
+}
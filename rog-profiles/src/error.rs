@@ -1,8 +1,52 @@
 use std::fmt;
 
 use log::error;
+use rog_platform::error::PlatformError;
 use zbus::fdo::Error as FdoErr;
 
+use crate::FanCurvePU;
+
+/// Why a fan curve point was rejected by `CurveData::validate()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveFault {
+    /// (previous, this)
+    TempFalling(u8, u8),
+    /// (previous, this)
+    PwmFalling(u8, u8),
+    /// Two control points can't be at the same temperature
+    TempRepeated(u8),
+    /// (temp, min, max)
+    TempOutOfRange(u8, u8, u8),
+    /// (pwm, min pwm) at or above the hot temperature
+    DutyTooLow(u8, u8),
+}
+
+impl fmt::Display for CurveFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurveFault::TempFalling(prev, temp) => write!(
+                f,
+                "temperature {}c is lower than the previous point {}c",
+                temp, prev
+            ),
+            CurveFault::PwmFalling(prev, pwm) => {
+                write!(f, "pwm {} is lower than the previous point {}", pwm, prev)
+            }
+            CurveFault::TempRepeated(temp) => {
+                write!(f, "temperature {}c is the same as the previous point", temp)
+            }
+            CurveFault::TempOutOfRange(temp, min, max) => {
+                write!(f, "temperature {}c is outside of {}c-{}c", temp, min, max)
+            }
+            CurveFault::DutyTooLow(pwm, min) => write!(
+                f,
+                "pwm {} is below the minimum of {} for this temperature",
+                pwm, min
+            ),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ProfileError {
     Path(String, std::io::Error),
@@ -11,12 +55,17 @@ pub enum ProfileError {
     NotSupported,
     NotFound(String),
     Io(std::io::Error),
+    Platform(PlatformError),
     ParseProfileName,
     ParseFanCurveDigit(std::num::ParseIntError),
     /// (pwm/temp, prev, next)
     ParseFanCurvePrevHigher(&'static str, u8, u8),
     ParseFanCurvePercentOver100(u8),
     NotEnoughPoints, // Zbus(zbus::Error),
+    /// More than the 8 points the firmware takes, with the number given
+    TooManyPoints(usize),
+    /// (fan, point index, fault)
+    InvalidCurvePoint(FanCurvePU, usize, CurveFault),
 }
 
 impl fmt::Display for ProfileError {
@@ -28,8 +77,12 @@ impl fmt::Display for ProfileError {
             ProfileError::Write(path, error) => write!(f, "Write {}: {}", path, error),
             ProfileError::NotSupported => write!(f, "Not supported"),
             ProfileError::NotEnoughPoints => write!(f, "Less than 8 curve points supplied"),
+            ProfileError::TooManyPoints(count) => {
+                write!(f, "{} curve points supplied, the firmware takes 8", count)
+            }
             ProfileError::NotFound(deets) => write!(f, "Not found: {}", deets),
             ProfileError::Io(detail) => write!(f, "std::io error: {}", detail),
+            ProfileError::Platform(detail) => write!(f, "Platform error: {}", detail),
             ProfileError::ParseProfileName => write!(f, "Invalid profile name"),
             ProfileError::ParseFanCurveDigit(e) => {
                 write!(f, "Could not parse number to 0-255: {}", e)
@@ -41,7 +94,14 @@ impl fmt::Display for ProfileError {
             ),
             ProfileError::ParseFanCurvePercentOver100(value) => {
                 write!(f, "Invalid percentage, {} is higher than 100", value)
-            } // Error::Zbus(detail) => write!(f, "Zbus error: {}", detail),
+            }
+            ProfileError::InvalidCurvePoint(fan, index, fault) => write!(
+                f,
+                "Invalid {:?} fan curve point {}: {}",
+                fan,
+                index + 1,
+                fault
+            ),
         }
     }
 }
@@ -55,11 +115,19 @@ impl From<std::io::Error> for ProfileError {
     }
 }
 
+impl From<PlatformError> for ProfileError {
+    fn from(err: PlatformError) -> Self {
+        error!("ProfileError: got: {err}");
+        ProfileError::Platform(err)
+    }
+}
+
 impl From<ProfileError> for FdoErr {
     fn from(error: ProfileError) -> Self {
         error!("ProfileError: got: {error}");
         match error {
             ProfileError::NotSupported => FdoErr::NotSupported("".to_owned()),
+            ProfileError::InvalidCurvePoint(..) => FdoErr::InvalidArgs(error.to_string()),
             _ => FdoErr::Failed(format!("Failed with {error}")),
         }
     }
@@ -0,0 +1,203 @@
+//! Fan curves in a form that can be shared between machines. The document
+//! records the board it was captured on and the fans it had so that an import
+//! can tell if the curves are meant for another model.
+
+use rog_platform::platform::PlatformProfile;
+use serde::{Deserialize, Serialize};
+
+use crate::fan_curve_set::CurveData;
+use crate::{FanCurvePU, FanCurveProfiles};
+
+/// Bumped on any incompatible change to `FanCurveExport`
+pub const FAN_CURVE_EXPORT_VERSION: u32 = 1;
+
+/// The curves of one profile
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProfileCurves {
+    pub profile: PlatformProfile,
+    pub curves: Vec<CurveData>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FanCurveExport {
+    pub version: u32,
+    /// The DMI board name of the machine the curves were exported from
+    pub board_name: String,
+    /// Every fan the machine has, which may be more than are in the curves
+    pub fans: Vec<FanCurvePU>,
+    pub profiles: Vec<ProfileCurves>,
+}
+
+impl FanCurveExport {
+    /// Anything about the machine that differs from the one this was exported
+    /// from, or which the importer doesn't understand
+    pub fn mismatches(&self, board_name: &str, fans: &[FanCurvePU]) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if self.version > FAN_CURVE_EXPORT_VERSION {
+            mismatches.push(format!(
+                "exported with a newer format version {}",
+                self.version
+            ));
+        }
+        if self.board_name.trim() != board_name.trim() {
+            mismatches.push(format!(
+                "exported from board {} but this is {}",
+                self.board_name.trim(),
+                board_name.trim()
+            ));
+        }
+        if self.fans.len() != fans.len() {
+            mismatches.push(format!(
+                "exported from a machine with {} fans but this has {}",
+                self.fans.len(),
+                fans.len()
+            ));
+        }
+        mismatches
+    }
+
+    /// Every curve in the document
+    pub fn curves(&self) -> impl Iterator<Item = (PlatformProfile, &CurveData)> {
+        self.profiles
+            .iter()
+            .flat_map(|p| p.curves.iter().map(|c| (p.profile, c)))
+    }
+}
+
+impl FanCurveProfiles {
+    /// Export the curves of `profiles` for `fans`. Empty means all.
+    pub fn export(
+        &self,
+        profiles: &[PlatformProfile],
+        fans: &[FanCurvePU],
+        board_name: &str,
+        machine_fans: &[FanCurvePU],
+    ) -> FanCurveExport {
+        let all = [
+            PlatformProfile::Balanced,
+            PlatformProfile::Performance,
+            PlatformProfile::Quiet,
+            PlatformProfile::Custom,
+        ];
+        let profiles = if profiles.is_empty() { &all } else { profiles };
+        FanCurveExport {
+            version: FAN_CURVE_EXPORT_VERSION,
+            board_name: board_name.trim().to_owned(),
+            fans: machine_fans.to_vec(),
+            profiles: profiles
+                .iter()
+                .map(|profile| ProfileCurves {
+                    profile: *profile,
+                    curves: self
+                        .get_fan_curves_for(*profile)
+                        .iter()
+                        .filter(|c| fans.is_empty() || fans.contains(&c.fan))
+                        .cloned()
+                        .collect(),
+                })
+                .filter(|p| !p.curves.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Store every curve of the export for a fan this machine has. Returns a
+    /// warning for each curve which was skipped.
+    pub fn import(&mut self, export: &FanCurveExport) -> Vec<String> {
+        let mut skipped = Vec::new();
+        for (profile, curve) in export.curves() {
+            if self.get_fan_curve_for(&profile, curve.fan).is_none() {
+                skipped.push(format!(
+                    "skipped the {:?} fan curve for {profile}, this machine has no such fan",
+                    curve.fan
+                ));
+                continue;
+            }
+            // Can't fail
+            self.save_fan_curve(curve.clone(), profile).ok();
+        }
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles() -> FanCurveProfiles {
+        let curve = |fan| CurveData {
+            fan,
+            pwm: [
+                10, 20, 30, 40, 50, 60, 70, 80,
+            ],
+            temp: [
+                30, 40, 50, 60, 70, 80, 90, 100,
+            ],
+            enabled: true,
+        };
+        FanCurveProfiles {
+            balanced: vec![
+                curve(FanCurvePU::CPU),
+                curve(FanCurvePU::GPU),
+            ],
+            performance: vec![
+                curve(FanCurvePU::CPU),
+                curve(FanCurvePU::GPU),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn export_selected_curves() {
+        let fans = [
+            FanCurvePU::CPU,
+            FanCurvePU::GPU,
+        ];
+        let export = profiles().export(&[], &[], "GA401Q\n", &fans);
+        assert_eq!(export.board_name, "GA401Q");
+        // Empty profiles are left out
+        assert_eq!(export.profiles.len(), 2);
+        assert_eq!(export.curves().count(), 4);
+
+        let export = profiles().export(
+            &[PlatformProfile::Performance],
+            &[FanCurvePU::GPU],
+            "GA401Q",
+            &fans,
+        );
+        assert_eq!(export.profiles.len(), 1);
+        assert_eq!(export.profiles[0].profile, PlatformProfile::Performance);
+        assert_eq!(export.profiles[0].curves[0].fan, FanCurvePU::GPU);
+        assert!(export.mismatches("GA401Q", &fans).is_empty());
+    }
+
+    #[test]
+    fn import_other_model() {
+        let fans = [
+            FanCurvePU::CPU,
+            FanCurvePU::GPU,
+            FanCurvePU::MID,
+        ];
+        let mut export = profiles().export(&[], &[], "GU604VY", &fans);
+        export.profiles[0].curves.push(CurveData {
+            fan: FanCurvePU::MID,
+            ..Default::default()
+        });
+        export.profiles[0].curves[0].pwm[7] = 200;
+
+        let mismatches = export.mismatches("GA401Q", &fans[..2]);
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(
+            mismatches[0],
+            "exported from board GU604VY but this is GA401Q"
+        );
+
+        let mut local = profiles();
+        let skipped = local.import(&export);
+        assert_eq!(skipped.len(), 1);
+        let curve = local
+            .get_fan_curve_for(&PlatformProfile::Balanced, FanCurvePU::CPU)
+            .unwrap();
+        assert_eq!(curve.pwm[7], 200);
+    }
+}
@@ -1,11 +1,10 @@
 use log::{error, trace};
 use serde::{Deserialize, Serialize};
-use udev::Device;
 #[cfg(feature = "dbus")]
 use zbus::zvariant::Type;
 
-use crate::error::ProfileError;
-use crate::FanCurvePU;
+use crate::error::{CurveFault, ProfileError};
+use crate::{Device, FanCurvePU};
 
 fn set_sysfs_name(string: &mut [u8], fan: char, index: usize) {
     string[3] = fan as u8;
@@ -30,6 +29,67 @@ pub(crate) fn temp_str(fan: char, index: usize) -> String {
     string
 }
 
+/// The bounds a fan curve must stay in before it is written to the firmware.
+/// The defaults are deliberately conservative, models which need more cooling
+/// are listed in `BOARD_CURVE_LIMITS` and can be raised further in config.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurveLimits {
+    /// Lowest temperature in degrees C the firmware accepts
+    pub min_temp: u8,
+    /// Highest temperature in degrees C the firmware accepts
+    pub max_temp: u8,
+    /// Points at or above this temperature must have at least `min_hot_pwm`
+    pub hot_temp: u8,
+    pub min_hot_pwm: u8,
+}
+
+impl Default for CurveLimits {
+    fn default() -> Self {
+        Self {
+            min_temp: 20,
+            max_temp: 110,
+            hot_temp: 80,
+            // 30%
+            min_hot_pwm: 77,
+        }
+    }
+}
+
+/// Boards with less cooling than the defaults allow for, matched against the
+/// DMI board name
+const BOARD_CURVE_LIMITS: &[(&str, CurveLimits)] = &[
+    ("GA401", THIN_CURVE_LIMITS),
+    ("GA402", THIN_CURVE_LIMITS),
+    ("GA403", THIN_CURVE_LIMITS),
+    ("GU603", THIN_CURVE_LIMITS),
+    ("GU604", THIN_CURVE_LIMITS),
+    ("GU605", THIN_CURVE_LIMITS),
+    ("GV301", THIN_CURVE_LIMITS),
+    ("GV302", THIN_CURVE_LIMITS),
+];
+
+/// The thin 13-16" models run hotter, so the fans must be faster sooner
+const THIN_CURVE_LIMITS: CurveLimits = CurveLimits {
+    min_temp: 20,
+    max_temp: 110,
+    hot_temp: 75,
+    // 40%
+    min_hot_pwm: 102,
+};
+
+impl CurveLimits {
+    /// The limits for a DMI board name, or the defaults if the board isn't
+    /// listed
+    pub fn for_board(board_name: &str) -> Self {
+        let board_name = board_name.to_uppercase();
+        BOARD_CURVE_LIMITS
+            .iter()
+            .find(|(board, _)| board_name.contains(board))
+            .map(|(_, limits)| *limits)
+            .unwrap_or_default()
+    }
+}
+
 #[cfg_attr(feature = "dbus", derive(Type))]
 #[derive(Deserialize, Serialize, Default, Debug, Clone)]
 pub struct CurveData {
@@ -78,49 +138,18 @@ impl std::str::FromStr for CurveData {
     /// Temperature range is 0-255 in degrees C. You don't want to be setting
     /// over 100.
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut temp = [0u8; 8];
-        let mut pwm = [0u8; 8];
-        let mut temp_prev = 0;
-        let mut pwm_prev = 0;
-        let mut percentages = false;
-
-        if input.split(',').count() < 8 {
+        let points = parse_points(input)?;
+        if points.len() < 8 {
             return Err(ProfileError::NotEnoughPoints);
         }
-
-        for (index, value) in input.split(',').enumerate() {
-            for (select, num) in value.splitn(2, ['c', ':']).enumerate() {
-                if num.contains('%') {
-                    percentages = true;
-                }
-                let r = num.trim_matches(|c| c == 'c' || c == ':' || c == '%');
-                let r = r.parse::<u8>().map_err(ProfileError::ParseFanCurveDigit)?;
-
-                if select == 0 {
-                    if temp_prev > r {
-                        return Err(ProfileError::ParseFanCurvePrevHigher(
-                            "temperature", temp_prev, r,
-                        ));
-                    }
-                    temp_prev = r;
-                    temp[index] = r;
-                } else {
-                    let mut p = r;
-                    if percentages {
-                        if r > 100 {
-                            return Err(ProfileError::ParseFanCurvePercentOver100(r));
-                        }
-                        p = (p as f32 * 2.55).round() as u8;
-                    }
-                    if pwm_prev > p {
-                        return Err(ProfileError::ParseFanCurvePrevHigher(
-                            "percentage", pwm_prev, p,
-                        ));
-                    }
-                    pwm_prev = p;
-                    pwm[index] = p;
-                }
-            }
+        if points.len() > 8 {
+            return Err(ProfileError::TooManyPoints(points.len()));
+        }
+        let mut temp = [0u8; 8];
+        let mut pwm = [0u8; 8];
+        for (index, (t, p)) in points.into_iter().enumerate() {
+            temp[index] = t;
+            pwm[index] = p;
         }
         Ok(Self {
             fan: FanCurvePU::CPU,
@@ -131,11 +160,97 @@ impl std::str::FromStr for CurveData {
     }
 }
 
+/// Parse `30c:1%,49c:2%,...` to (temperature, pwm) pairs, any number of them.
+/// If '%' is used the fan power values are converted from percentages,
+/// otherwise the range is 0-255. Neither may fall from one point to the next.
+pub(crate) fn parse_points(input: &str) -> Result<Vec<(u8, u8)>, ProfileError> {
+    let mut points = Vec::new();
+    let mut temp_prev = 0;
+    let mut pwm_prev = 0;
+    let mut percentages = false;
+
+    for value in input.split(',') {
+        let mut point = (0, 0);
+        for (select, num) in value.splitn(2, ['c', ':']).enumerate() {
+            if num.contains('%') {
+                percentages = true;
+            }
+            let r = num.trim_matches(|c| c == 'c' || c == ':' || c == '%');
+            let r = r.parse::<u8>().map_err(ProfileError::ParseFanCurveDigit)?;
+
+            if select == 0 {
+                if temp_prev > r {
+                    return Err(ProfileError::ParseFanCurvePrevHigher(
+                        "temperature", temp_prev, r,
+                    ));
+                }
+                temp_prev = r;
+                point.0 = r;
+            } else {
+                let mut p = r;
+                if percentages {
+                    if r > 100 {
+                        return Err(ProfileError::ParseFanCurvePercentOver100(r));
+                    }
+                    p = (p as f32 * 2.55).round() as u8;
+                }
+                if pwm_prev > p {
+                    return Err(ProfileError::ParseFanCurvePrevHigher(
+                        "percentage", pwm_prev, p,
+                    ));
+                }
+                pwm_prev = p;
+                point.1 = p;
+            }
+        }
+        points.push(point);
+    }
+    Ok(points)
+}
+
 impl CurveData {
     pub fn set_fan(&mut self, fan: FanCurvePU) {
         self.fan = fan;
     }
 
+    /// Check the curve is safe to write. Temperatures and pwm must not fall
+    /// from one point to the next, temperatures must be in the firmware
+    /// range, and hot points must not be below the minimum duty. A curve that
+    /// ends below `hot_temp` holds its last pwm from there on, so that must
+    /// not be below the minimum duty either. The error names the first point
+    /// which failed.
+    pub fn validate(&self, limits: &CurveLimits) -> Result<(), ProfileError> {
+        let fail = |index, fault| Err(ProfileError::InvalidCurvePoint(self.fan, index, fault));
+        for (index, (&temp, &pwm)) in self.temp.iter().zip(self.pwm.iter()).enumerate() {
+            if temp < limits.min_temp || temp > limits.max_temp {
+                return fail(
+                    index,
+                    CurveFault::TempOutOfRange(temp, limits.min_temp, limits.max_temp),
+                );
+            }
+            if index > 0 {
+                let (prev_temp, prev_pwm) = (self.temp[index - 1], self.pwm[index - 1]);
+                if temp < prev_temp {
+                    return fail(index, CurveFault::TempFalling(prev_temp, temp));
+                }
+                if pwm < prev_pwm {
+                    return fail(index, CurveFault::PwmFalling(prev_pwm, pwm));
+                }
+            }
+            if temp >= limits.hot_temp && pwm < limits.min_hot_pwm {
+                return fail(index, CurveFault::DutyTooLow(pwm, limits.min_hot_pwm));
+            }
+        }
+        let last = self.temp.len() - 1;
+        if self.temp[last] < limits.hot_temp && self.pwm[last] < limits.min_hot_pwm {
+            return fail(
+                last,
+                CurveFault::DutyTooLow(self.pwm[last], limits.min_hot_pwm),
+            );
+        }
+        Ok(())
+    }
+
     fn set_val_from_attr(tmp: &str, device: &Device, buf: &mut [u8; 8]) {
         if let Some(n) = tmp.chars().nth(15) {
             let i = n.to_digit(10).unwrap() as usize;
@@ -146,8 +261,7 @@ impl CurveData {
     }
 
     pub fn read_from_device(&mut self, device: &Device) {
-        for attr in device.attributes() {
-            let tmp = attr.name().to_string_lossy();
+        for tmp in device.attribute_names() {
             let pwm_num: char = self.fan.into();
             let pwm = format!("pwm{pwm_num}");
             if tmp.starts_with(&pwm) && tmp.ends_with("_temp") {
@@ -178,7 +292,7 @@ impl CurveData {
 
         // Enable must be done *after* all points are written pwm3_enable
         device
-            .set_attribute_value(format!("pwm{pwm_num}_enable"), enable.to_string())
+            .set_attribute_value(&format!("pwm{pwm_num}_enable"), enable.to_string())
             .map_err(|e| error!("Failed to set pwm{pwm_num}_enable to {enable}: {e:?}"))
             .ok();
         Ok(())
@@ -213,6 +327,37 @@ mod tests {
         assert!(curve.is_err());
     }
 
+    #[test]
+    fn curve_data_from_str_too_many_points() {
+        let curve = CurveData::from_str(
+            "30c:1%,49c:2%,59c:3%,69c:4%,79c:31%,89c:49%,99c:56%,109c:58%,110c:60%",
+        );
+        assert!(matches!(curve, Err(ProfileError::TooManyPoints(9))));
+    }
+
+    #[test]
+    fn curve_limits_for_board() {
+        assert_eq!(CurveLimits::for_board("GA403UV"), THIN_CURVE_LIMITS);
+        assert_eq!(CurveLimits::for_board("gu605my"), THIN_CURVE_LIMITS);
+        assert_eq!(CurveLimits::for_board("G834JY"), CurveLimits::default());
+        assert_eq!(CurveLimits::for_board(""), CurveLimits::default());
+
+        // A curve which is fine for most models runs the fans too slow on a
+        // thin one
+        let curve =
+            CurveData::from_str("30c:1%,49c:2%,59c:3%,69c:4%,79c:31%,89c:49%,99c:56%,109c:58%")
+                .unwrap();
+        curve.validate(&CurveLimits::default()).unwrap();
+        assert!(matches!(
+            curve.validate(&CurveLimits::for_board("GA403UV")),
+            Err(ProfileError::InvalidCurvePoint(
+                _,
+                4,
+                CurveFault::DutyTooLow(79, 102)
+            ))
+        ));
+    }
+
     #[test]
     fn curve_data_from_str_simple() {
         let curve = CurveData::from_str("30:1,49:2,59:3,69:4,79:31,89:49,99:56,109:58").unwrap();
@@ -232,6 +377,82 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn curve_data_validate() {
+        let limits = CurveLimits::default();
+        let mut curve =
+            CurveData::from_str("30c:1%,49c:2%,59c:3%,69c:4%,79c:31%,89c:49%,99c:56%,109c:58%")
+                .unwrap();
+        curve.validate(&limits).unwrap();
+
+        curve.temp[3] = 55;
+        assert!(matches!(
+            curve.validate(&limits),
+            Err(ProfileError::InvalidCurvePoint(
+                FanCurvePU::CPU,
+                3,
+                CurveFault::TempFalling(59, 55)
+            ))
+        ));
+        curve.temp[3] = 69;
+
+        curve.pwm[6] = 100;
+        assert!(matches!(
+            curve.validate(&limits),
+            Err(ProfileError::InvalidCurvePoint(
+                _,
+                6,
+                CurveFault::PwmFalling(125, 100)
+            ))
+        ));
+        curve.pwm[6] = 143;
+
+        curve.temp[7] = 120;
+        assert!(matches!(
+            curve.validate(&limits),
+            Err(ProfileError::InvalidCurvePoint(
+                _,
+                7,
+                CurveFault::TempOutOfRange(120, 20, 110)
+            ))
+        ));
+        curve.temp[7] = 109;
+
+        // A flat low curve would let the laptop overheat
+        curve.pwm = [10; 8];
+        let err = curve.validate(&limits).unwrap_err();
+        assert!(matches!(
+            err,
+            ProfileError::InvalidCurvePoint(_, 5, CurveFault::DutyTooLow(10, 77))
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Invalid CPU fan curve point 6: pwm 10 is below the minimum of 77 for this temperature"
+        );
+    }
+
+    #[test]
+    fn curve_data_validate_ends_below_hot() {
+        let limits = CurveLimits::default();
+        // The firmware holds pwm 30 above 70c
+        let curve =
+            CurveData::from_str("30c:1%,35c:2%,40c:3%,45c:4%,50c:5%,60c:8%,65c:10%,70c:12%")
+                .unwrap();
+        assert!(matches!(
+            curve.validate(&limits),
+            Err(ProfileError::InvalidCurvePoint(
+                _,
+                7,
+                CurveFault::DutyTooLow(31, 77)
+            ))
+        ));
+
+        let curve =
+            CurveData::from_str("30c:1%,35c:2%,40c:3%,45c:4%,50c:5%,60c:8%,65c:10%,70c:40%")
+                .unwrap();
+        curve.validate(&limits).unwrap();
+    }
+
     #[test]
     fn check_pwm_str() {
         assert_eq!(pwm_str('1', 0), "pwm1_auto_point1_pwm");
@@ -1,12 +1,18 @@
+pub mod calibration;
+pub mod curve_points;
 pub mod error;
+pub mod export;
 pub mod fan_curve_set;
+pub mod software_curve;
+pub mod telemetry;
 
 use error::ProfileError;
 use fan_curve_set::CurveData;
 use log::debug;
 use rog_platform::platform::PlatformProfile;
+pub use rog_platform::sysfs::SysfsDevice as Device;
+use rog_platform::sysfs::SysfsRoot;
 use serde::{Deserialize, Serialize};
-pub use udev::Device;
 #[cfg(feature = "dbus")]
 use zbus::zvariant::Type;
 use zbus::zvariant::{OwnedValue, Value};
@@ -14,13 +20,10 @@ use zbus::zvariant::{OwnedValue, Value};
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Use udev system to find the fan curve path/node which is labelled with
-/// "asus_custom_fan_curve" in the kernel
+/// "asus_custom_fan_curve" in the kernel. Respects the active `SysfsRoot`.
 pub fn find_fan_curve_node() -> Result<Device, ProfileError> {
-    let mut enumerator = udev::Enumerator::new()?;
-    enumerator.match_subsystem("hwmon")?;
-
-    for device in enumerator.scan_devices()? {
-        if device.parent_with_subsystem("platform")?.is_some() {
+    for device in SysfsRoot::get().scan_subsystem("hwmon")? {
+        if device.has_parent_with_subsystem("platform")? {
             if let Some(name) = device.attribute_value("name") {
                 if name == "asus_custom_fan_curve" {
                     return Ok(device);
@@ -55,8 +58,7 @@ impl FanCurvePU {
             let pwm_num: char = fan.into();
             let pwm_enable = format!("pwm{pwm_num}_enable");
             debug!("Looking for {pwm_enable}");
-            for attr in device.attributes() {
-                let tmp = attr.name().to_string_lossy();
+            for tmp in device.attribute_names() {
                 if tmp.contains(&pwm_enable) {
                     debug!("Found {pwm_enable}");
                     fans.push(fan);
@@ -152,6 +154,63 @@ impl FanCurveProfiles {
         Ok(())
     }
 
+    /// Fans which have no curve stored for the profile, such as the MID fan in
+    /// a config from before it was supported
+    pub fn missing_fans(&self, profile: PlatformProfile, fans: &[FanCurvePU]) -> Vec<FanCurvePU> {
+        fans.iter()
+            .filter(|fan| self.get_fan_curve_for(&profile, **fan).is_none())
+            .copied()
+            .collect()
+    }
+
+    /// Reset each fan on the device to its default curve and add it to the
+    /// stored profile. The profile must be the active one as only the active
+    /// defaults can be read.
+    pub fn add_fan_defaults(
+        &mut self,
+        profile: PlatformProfile,
+        fans: &[FanCurvePU],
+        device: &mut Device,
+    ) -> Result<(), ProfileError> {
+        for fan in fans {
+            let pwm_num: char = (*fan).into();
+            device.set_attribute_value(&format!("pwm{pwm_num}_enable"), "3")?;
+            let mut curve = CurveData {
+                fan: *fan,
+                ..Default::default()
+            };
+            curve.read_from_device(device);
+            debug!("Adding default curve: {curve:?}");
+            self.curves_mut(profile).push(curve);
+        }
+        Ok(())
+    }
+
+    /// Copy every curve of `other` for a profile and fan this has no curve for
+    pub fn add_missing_from(&mut self, other: &FanCurveProfiles) {
+        for profile in [
+            PlatformProfile::Balanced,
+            PlatformProfile::Performance,
+            PlatformProfile::Quiet,
+            PlatformProfile::Custom,
+        ] {
+            for curve in other.get_fan_curves_for(profile) {
+                if self.get_fan_curve_for(&profile, curve.fan).is_none() {
+                    self.curves_mut(profile).push(curve.clone());
+                }
+            }
+        }
+    }
+
+    fn curves_mut(&mut self, profile: PlatformProfile) -> &mut Vec<CurveData> {
+        match profile {
+            PlatformProfile::Balanced => &mut self.balanced,
+            PlatformProfile::Performance => &mut self.performance,
+            PlatformProfile::Quiet | PlatformProfile::LowPower => &mut self.quiet,
+            PlatformProfile::Custom => &mut self.custom,
+        }
+    }
+
     /// Reset the stored (self) and device curve to the defaults of the
     /// platform.
     ///
@@ -351,3 +410,113 @@ impl FanCurveProfiles {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all};
+
+    use super::*;
+
+    #[test]
+    fn add_missing_fan_defaults() {
+        let dir = std::env::temp_dir().join(format!("rog-fan-migrate-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let mut device = Device::Path(dir.clone());
+        let mid = CurveData {
+            fan: FanCurvePU::MID,
+            pwm: [
+                10, 20, 30, 40, 50, 60, 70, 80,
+            ],
+            temp: [
+                30, 40, 50, 60, 70, 80, 90, 100,
+            ],
+            enabled: false,
+        };
+        mid.write_to_device(&mut device).unwrap();
+
+        let fans = [
+            FanCurvePU::CPU,
+            FanCurvePU::GPU,
+            FanCurvePU::MID,
+        ];
+        let mut profiles = FanCurveProfiles {
+            balanced: vec![
+                CurveData {
+                    fan: FanCurvePU::CPU,
+                    ..Default::default()
+                },
+                CurveData {
+                    fan: FanCurvePU::GPU,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let missing = profiles.missing_fans(PlatformProfile::Balanced, &fans);
+        assert_eq!(missing, vec![FanCurvePU::MID]);
+
+        profiles
+            .add_fan_defaults(PlatformProfile::Balanced, &missing, &mut device)
+            .unwrap();
+        assert_eq!(read_to_string(dir.join("pwm3_enable")).unwrap(), "3");
+        let curve = profiles
+            .get_fan_curve_for(&PlatformProfile::Balanced, FanCurvePU::MID)
+            .unwrap();
+        assert_eq!(curve.pwm, mid.pwm);
+        assert_eq!(curve.temp, mid.temp);
+        assert!(profiles
+            .missing_fans(PlatformProfile::Balanced, &fans)
+            .is_empty());
+
+        remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn add_missing_from_keeps_existing_curves() {
+        let curve = |fan, pwm| CurveData {
+            fan,
+            pwm: [pwm; 8],
+            ..Default::default()
+        };
+        let ac = FanCurveProfiles {
+            balanced: vec![
+                curve(FanCurvePU::CPU, 10),
+                curve(FanCurvePU::GPU, 20),
+                curve(FanCurvePU::MID, 30),
+            ],
+            quiet: vec![
+                curve(FanCurvePU::CPU, 40),
+                curve(FanCurvePU::GPU, 40),
+            ],
+            ..Default::default()
+        };
+        let mut battery = FanCurveProfiles {
+            balanced: vec![
+                CurveData {
+                    enabled: true,
+                    ..curve(FanCurvePU::CPU, 50)
+                },
+            ],
+            ..Default::default()
+        };
+        battery.add_missing_from(&ac);
+
+        // Only the curves the battery set has no curve for are copied
+        assert_eq!(battery.balanced.len(), 3);
+        let cpu = battery
+            .get_fan_curve_for(&PlatformProfile::Balanced, FanCurvePU::CPU)
+            .unwrap();
+        assert!(cpu.enabled);
+        assert_eq!(cpu.pwm, [50; 8]);
+        let mid = battery
+            .get_fan_curve_for(&PlatformProfile::Balanced, FanCurvePU::MID)
+            .unwrap();
+        assert_eq!(mid.pwm, [30; 8]);
+        assert_eq!(battery.quiet.len(), 2);
+        assert!(battery.performance.is_empty());
+
+        // Nothing changes a second time
+        battery.add_missing_from(&ac);
+        assert_eq!(battery.balanced.len(), 3);
+    }
+}
@@ -0,0 +1,361 @@
+//! Fan curves applied by asusd itself instead of the firmware. The fan is put
+//! in manual mode and `pwmN` is written from a curve driven by any hwmon
+//! temperature inputs, e.g the GPU temperature driving the CPU fan.
+
+use log::debug;
+use rog_platform::sysfs::SysfsRoot;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "dbus")]
+use zbus::zvariant::Type;
+
+use crate::error::{CurveFault, ProfileError};
+use crate::fan_curve_set::CurveLimits;
+use crate::{Device, FanCurvePU};
+
+/// The hwmon device of `asus-wmi` which has the manual `pwmN` controls
+pub const PWM_NODE_NAME: &str = "asus";
+/// `pwmN_enable` value for the fan being driven by `pwmN`
+pub const PWM_ENABLE_MANUAL: &str = "1";
+/// `pwmN_enable` value for the fan being driven by the firmware
+pub const PWM_ENABLE_AUTO: &str = "2";
+
+/// Find the hwmon device which can set `pwmN` for the fan
+pub fn find_pwm_node(fan: FanCurvePU) -> Result<Device, ProfileError> {
+    let pwm_num: char = fan.into();
+    let pwm = format!("pwm{pwm_num}");
+    for device in SysfsRoot::get().scan_subsystem("hwmon")? {
+        if device
+            .attribute_value("name")
+            .is_some_and(|n| n == PWM_NODE_NAME)
+            && device.has_attribute(&pwm)
+            && device.has_attribute(&format!("{pwm}_enable"))
+        {
+            return Ok(device);
+        }
+    }
+    Err(ProfileError::NotFound(format!(
+        "{PWM_NODE_NAME} hwmon {pwm}"
+    )))
+}
+
+/// Set the fan to manual or firmware control, one of `PWM_ENABLE_MANUAL` or
+/// `PWM_ENABLE_AUTO`
+pub fn set_pwm_enable(
+    device: &mut Device,
+    fan: FanCurvePU,
+    mode: &str,
+) -> Result<(), ProfileError> {
+    let pwm_num: char = fan.into();
+    let attr = format!("pwm{pwm_num}_enable");
+    device
+        .set_attribute_value(&attr, mode)
+        .map_err(|e| ProfileError::Write(attr, e))
+}
+
+/// Write the fan duty, 0-255. The fan must be in manual mode.
+pub fn set_pwm(device: &mut Device, fan: FanCurvePU, pwm: u8) -> Result<(), ProfileError> {
+    let pwm_num: char = fan.into();
+    let attr = format!("pwm{pwm_num}");
+    device
+        .set_attribute_value(&attr, pwm.to_string())
+        .map_err(|e| ProfileError::Write(attr, e))
+}
+
+/// A temperature input of a hwmon device, found by the hwmon `name` as the
+/// `hwmonN` numbering is not stable across boots
+#[cfg_attr(feature = "dbus", derive(Type))]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TempSource {
+    /// The hwmon `name`, such as `k10temp`, `coretemp` or `amdgpu`
+    pub hwmon: String,
+    /// The attribute to read, such as `temp1_input`
+    pub input: String,
+}
+
+impl TempSource {
+    pub fn new(hwmon: &str, input: &str) -> Self {
+        Self {
+            hwmon: hwmon.to_owned(),
+            input: input.to_owned(),
+        }
+    }
+
+    /// Find the hwmon device this input belongs to. A dGPU which is powered
+    /// off will not have one.
+    pub fn find(&self) -> Result<Device, ProfileError> {
+        self.find_in(&SysfsRoot::get())
+    }
+
+    /// As `find()`, in the given root
+    pub fn find_in(&self, root: &SysfsRoot) -> Result<Device, ProfileError> {
+        for device in root.scan_subsystem("hwmon")? {
+            if device
+                .attribute_value("name")
+                .is_some_and(|n| n == self.hwmon.as_str())
+                && device.has_attribute(&self.input)
+            {
+                return Ok(device);
+            }
+        }
+        Err(ProfileError::NotFound(format!(
+            "hwmon {} {}",
+            self.hwmon, self.input
+        )))
+    }
+
+    /// Read the temperature in degrees C from the device found by `find()`
+    pub fn read(&self, device: &Device) -> Result<f32, ProfileError> {
+        let value = device
+            .attribute_value(&self.input)
+            .ok_or_else(|| ProfileError::NotFound(self.input.clone()))?;
+        let millidegrees: i32 = value
+            .to_string_lossy()
+            .trim()
+            .parse()
+            .map_err(ProfileError::ParseFanCurveDigit)?;
+        Ok(millidegrees as f32 / 1000.0)
+    }
+}
+
+/// A curve for one fan, run by asusd rather than the firmware
+#[cfg_attr(feature = "dbus", derive(Type))]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SoftwareCurve {
+    pub fan: FanCurvePU,
+    /// The hottest of these drives the fan
+    pub sources: Vec<TempSource>,
+    /// (temperature C, pwm 0-255) in order of temperature. Between points the
+    /// pwm is interpolated.
+    pub points: Vec<(u8, u8)>,
+    /// Degrees C the temperature must fall by before the fan slows down
+    pub hysteresis: u8,
+    /// Maximum pwm increase per second, 0 for no limit
+    pub ramp_up: u8,
+    /// Maximum pwm decrease per second, 0 for no limit
+    pub ramp_down: u8,
+}
+
+impl SoftwareCurve {
+    /// Check the curve can be run. The points must rise in temperature and
+    /// there must be at least one source.
+    pub fn check(&self) -> Result<(), ProfileError> {
+        if self.points.len() < 2 {
+            return Err(ProfileError::NotEnoughPoints);
+        }
+        if self.sources.is_empty() {
+            return Err(ProfileError::NotFound("temperature source".to_owned()));
+        }
+        for pair in self.points.windows(2) {
+            if pair[0].0 >= pair[1].0 {
+                return Err(ProfileError::ParseFanCurvePrevHigher(
+                    "temperature", pair[0].0, pair[1].0,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the curve can be run and keeps the fan within the same limits as
+    /// the firmware curves
+    pub fn validate(&self, limits: &CurveLimits) -> Result<(), ProfileError> {
+        self.check()?;
+        let fail = |index, fault| Err(ProfileError::InvalidCurvePoint(self.fan, index, fault));
+        for (index, &(temp, pwm)) in self.points.iter().enumerate() {
+            if temp < limits.min_temp || temp > limits.max_temp {
+                return fail(
+                    index,
+                    CurveFault::TempOutOfRange(temp, limits.min_temp, limits.max_temp),
+                );
+            }
+            if index > 0 && pwm < self.points[index - 1].1 {
+                return fail(index, CurveFault::PwmFalling(self.points[index - 1].1, pwm));
+            }
+        }
+        // The curve is held at the last point, so it must reach the hot duty
+        let hot = self.pwm_at(limits.hot_temp as f32);
+        if hot < limits.min_hot_pwm {
+            let index = self
+                .points
+                .iter()
+                .position(|&(temp, _)| temp >= limits.hot_temp)
+                .unwrap_or(self.points.len() - 1);
+            return fail(index, CurveFault::DutyTooLow(hot, limits.min_hot_pwm));
+        }
+        Ok(())
+    }
+
+    /// The pwm for a temperature, held at the first and last points outside
+    /// of the curve
+    pub fn pwm_at(&self, temp: f32) -> u8 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 0;
+        };
+        if temp <= first.0 as f32 {
+            return first.1;
+        }
+        for pair in self.points.windows(2) {
+            let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+            if temp <= t1 as f32 {
+                let span = (t1 as f32 - t0 as f32).max(1.0);
+                let amount = (temp - t0 as f32) / span;
+                return (p0 as f32 + (p1 as f32 - p0 as f32) * amount).round() as u8;
+            }
+        }
+        last.1
+    }
+}
+
+/// The state of a running `SoftwareCurve`
+#[derive(Debug, Default, Clone)]
+pub struct SoftwareCurveState {
+    /// The temperature the pwm was last set for
+    temp: Option<f32>,
+    pwm: Option<f32>,
+}
+
+impl SoftwareCurveState {
+    /// Forget the previous pwm, the next step sets the curve pwm without ramp
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Advance by `elapsed` seconds with a new temperature reading and return
+    /// the pwm to write
+    pub fn step(&mut self, curve: &SoftwareCurve, temp: f32, elapsed: f32) -> u8 {
+        // Hold the fan speed until the temperature has fallen far enough
+        let temp = match self.temp {
+            Some(last) if temp < last && last - temp < curve.hysteresis as f32 => last,
+            _ => temp,
+        };
+        self.temp = Some(temp);
+
+        let target = curve.pwm_at(temp) as f32;
+        let pwm = match self.pwm {
+            Some(current) if target > current && curve.ramp_up > 0 => {
+                (current + curve.ramp_up as f32 * elapsed).min(target)
+            }
+            Some(current) if target < current && curve.ramp_down > 0 => {
+                (current - curve.ramp_down as f32 * elapsed).max(target)
+            }
+            _ => target,
+        };
+        debug!("{:?} at {temp}C, pwm {pwm}", curve.fan);
+        self.pwm = Some(pwm);
+        pwm.round() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    fn curve() -> SoftwareCurve {
+        SoftwareCurve {
+            fan: FanCurvePU::CPU,
+            sources: vec![TempSource::new("amdgpu", "temp1_input")],
+            points: vec![
+                (40, 50),
+                (60, 100),
+                (80, 200),
+            ],
+            hysteresis: 5,
+            ramp_up: 20,
+            ramp_down: 10,
+        }
+    }
+
+    #[test]
+    fn software_curve_interpolates() {
+        let curve = curve();
+        curve.check().unwrap();
+        assert_eq!(curve.pwm_at(20.0), 50);
+        assert_eq!(curve.pwm_at(50.0), 75);
+        assert_eq!(curve.pwm_at(70.0), 150);
+        assert_eq!(curve.pwm_at(95.0), 200);
+
+        let mut bad = curve.clone();
+        bad.points.swap(0, 1);
+        assert!(matches!(
+            bad.check(),
+            Err(ProfileError::ParseFanCurvePrevHigher(_, 60, 40))
+        ));
+    }
+
+    #[test]
+    fn software_curve_validate() {
+        let limits = CurveLimits::default();
+        let mut curve = curve();
+        curve.validate(&limits).unwrap();
+
+        curve.points[0].0 = 10;
+        assert!(matches!(
+            curve.validate(&limits),
+            Err(ProfileError::InvalidCurvePoint(
+                FanCurvePU::CPU,
+                0,
+                CurveFault::TempOutOfRange(10, 20, 110)
+            ))
+        ));
+        curve.points[0].0 = 40;
+
+        curve.points[2].1 = 90;
+        assert!(matches!(
+            curve.validate(&limits),
+            Err(ProfileError::InvalidCurvePoint(
+                _,
+                2,
+                CurveFault::PwmFalling(100, 90)
+            ))
+        ));
+
+        // Ending below the hot temperature still holds the fan at the last pwm
+        curve.points = vec![
+            (40, 20),
+            (60, 40),
+        ];
+        assert!(matches!(
+            curve.validate(&limits),
+            Err(ProfileError::InvalidCurvePoint(
+                _,
+                1,
+                CurveFault::DutyTooLow(40, 77)
+            ))
+        ));
+        curve.points[1].1 = 80;
+        curve.validate(&limits).unwrap();
+    }
+
+    #[test]
+    fn software_curve_hysteresis_and_ramp() {
+        let curve = curve();
+        let mut state = SoftwareCurveState::default();
+        assert_eq!(state.step(&curve, 60.0, 1.0), 100);
+        // Ramps up at 20 per second
+        assert_eq!(state.step(&curve, 80.0, 1.0), 120);
+        assert_eq!(state.step(&curve, 80.0, 2.0), 160);
+        // Within the hysteresis the fan keeps going up to the 80C pwm
+        assert_eq!(state.step(&curve, 77.0, 2.0), 200);
+        assert_eq!(state.step(&curve, 76.0, 1.0), 200);
+        // Then ramps down at 10 per second
+        assert_eq!(state.step(&curve, 70.0, 1.0), 190);
+        state.reset();
+        assert_eq!(state.step(&curve, 70.0, 1.0), 150);
+    }
+
+    #[test]
+    fn temp_source_read() {
+        let dir = std::env::temp_dir().join(format!("rog-temp-source-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("temp1_input"), "65500\n").unwrap();
+
+        let source = TempSource::new("amdgpu", "temp1_input");
+        let device = Device::Path(dir.clone());
+        assert_eq!(source.read(&device).unwrap(), 65.5);
+        let missing = TempSource::new("amdgpu", "temp2_input");
+        assert!(missing.read(&device).is_err());
+
+        remove_dir_all(&dir).ok();
+    }
+}
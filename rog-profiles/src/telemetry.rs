@@ -0,0 +1,220 @@
+//! What the fans are doing: RPM and duty from the `asus` hwmon device, and the
+//! temperature driving each fan.
+
+use std::path::PathBuf;
+
+use rog_platform::sysfs::SysfsRoot;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "dbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+use crate::error::ProfileError;
+use crate::software_curve::{TempSource, PWM_NODE_NAME};
+use crate::{Device, FanCurvePU};
+
+/// One sample of a fan
+#[cfg_attr(feature = "dbus", derive(Type, Value, OwnedValue))]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct FanTelemetry {
+    pub fan: FanCurvePU,
+    /// `fanN_input`, 0 if not available
+    pub rpm: u32,
+    /// `pwmN`, 0 if not available
+    pub pwm: u8,
+    /// The hottest of the temperatures driving the fan in degrees C, 0 if none
+    /// could be read
+    pub temp: f64,
+}
+
+impl FanTelemetry {
+    /// Read the fan from the `asus` hwmon device, with the temperature driving
+    /// it
+    pub fn read(fan: FanCurvePU, device: &Device, temp: f64) -> Self {
+        let fan_num: char = fan.into();
+        let read = |attr: String| {
+            device
+                .attribute_value(&attr)
+                .and_then(|v| v.to_string_lossy().trim().parse::<u32>().ok())
+                .unwrap_or_default()
+        };
+        Self {
+            fan,
+            rpm: read(format!("fan{fan_num}_input")),
+            pwm: read(format!("pwm{fan_num}")).min(u8::MAX as u32) as u8,
+            temp,
+        }
+    }
+}
+
+/// The hwmon devices telemetry is read from, kept by path as a udev device
+/// caches the values read. Each is found on first use and found again only
+/// after reading it fails, as a device such as a dGPU can go away and come
+/// back as another `hwmonN`.
+#[derive(Debug, Default, Clone)]
+pub struct TelemetryNodes {
+    /// The active root when created
+    root: SysfsRoot,
+    fans: Option<PathBuf>,
+    temps: Vec<(TempSource, PathBuf)>,
+}
+
+impl TelemetryNodes {
+    /// Find the devices in `root` instead of the active root
+    pub fn with_root(root: SysfsRoot) -> Self {
+        Self {
+            root,
+            ..Default::default()
+        }
+    }
+
+    /// Sample a fan and the hottest of the temperatures driving it
+    pub fn read(&mut self, fan: FanCurvePU, sources: &[TempSource]) -> FanTelemetry {
+        let temp = sources
+            .iter()
+            .filter_map(|s| self.read_temp(s))
+            .fold(None, |hottest: Option<f32>, t| {
+                Some(hottest.map_or(t, |h| h.max(t)))
+            })
+            .unwrap_or_default() as f64;
+        if self
+            .fans
+            .as_ref()
+            .is_some_and(|path| !Device::Path(path.clone()).has_attribute("name"))
+        {
+            self.fans = None;
+        }
+        if self.fans.is_none() {
+            self.fans = find_fan_node_in(&self.root)
+                .ok()
+                .map(|d| d.syspath().to_owned());
+        }
+        match &self.fans {
+            Some(path) => FanTelemetry::read(fan, &Device::Path(path.clone()), temp),
+            None => FanTelemetry {
+                fan,
+                temp,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn read_temp(&mut self, source: &TempSource) -> Option<f32> {
+        let path = match self.temps.iter().find(|(s, _)| s == source) {
+            Some((_, path)) => path.clone(),
+            None => {
+                let path = source.find_in(&self.root).ok()?.syspath().to_owned();
+                self.temps.push((source.clone(), path.clone()));
+                path
+            }
+        };
+        let temp = source.read(&Device::Path(path)).ok();
+        if temp.is_none() {
+            self.temps.retain(|(s, _)| s != source);
+        }
+        temp
+    }
+}
+
+/// Find the `asus` hwmon device with the fan inputs
+pub fn find_fan_node() -> Result<Device, ProfileError> {
+    find_fan_node_in(&SysfsRoot::get())
+}
+
+/// As `find_fan_node()`, in the given root
+pub fn find_fan_node_in(root: &SysfsRoot) -> Result<Device, ProfileError> {
+    for device in root.scan_subsystem("hwmon")? {
+        if device
+            .attribute_value("name")
+            .is_some_and(|n| n == PWM_NODE_NAME)
+        {
+            return Ok(device);
+        }
+    }
+    Err(ProfileError::NotFound(format!("{PWM_NODE_NAME} hwmon")))
+}
+
+impl TempSource {
+    /// The temperatures that usually drive a fan if none are chosen. CPU and
+    /// MID fans follow the CPU package, the GPU fan follows an AMD dGPU, an
+    /// Nvidia dGPU has no hwmon device.
+    pub fn defaults_for(fan: FanCurvePU) -> Vec<Self> {
+        match fan {
+            FanCurvePU::CPU | FanCurvePU::MID => vec![
+                Self::new("k10temp", "temp1_input"),
+                Self::new("coretemp", "temp1_input"),
+            ],
+            FanCurvePU::GPU => vec![Self::new("amdgpu", "temp1_input")],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    #[test]
+    fn fan_telemetry_read() {
+        let dir = std::env::temp_dir().join(format!("rog-fan-telemetry-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("fan2_input"), "3100\n").unwrap();
+        write(dir.join("pwm2"), "128\n").unwrap();
+
+        let device = Device::Path(dir.clone());
+        let telemetry = FanTelemetry::read(FanCurvePU::GPU, &device, 0.0);
+        assert_eq!(telemetry.rpm, 3100);
+        assert_eq!(telemetry.pwm, 128);
+        assert_eq!(telemetry.temp, 0.0);
+        // Missing attributes read as zero
+        let telemetry = FanTelemetry::read(FanCurvePU::MID, &device, 0.0);
+        assert_eq!(telemetry.rpm, 0);
+
+        remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn telemetry_nodes_rescan_on_error() {
+        let root = std::env::temp_dir().join(format!("rog-telemetry-nodes-{}", std::process::id()));
+        let hwmon = |name: &str, node: &str, attrs: &[(&str, &str)]| {
+            let dir = root.join("class/hwmon").join(node);
+            create_dir_all(&dir).unwrap();
+            write(dir.join("name"), name).unwrap();
+            for (attr, value) in attrs {
+                write(dir.join(attr), value).unwrap();
+            }
+        };
+        hwmon("asus", "hwmon3", &[
+            ("fan1_input", "2500"),
+            ("pwm1", "90"),
+        ]);
+        hwmon("amdgpu", "hwmon5", &[("temp1_input", "61000")]);
+
+        let sources = [TempSource::new("amdgpu", "temp1_input")];
+        let mut nodes = TelemetryNodes::with_root(SysfsRoot::new(&root));
+        let telemetry = nodes.read(FanCurvePU::CPU, &sources);
+        assert_eq!((telemetry.rpm, telemetry.pwm), (2500, 90));
+        assert_eq!(telemetry.temp, 61.0);
+
+        // Found devices are kept, a new one isn't seen until they fail
+        hwmon("asus", "hwmon0", &[
+            ("fan1_input", "4000"),
+            ("pwm1", "200"),
+        ]);
+        hwmon("amdgpu", "hwmon1", &[("temp1_input", "70000")]);
+        let telemetry = nodes.read(FanCurvePU::CPU, &sources);
+        assert_eq!(telemetry.rpm, 2500);
+        assert_eq!(telemetry.temp, 61.0);
+
+        remove_dir_all(root.join("class/hwmon/hwmon3")).unwrap();
+        remove_dir_all(root.join("class/hwmon/hwmon5")).unwrap();
+        // The failed temperature is found again on the next read
+        let telemetry = nodes.read(FanCurvePU::CPU, &sources);
+        assert_eq!(telemetry.rpm, 4000);
+        assert_eq!(telemetry.temp, 0.0);
+        let telemetry = nodes.read(FanCurvePU::CPU, &sources);
+        assert_eq!(telemetry.temp, 70.0);
+
+        remove_dir_all(&root).ok();
+    }
+}
@@ -111,6 +111,31 @@ impl FromStr for SlashMode {
     }
 }
 
+impl TryFrom<u8> for SlashMode {
+    type Error = SlashError;
+
+    fn try_from(value: u8) -> Result<Self, SlashError> {
+        Ok(match value {
+            0x10 => SlashMode::Bounce,
+            0x12 => SlashMode::Slash,
+            0x13 => SlashMode::Loading,
+            0x1d => SlashMode::BitStream,
+            0x1a => SlashMode::Transmission,
+            0x19 => SlashMode::Flow,
+            0x25 => SlashMode::Flux,
+            0x24 => SlashMode::Phantom,
+            0x26 => SlashMode::Spectrum,
+            0x32 => SlashMode::Hazard,
+            0x33 => SlashMode::Interfacing,
+            0x34 => SlashMode::Ramp,
+            0x42 => SlashMode::GameOver,
+            0x43 => SlashMode::Start,
+            0x44 => SlashMode::Buzzer,
+            _ => return Err(SlashError::ParseError(format!("mode {value:#04x}"))),
+        })
+    }
+}
+
 impl Display for SlashMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match &self {
@@ -7,10 +7,12 @@
 //! display the data from step 2
 //!
 //! Step 1 needs to be applied only on fresh system boot.
+//!
+//! `SlashReport::decode()` does the reverse, turning any packet written by
+//! the functions here back in to what it asks of the device.
 
 use dmi_id::DMIID;
 
-#[cfg(feature = "dbus")]
 use crate::error::SlashError;
 use crate::{SlashMode, SlashType};
 
@@ -206,3 +208,153 @@ pub const fn slash_pkt_lid_closed(slash_type: SlashType, enabled: bool) -> [u8;
         typ, 0xd8, 0x00, 0x00, 0x02, 0xa5, status,
     ]
 }
+
+/// A packet sent to the Slash device, decoded by `SlashReport::decode()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlashReport {
+    /// The first packet of `slash_pkt_init()`
+    Init1,
+    /// The second packet of `slash_pkt_init()`
+    Init2,
+    Enable(bool),
+    Save,
+    /// The first packet of `slash_pkt_set_mode()`
+    ModeSelect,
+    Mode(SlashMode),
+    Options {
+        enabled: bool,
+        brightness: u8,
+        interval: u8,
+    },
+    ShowOnBoot(bool),
+    ShowOnSleep(bool),
+    ShowOnLowBattery(bool),
+    ShowOnShutdown(bool),
+    ShowOnBatterySaver(bool),
+    ShowOnLidClosed(bool),
+}
+
+impl SlashReport {
+    /// Decode a packet as written to the device. The report ID must be the
+    /// one used by `slash_type`.
+    pub fn decode(slash_type: SlashType, report: &[u8]) -> Result<Self, SlashError> {
+        let err = || SlashError::ParseError(format!("Slash packet {report:02x?}"));
+        let Some((&id, pkt)) = report.split_first() else {
+            return Err(err());
+        };
+        if id != report_id(slash_type) {
+            return Err(err());
+        }
+
+        Ok(match pkt {
+            [0xd7, 0x00, 0x00, 0x01, 0xac, ..] => Self::Init1,
+            [0xd2, 0x02, 0x01, 0x08, 0xab, ..] => Self::Init2,
+            [0xd2, 0x03, 0x00, 0x0c, ..] => Self::ModeSelect,
+            [0xd3, 0x04, 0x00, 0x0c, 0x01, mode, ..] => Self::Mode(SlashMode::try_from(*mode)?),
+            [0xd3, 0x03, 0x01, 0x08, 0xab, _, _, status, _, brightness, _, interval, ..] => {
+                Self::Options {
+                    enabled: *status != 0,
+                    brightness: *brightness,
+                    interval: *interval,
+                }
+            }
+            [0xd3, 0x03, 0x01, 0x08, typ, _, _, status, ..] => match typ {
+                0xa0 => Self::ShowOnBoot(*status != 0),
+                0xa1 => Self::ShowOnSleep(*status == 0),
+                0xa2 => Self::ShowOnLowBattery(*status != 0),
+                0xa4 => Self::ShowOnShutdown(*status != 0),
+                _ => return Err(err()),
+            },
+            [0xd4, 0x00, ..] => Self::Save,
+            [0xd8, 0x02, _, 0x01, status, ..] => Self::Enable(*status == 0x00),
+            [0xd8, 0x01, _, 0x01, status, ..] => Self::ShowOnBatterySaver(*status == 0x00),
+            [0xd8, 0x00, _, 0x02, 0xa5, status, ..] => Self::ShowOnLidClosed(*status == 0x00),
+            _ => return Err(err()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_round_trip() {
+        for slash_type in [
+            SlashType::GA403,
+            SlashType::GA605,
+            SlashType::GU605,
+        ] {
+            let decode = |pkt: &[u8]| SlashReport::decode(slash_type, pkt).unwrap();
+            let [init1, init2] = slash_pkt_init(slash_type);
+            assert_eq!(decode(&init1), SlashReport::Init1);
+            assert_eq!(decode(&init2), SlashReport::Init2);
+            assert_eq!(decode(&slash_pkt_save(slash_type)), SlashReport::Save);
+
+            for mode in SlashMode::list() {
+                let mode = mode.parse().unwrap();
+                let [select, set] = slash_pkt_set_mode(slash_type, mode);
+                assert_eq!(decode(&select), SlashReport::ModeSelect);
+                assert_eq!(decode(&set), SlashReport::Mode(mode));
+            }
+
+            for enabled in [
+                true, false,
+            ] {
+                assert_eq!(
+                    decode(&slash_pkt_enable(slash_type, enabled)),
+                    SlashReport::Enable(enabled)
+                );
+                assert_eq!(
+                    decode(&slash_pkt_options(slash_type, enabled, 136, 3)),
+                    SlashReport::Options {
+                        enabled,
+                        brightness: 136,
+                        interval: 3
+                    }
+                );
+                assert_eq!(
+                    decode(&slash_pkt_boot(slash_type, enabled)),
+                    SlashReport::ShowOnBoot(enabled)
+                );
+                assert_eq!(
+                    decode(&slash_pkt_sleep(slash_type, enabled)),
+                    SlashReport::ShowOnSleep(enabled)
+                );
+                assert_eq!(
+                    decode(&slash_pkt_low_battery(slash_type, enabled)),
+                    SlashReport::ShowOnLowBattery(enabled)
+                );
+                assert_eq!(
+                    decode(&slash_pkt_shutdown(slash_type, enabled)),
+                    SlashReport::ShowOnShutdown(enabled)
+                );
+                assert_eq!(
+                    decode(&slash_pkt_battery_saver(slash_type, enabled)),
+                    SlashReport::ShowOnBatterySaver(enabled)
+                );
+                assert_eq!(
+                    decode(&slash_pkt_lid_closed(slash_type, enabled)),
+                    SlashReport::ShowOnLidClosed(enabled)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decode_invalid() {
+        let save = slash_pkt_save(SlashType::GA403);
+        // Wrong report ID for the device
+        assert!(SlashReport::decode(SlashType::GA605, &save).is_err());
+        assert!(SlashReport::decode(SlashType::GA403, &save[..1]).is_err());
+        assert!(SlashReport::decode(SlashType::GA403, &[0x5e, 0xd7]).is_err());
+
+        let [_, mut set] = slash_pkt_set_mode(SlashType::GA403, SlashMode::Flow);
+        set[6] = 0xff;
+        assert!(SlashReport::decode(SlashType::GA403, &set).is_err());
+
+        let mut boot = slash_pkt_boot(SlashType::GA403, true);
+        boot[5] = 0xa3;
+        assert!(SlashReport::decode(SlashType::GA403, &boot).is_err());
+    }
+}
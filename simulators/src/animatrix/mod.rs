@@ -31,6 +31,14 @@ pub struct LedShape {
     pub horizontal: i32,
 }
 
+/// A single LED as placed on the display, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Led {
+    pub x: i32,
+    pub y: i32,
+    pub brightness: u8,
+}
+
 pub struct AniMatrix {
     rows: Vec<Row>,
     led_shape: LedShape,
@@ -63,11 +71,66 @@ impl AniMatrix {
         Self { rows, led_shape }
     }
 
-    pub fn rows(&self) -> &[Row] {
-        &self.rows
+    /// The size in pixels of each drawn LED
+    pub fn led_size(&self) -> (i32, i32) {
+        (self.led_shape.horizontal * 6, self.led_shape.vertical * 6)
+    }
+
+    /// Place each LED from a single data pane. The 4th byte of the pane is the
+    /// index which selects the rows it contains.
+    pub fn pane_leds(&self, pane: &[u8]) -> Vec<Led> {
+        let mut leds = Vec::new();
+        let Some(&index) = pane.get(3) else {
+            return leds;
+        };
+        let (w, h) = self.led_size();
+        let mut y_offset = 0;
+        for (y_count, row) in self.rows.iter().enumerate() {
+            if row.0 == index {
+                let start = row.1;
+                let end = start + row.2;
+                if row.1 < 10 && row.2 < 15 {
+                    if index == 0x74 {
+                        y_offset = 1;
+                    } else if index == 0xe7 {
+                        y_offset = 2;
+                    }
+                }
+                let Some(data) = pane.get(start..=end) else {
+                    continue;
+                };
+                for (x_count, b) in data.iter().enumerate() {
+                    let x: i32 = w + x_count as i32 * w
+                        - if (y_count + y_offset as usize) % 2 != 0 {
+                            0
+                        } else {
+                            w / 2
+                        }
+                        + row.3 * w;
+                    let y = y_count as i32 * h - y_offset * h;
+                    leds.push(Led {
+                        x,
+                        y,
+                        brightness: *b,
+                    });
+                }
+            }
+        }
+        leds
     }
 
-    pub fn led_shape(&self) -> LedShape {
-        self.led_shape
+    /// The size in pixels required to draw every LED
+    pub fn dimensions(&self) -> (u32, u32) {
+        let (w, h) = self.led_size();
+        let mut pane = [0u8; 640];
+        let (mut width, mut height) = (0, 0);
+        for row in &self.rows {
+            pane[3] = row.0;
+            for led in self.pane_leds(&pane) {
+                width = width.max(led.x + w);
+                height = height.max(led.y + h);
+            }
+        }
+        (width as u32, height as u32)
     }
 }
@@ -0,0 +1,185 @@
+//! Recording of the AniMe display without a window, for use in CI or for
+//! snapshotting what `asusd` and `asusd-user` send to the device.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rog_anime::usb::pkt_flush;
+use rog_anime::AnimeType;
+
+use crate::animatrix::AniMatrix;
+
+/// A complete display frame, greyscale with one byte per pixel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Time since the recording started
+    pub timestamp: Duration,
+    pub pixels: Vec<u8>,
+}
+
+/// Reassembles the data panes written to the device in to frames. A frame is
+/// complete when the flush packet is written.
+pub struct FrameRecorder {
+    animatrix: AniMatrix,
+    width: u32,
+    height: u32,
+    canvas: Vec<u8>,
+    frames: Vec<Frame>,
+}
+
+impl FrameRecorder {
+    pub fn new(model: AnimeType) -> Self {
+        let animatrix = AniMatrix::new(model);
+        let (width, height) = animatrix.dimensions();
+        Self {
+            animatrix,
+            width,
+            height,
+            canvas: vec![0; (width * height) as usize],
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Feed a report written to the device. Returns the frame if this report
+    /// completed one.
+    pub fn write(&mut self, report: &[u8], timestamp: Duration) -> Option<&Frame> {
+        let flush = pkt_flush();
+        if report.starts_with(&flush[..3]) {
+            self.frames.push(Frame {
+                timestamp,
+                pixels: self.canvas.clone(),
+            });
+            return self.frames.last();
+        }
+        // Data panes share the flush header bar the last byte
+        if report.starts_with(&flush[..2]) && report.get(2) == Some(&0x02) {
+            self.draw_pane(report);
+        }
+        None
+    }
+
+    fn draw_pane(&mut self, pane: &[u8]) {
+        let (w, h) = self.animatrix.led_size();
+        for led in self.animatrix.pane_leds(pane) {
+            for y in led.y.max(0)..(led.y + h).min(self.height as i32) {
+                let row = y as usize * self.width as usize;
+                for x in led.x.max(0)..(led.x + w).min(self.width as i32) {
+                    self.canvas[row + x as usize] = led.brightness;
+                }
+            }
+        }
+    }
+
+    /// Write a frame to `dir` as a PNG, the file name holds the frame number
+    /// and timestamp in milliseconds
+    pub fn write_png(
+        &self,
+        dir: &Path,
+        index: usize,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let frame = &self.frames[index];
+        let path = dir.join(format!(
+            "frame_{index:05}_{:08}ms.png",
+            frame.timestamp.as_millis()
+        ));
+        let raster = pix::Raster::<pix::gray::SGray8>::with_u8_buffer(
+            self.width,
+            self.height,
+            frame.pixels.clone(),
+        );
+        let mut encoder =
+            png_pong::Encoder::new(BufWriter::new(File::create(&path)?)).into_step_enc();
+        encoder.encode(&png_pong::Step {
+            raster: png_pong::PngRaster::Gray8(raster),
+            delay: 0,
+        })?;
+        Ok(path)
+    }
+
+    /// Write all frames as an animated GIF, each frame is shown until the
+    /// timestamp of the next
+    pub fn write_gif(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let palette: Vec<u8> = (0..=255u8).flat_map(|v| [v, v, v]).collect();
+        let mut encoder = gif::Encoder::new(
+            BufWriter::new(File::create(path)?),
+            self.width as u16,
+            self.height as u16,
+            &palette,
+        )?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let mut gif_frame = gif::Frame::from_indexed_pixels(
+                self.width as u16, self.height as u16, &frame.pixels, None,
+            );
+            // GIF delays are in units of 10ms
+            gif_frame.delay = self
+                .frames
+                .get(i + 1)
+                .map(|next| {
+                    (next.timestamp.saturating_sub(frame.timestamp).as_millis() / 10) as u16
+                })
+                .unwrap_or(10);
+            encoder.write_frame(&gif_frame)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rog_anime::{AnimeDataBuffer, AnimePacketType, AnimeType};
+
+    use super::*;
+
+    #[test]
+    fn reassemble_frames() {
+        for model in [
+            AnimeType::GA401,
+            AnimeType::GA402,
+            AnimeType::GU604,
+        ] {
+            let mut recorder = FrameRecorder::new(model);
+            let mut buffer = AnimeDataBuffer::new(model);
+            buffer.data_mut().fill(200);
+            let packets = AnimePacketType::try_from(buffer).unwrap();
+
+            for pane in &packets {
+                assert!(recorder.write(pane, Duration::ZERO).is_none());
+            }
+            let frame = recorder
+                .write(&pkt_flush(), Duration::from_millis(40))
+                .unwrap()
+                .clone();
+            assert_eq!(frame.timestamp, Duration::from_millis(40));
+            assert_eq!(
+                frame.pixels.len(),
+                (recorder.width * recorder.height) as usize
+            );
+
+            // Each LED centre shows the brightness of the last LED drawn over it
+            let (w, h) = recorder.animatrix.led_size();
+            let mut centres = HashMap::new();
+            for pane in &packets {
+                let leds = recorder.animatrix.pane_leds(pane);
+                assert!(leds.iter().any(|led| led.brightness == 200));
+                for led in leds {
+                    let x = (led.x + w / 2) as usize;
+                    let y = (led.y + h / 2) as usize;
+                    centres.insert(y * recorder.width as usize + x, led.brightness);
+                }
+            }
+            for (idx, brightness) in centres {
+                assert_eq!(frame.pixels[idx], brightness);
+            }
+            assert!(frame.pixels.contains(&0));
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Reassembly and rendering of the per-key and zoned Aura packets using the
+//! keyboard layouts from `rog-aura/data/layouts`.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use rog_aura::keyboard::{KeyLayout, LedCode, LedUsbPackets};
+use rog_aura::Colour;
+
+/// The final per-key packet is key group `0xa0`
+const LAST_KEY_GROUP: u8 = 10;
+const BACKGROUND: [u8; 3] = [
+    24, 24, 24,
+];
+/// Keys which have no colour in the packets received
+const UNLIT: [u8; 3] = [
+    64, 64, 64,
+];
+
+/// Collects the packets written to the keyboard in to a complete set
+#[derive(Debug, Default)]
+pub struct KeyboardState {
+    packets: BTreeMap<u8, Vec<u8>>,
+    leds: Option<LedUsbPackets>,
+    /// `Some(multizone)` if the last set was zoned
+    zoned: Option<bool>,
+}
+
+impl KeyboardState {
+    /// Feed a report written to the device. Returns true if it completed a set
+    /// of packets and the keyboard colours changed.
+    pub fn write(&mut self, report: &[u8]) -> bool {
+        if report.len() != 64 || report[0] != 0x5d || report[1] != 0xbc {
+            return false;
+        }
+        if report == LedUsbPackets::get_init_msg() {
+            self.packets.clear();
+            return false;
+        }
+
+        if report[2] == 0x01 {
+            self.packets.clear();
+            self.zoned = Some(report[4] != 0x00);
+            self.leds = LedUsbPackets::from_packets(&[report.to_vec()]).ok();
+            return self.leds.is_some();
+        }
+
+        let group = report[6] >> 4;
+        self.packets.insert(group, report.to_vec());
+        if group != LAST_KEY_GROUP {
+            return false;
+        }
+        let packets: Vec<Vec<u8>> = self.packets.values().cloned().collect();
+        self.zoned = None;
+        self.leds = LedUsbPackets::from_packets(&packets).ok();
+        self.leds.is_some()
+    }
+
+    /// The colour of a key. On zoned keyboards the key takes the colour of the
+    /// zone it sits in, `position` being how far across the keyboard it is
+    /// from 0.0 to 1.0.
+    pub fn colour(&self, key: LedCode, position: f32) -> Option<Colour> {
+        let leds = self.leds.as_ref()?;
+        if let Some(colour) = leds.get_colour(key) {
+            return Some(colour);
+        }
+        match self.zoned {
+            Some(true) => leds.get_colour(match (position * 4.0) as u32 {
+                0 => LedCode::ZonedKbLeft,
+                1 => LedCode::ZonedKbLeftMid,
+                2 => LedCode::ZonedKbRightMid,
+                _ => LedCode::ZonedKbRight,
+            }),
+            Some(false) => leds.get_colour(LedCode::SingleZone),
+            None => None,
+        }
+    }
+}
+
+/// An RGB image of the keyboard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardImage {
+    pub width: u32,
+    pub height: u32,
+    /// RGB, 3 bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+/// A key placed in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRect {
+    pub led: LedCode,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct KeyboardRenderer {
+    /// Each key and how far across the keyboard its centre is, 0.0 to 1.0
+    keys: Vec<(KeyRect, f32)>,
+    width: u32,
+    height: u32,
+}
+
+impl KeyboardRenderer {
+    /// Place the keys of `layout` with `scale` pixels per key unit
+    pub fn new(layout: &KeyLayout, scale: f32) -> Self {
+        let positions = layout.key_positions();
+        // Lightbars can sit outside the keyboard
+        let min_x = positions.iter().map(|k| k.x).fold(0.0, f32::min);
+        let min_y = positions.iter().map(|k| k.y).fold(0.0, f32::min);
+        let max_x = positions.iter().map(|k| k.x + k.width).fold(0.0, f32::max);
+        let max_y = positions.iter().map(|k| k.y + k.height).fold(0.0, f32::max);
+        let keyboard_width = layout.max_width().max(f32::EPSILON);
+        let margin = 0.2;
+
+        let keys = positions
+            .iter()
+            .map(|k| {
+                let rect = KeyRect {
+                    led: k.led,
+                    x: ((k.x - min_x + margin) * scale) as i32,
+                    y: ((k.y - min_y + margin) * scale) as i32,
+                    width: (k.width * scale).max(1.0) as u32,
+                    height: (k.height * scale).max(1.0) as u32,
+                };
+                (rect, (k.x + k.width / 2.0) / keyboard_width)
+            })
+            .collect();
+
+        Self {
+            keys,
+            width: ((max_x - min_x + margin * 2.0) * scale) as u32,
+            height: ((max_y - min_y + margin * 2.0) * scale) as u32,
+        }
+    }
+
+    /// The colour to draw each key with
+    pub fn key_colours<'a>(
+        &'a self,
+        state: &'a KeyboardState,
+    ) -> impl Iterator<Item = (&'a KeyRect, [u8; 3])> {
+        self.keys.iter().map(|(rect, position)| {
+            let colour = state
+                .colour(rect.led, *position)
+                .map(|c| {
+                    [
+                        c.r, c.g, c.b,
+                    ]
+                })
+                .unwrap_or(UNLIT);
+            (rect, colour)
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn render(&self, state: &KeyboardState) -> KeyboardImage {
+        let mut pixels = BACKGROUND.repeat((self.width * self.height) as usize);
+        for (rect, colour) in self.key_colours(state) {
+            let x_end = (rect.x + rect.width as i32).min(self.width as i32);
+            let y_end = (rect.y + rect.height as i32).min(self.height as i32);
+            for y in rect.y.max(0)..y_end {
+                for x in rect.x.max(0)..x_end {
+                    let idx = (y as usize * self.width as usize + x as usize) * 3;
+                    pixels[idx..idx + 3].copy_from_slice(&colour);
+                }
+            }
+        }
+        KeyboardImage {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+impl KeyboardImage {
+    pub fn write_png(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let raster = pix::Raster::<pix::rgb::SRgb8>::with_u8_buffer(
+            self.width,
+            self.height,
+            self.pixels.clone(),
+        );
+        let mut encoder =
+            png_pong::Encoder::new(BufWriter::new(File::create(path)?)).into_step_enc();
+        encoder.encode(&png_pong::Step {
+            raster: png_pong::PngRaster::Rgb8(raster),
+            delay: 0,
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rog_aura::keyboard::AuraLaptopUsbPackets;
+
+    use super::*;
+
+    fn layout(name: &str) -> KeyLayout {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push(format!("../rog-aura/data/layouts/{name}_US.ron"));
+        KeyLayout::from_file(&path).unwrap()
+    }
+
+    fn pixel(image: &KeyboardImage, rect: &KeyRect) -> (u8, u8, u8) {
+        let x = rect.x as usize + rect.width as usize / 2;
+        let y = rect.y as usize + rect.height as usize / 2;
+        let idx = (y * image.width as usize + x) * 3;
+        (
+            image.pixels[idx],
+            image.pixels[idx + 1],
+            image.pixels[idx + 2],
+        )
+    }
+
+    #[test]
+    fn render_per_key() {
+        let renderer = KeyboardRenderer::new(&layout("g513i-per-key"), 20.0);
+        let mut leds = LedUsbPackets::new_per_key();
+        leds.set(LedCode::A, 0xff, 0x00, 0x00);
+        leds.set(LedCode::Esc, 0x00, 0x00, 0xff);
+
+        let mut state = KeyboardState::default();
+        assert!(!state.write(&LedUsbPackets::get_init_msg()));
+        let packets: AuraLaptopUsbPackets = leds.into();
+        let (last, rest) = packets.split_last().unwrap();
+        for pkt in rest {
+            assert!(!state.write(pkt));
+        }
+        assert!(state.write(last));
+
+        let image = renderer.render(&state);
+        assert_eq!(
+            image.pixels.len(),
+            (image.width * image.height * 3) as usize
+        );
+        for (rect, _) in &renderer.keys {
+            let expected = match rect.led {
+                LedCode::A => (0xff, 0x00, 0x00),
+                LedCode::Esc => (0x00, 0x00, 0xff),
+                LedCode::Q => (0x00, 0x00, 0x00),
+                _ => continue,
+            };
+            assert_eq!(pixel(&image, rect), expected, "{:?}", rect.led);
+        }
+    }
+
+    #[test]
+    fn render_zoned() {
+        let renderer = KeyboardRenderer::new(&layout("g513i"), 20.0);
+        let mut leds = LedUsbPackets::new_zoned(true);
+        leds.set(LedCode::ZonedKbLeft, 0xff, 0x00, 0x00);
+        leds.set(LedCode::ZonedKbRight, 0x00, 0xff, 0x00);
+
+        let mut state = KeyboardState::default();
+        let packets: AuraLaptopUsbPackets = leds.into();
+        assert!(state.write(&packets[0]));
+
+        let image = renderer.render(&state);
+        for (rect, _) in &renderer.keys {
+            let expected = match rect.led {
+                LedCode::Esc | LedCode::Q => (0xff, 0x00, 0x00),
+                LedCode::Backspace => (0x00, 0xff, 0x00),
+                _ => continue,
+            };
+            assert_eq!(pixel(&image, rect), expected, "{:?}", rect.led);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::env;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use log::error;
+use rog_aura::keyboard::KeyLayout;
+#[cfg(feature = "window")]
+use sdl2::event::Event;
+#[cfg(feature = "window")]
+use sdl2::keyboard::Keycode;
+#[cfg(feature = "window")]
+use sdl2::pixels::Color;
+#[cfg(feature = "window")]
+use sdl2::rect::Rect;
+use uhid_virt::{Bus, CreateParams, OutputEvent, UHIDDevice};
+
+mod keyboard;
+use keyboard::{KeyboardRenderer, KeyboardState};
+
+const VENDOR_ID: u16 = 0x0b05;
+const USAGE: &str = "\
+Usage: keyboard_sim <layout.ron> [--product <id>] [--scale <px>] [--png <dir>]
+
+<layout.ron> is a layout file from rog-aura/data/layouts. The virtual keyboard
+uses the USB product ID <id> in hex, default 19b6. With --png no window is
+opened and each complete set of per-key or zoned packets is written to a PNG
+named with its frame number and timestamp.";
+
+pub struct VirtKeyboard {
+    device: UHIDDevice<std::fs::File>,
+}
+
+impl VirtKeyboard {
+    pub fn new(product: u16) -> Self {
+        VirtKeyboard {
+            device: UHIDDevice::create(CreateParams {
+                name: String::from("ROG_Virtual Aura Keyboard"),
+                phys: String::from(""),
+                uniq: String::from(""),
+                bus: Bus::USB,
+                vendor: VENDOR_ID as u32,
+                product: product as u32,
+                version: 0,
+                country: 0,
+                rd_data: [
+                    0x06, 0x31, 0xff, // Usage Page (Vendor Defined 0xFF31)
+                    0x09, 0x79, // Usage (0x79)
+                    0xa1, 0x01, // Collection (Application)
+                    0x85, 0x5d, //   Report ID (93)
+                    0x19, 0x00, //   Usage Minimum (0x00)
+                    0x2a, 0xff, 0x00, //   Usage Maximum (0xFF)
+                    0x15, 0x00, //   Logical Minimum (0)
+                    0x26, 0xff, 0x00, //   Logical Maximum (255)
+                    0x75, 0x08, //   Report Size (8)
+                    0x95, 0x3f, //   Report Count (63)
+                    0x91,
+                    0x00, /*   Output (Data,Array,Abs,No Wrap,Linear,Preferred State,No Null
+                           * Position,Non-volatile) */
+                    0x95, 0x3f, //   Report Count (63)
+                    0xb1,
+                    0x00, /*   Feature (Data,Array,Abs,No Wrap,Linear,Preferred State,No Null
+                           * Position,Non-volatile) */
+                    0xc0, // End Collection
+                ]
+                .to_vec(),
+            })
+            .map_err(|err| error!("Could not create virtual device: {:?}", err))
+            .expect("Could not create virtual device"),
+        }
+    }
+
+    /// Block until the next report is written
+    pub fn read(&mut self) -> Option<Vec<u8>> {
+        match self.device.read() {
+            Ok(OutputEvent::Output { data }) => Some(data),
+            Ok(OutputEvent::SetReport { id, data, .. }) => {
+                self.device.write_set_report_reply(id, 0).ok();
+                Some(data)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    layout: PathBuf,
+    product: u16,
+    scale: f32,
+    png: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut args = args.iter();
+        let mut options = Self {
+            layout: args.next().ok_or(USAGE)?.into(),
+            product: 0x19b6,
+            scale: 40.0,
+            png: None,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{arg} requires a value"));
+            match arg.as_str() {
+                "--product" => options.product = u16::from_str_radix(value()?, 16)?,
+                "--scale" => options.scale = value()?.parse()?,
+                "--png" => options.png = Some(value()?.into()),
+                _ => return Err(format!("Unknown argument {arg}\n{USAGE}").into()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn run_headless(
+    renderer: &KeyboardRenderer,
+    options: &Options,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    let mut dev = VirtKeyboard::new(options.product);
+    let mut state = KeyboardState::default();
+    let start = Instant::now();
+    let mut count = 0;
+    let (width, height) = renderer.size();
+    println!(
+        "Recording keyboard {:04x} as {width}x{height} images, waiting for asusd",
+        options.product
+    );
+    loop {
+        let Some(report) = dev.read() else {
+            continue;
+        };
+        if !state.write(&report) {
+            continue;
+        }
+        let millis = start.elapsed().as_millis();
+        let path = dir.join(format!("frame_{count:05}_{millis:08}ms.png"));
+        renderer.render(&state).write_png(&path)?;
+        println!("frame {count} at {millis}ms");
+        count += 1;
+    }
+}
+
+#[cfg(feature = "window")]
+fn run_window(renderer: &KeyboardRenderer, options: &Options) -> Result<(), Box<dyn Error>> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+    let (width, height) = renderer.size();
+    let window = video_subsystem
+        .window("ROG keyboard", width, height)
+        .position_centered()
+        .build()?;
+    let mut canvas = window.into_canvas().build()?;
+
+    let mut dev = VirtKeyboard::new(options.product);
+    let mut state = KeyboardState::default();
+    let mut event_pump = sdl_context.event_pump()?;
+    'running: loop {
+        // Blocking, so the window only updates as packets arrive
+        if let Some(report) = dev.read() {
+            if state.write(&report) {
+                canvas.set_draw_color(Color::RGB(24, 24, 24));
+                canvas.clear();
+                for (rect, [r, g, b]) in renderer.key_colours(&state) {
+                    canvas.set_draw_color(Color::RGB(r, g, b));
+                    canvas.fill_rect(Rect::new(rect.x, rect.y, rect.width, rect.height))?;
+                }
+                canvas.present();
+            }
+        }
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() <= 1 {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let options = Options::parse(&args[1..])?;
+    let layout = KeyLayout::from_file(&options.layout)?;
+    let renderer = KeyboardRenderer::new(&layout, options.scale);
+
+    if let Some(dir) = &options.png {
+        return run_headless(&renderer, &options, dir);
+    }
+    #[cfg(feature = "window")]
+    return run_window(&renderer, &options);
+    #[cfg(not(feature = "window"))]
+    {
+        println!("Built without the window feature, use --png\n{USAGE}");
+        Ok(())
+    }
+}
@@ -1,30 +1,60 @@
 use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Instant;
 
 use log::error;
 use rog_anime::usb::{PROD_ID, VENDOR_ID};
-use rog_anime::{AnimeType, USB_PREFIX2};
+use rog_anime::AnimeType;
+#[cfg(feature = "window")]
+use rog_anime::USB_PREFIX2;
+#[cfg(feature = "window")]
 use sdl2::event::Event;
+#[cfg(feature = "window")]
 use sdl2::keyboard::Keycode;
+#[cfg(feature = "window")]
 use sdl2::pixels::Color;
+#[cfg(feature = "window")]
 use sdl2::rect::Rect;
-use uhid_virt::{Bus, CreateParams, UHIDDevice};
+use uhid_virt::{Bus, CreateParams, OutputEvent, UHIDDevice};
 
 mod animatrix;
-use animatrix::*;
+#[cfg(feature = "window")]
+use animatrix::AniMatrix;
+mod headless;
+use headless::FrameRecorder;
+
+const USAGE: &str = "Usage: anime_sim <GA401, GA402, GU604> [--png <dir>] [--gif <file>] \
+                     [--frames <count>]
+
+With --png or --gif no window is opened, each complete frame is recorded with
+its timestamp until <count> frames are recorded or the device is closed.";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReadEvent {
+    /// A report was written and is now in the buffer
+    Data,
+    Other,
+    /// The device was closed by the last user
+    Closed,
+}
 
 pub struct VirtAnimeMatrix {
     device: UHIDDevice<std::fs::File>,
     buffer: [u8; 640],
-    animatrix: AniMatrix,
+}
+
+impl Default for VirtAnimeMatrix {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VirtAnimeMatrix {
-    pub fn new(model: AnimeType) -> Self {
+    pub fn new() -> Self {
         VirtAnimeMatrix {
             buffer: [0; 640],
-            animatrix: AniMatrix::new(model),
             device: UHIDDevice::create(CreateParams {
                 name: String::from("ROG_Virtual Anime Matrix"),
                 phys: String::from(""),
@@ -99,26 +129,90 @@ impl VirtAnimeMatrix {
     //     self.device.write(&reset).unwrap();
     // }
 
-    pub fn read(&mut self) {
-        if let Ok(uhid_virt::OutputEvent::Output { data }) = self.device.read() {
-            for (i, b) in self.buffer.iter_mut().enumerate() {
-                *b = 0;
-                if let Some(n) = data.get(i) {
-                    *b = *n;
+    pub fn read(&mut self) -> ReadEvent {
+        match self.device.read() {
+            Ok(OutputEvent::Output { data }) => {
+                for (i, b) in self.buffer.iter_mut().enumerate() {
+                    *b = 0;
+                    if let Some(n) = data.get(i) {
+                        *b = *n;
+                    }
                 }
+                ReadEvent::Data
             }
+            Ok(OutputEvent::Close) => ReadEvent::Closed,
+            _ => ReadEvent::Other,
         }
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() <= 1 {
-        println!("Must supply arg, one of <GA401, GA402, GU604>");
-        return Ok(());
+#[derive(Debug, Default)]
+struct Options {
+    png: Option<PathBuf>,
+    gif: Option<PathBuf>,
+    frames: Option<usize>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{arg} requires a value"));
+            match arg.as_str() {
+                "--png" => options.png = Some(value()?.into()),
+                "--gif" => options.gif = Some(value()?.into()),
+                "--frames" => options.frames = Some(value()?.parse()?),
+                _ => return Err(format!("Unknown argument {arg}\n{USAGE}").into()),
+            }
+        }
+        Ok(options)
     }
-    let anime_type = AnimeType::from_str(&args[1])?;
 
+    fn headless(&self) -> bool {
+        self.png.is_some() || self.gif.is_some()
+    }
+}
+
+fn run_headless(anime_type: AnimeType, options: &Options) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = &options.png {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut dev = VirtAnimeMatrix::new();
+    let mut recorder = FrameRecorder::new(anime_type);
+    let start = Instant::now();
+    println!("Recording AniMe {anime_type:?} frames, waiting for asusd");
+    loop {
+        match dev.read() {
+            ReadEvent::Data => {}
+            ReadEvent::Other => continue,
+            ReadEvent::Closed => break,
+        }
+        let Some(timestamp) = recorder
+            .write(&dev.buffer, start.elapsed())
+            .map(|f| f.timestamp)
+        else {
+            continue;
+        };
+        let index = recorder.frames().len() - 1;
+        println!("frame {index} at {}ms", timestamp.as_millis());
+        if let Some(dir) = &options.png {
+            recorder.write_png(dir, index)?;
+        }
+        if options.frames.is_some_and(|n| recorder.frames().len() >= n) {
+            break;
+        }
+    }
+
+    if let Some(path) = &options.gif {
+        recorder.write_gif(path)?;
+        println!("Wrote {} frames to {path:?}", recorder.frames().len());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "window")]
+fn run_window(anime_type: AnimeType) -> Result<(), Box<dyn Error>> {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
@@ -130,7 +224,8 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut canvas = window.into_canvas().build().unwrap();
 
-    let mut dev = VirtAnimeMatrix::new(anime_type);
+    let mut dev = VirtAnimeMatrix::new();
+    let animatrix = AniMatrix::new(anime_type);
 
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
@@ -138,38 +233,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     'running: loop {
         dev.read(); // it's blocking, and damned hard to sync with arc/mutex
                     // let one = dev.buffer[0..7] != USB_PREFIX2;
-        let index = dev.buffer[3];
-
-        let w = dev.animatrix.led_shape().horizontal * 6;
-        let h = dev.animatrix.led_shape().vertical * 6;
-        let mut y_offset = 0;
-        for (y_count, row) in dev.animatrix.rows().iter().enumerate() {
-            if row.0 == index {
-                let start = row.1;
-                let end = start + row.2;
-                if row.1 < 10 && row.2 < 15 {
-                    if index == 0x74 {
-                        y_offset = 1;
-                    } else if index == 0xe7 {
-                        y_offset = 2;
-                    }
-                }
-                for (x_count, b) in dev.buffer[start..=end].iter().enumerate() {
-                    canvas.set_draw_color(Color::RGB(*b, *b, *b));
-
-                    let x: i32 = w + x_count as i32 * w
-                        - if (y_count + y_offset as usize) % 2 != 0 {
-                            0
-                        } else {
-                            w / 2
-                        }
-                        + row.3 * w;
-                    let y = y_count as i32 * h - y_offset * h;
-                    canvas
-                        .fill_rect(Rect::new(x, y, w as u32, h as u32))
-                        .unwrap();
-                }
-            }
+        let (w, h) = animatrix.led_size();
+        for led in animatrix.pane_leds(&dev.buffer) {
+            let b = led.brightness;
+            canvas.set_draw_color(Color::RGB(b, b, b));
+            canvas
+                .fill_rect(Rect::new(led.x, led.y, w as u32, h as u32))
+                .unwrap();
         }
 
         for event in event_pump.poll_iter() {
@@ -191,3 +261,24 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() <= 1 {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let anime_type = AnimeType::from_str(&args[1])?;
+    let options = Options::parse(&args[2..])?;
+
+    if options.headless() {
+        return run_headless(anime_type, &options);
+    }
+    #[cfg(feature = "window")]
+    return run_window(anime_type);
+    #[cfg(not(feature = "window"))]
+    {
+        println!("Built without the window feature, use --png or --gif\n{USAGE}");
+        Ok(())
+    }
+}
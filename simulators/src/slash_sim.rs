@@ -0,0 +1,187 @@
+use std::env;
+use std::error::Error;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use log::{error, warn};
+use rog_slash::usb::{report_id, SlashReport, VENDOR_ID};
+use rog_slash::{SlashMode, SlashType};
+use uhid_virt::{Bus, CreateParams, OutputEvent, UHIDDevice};
+
+/// The state of the bar as set by the packets written to it
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SlashBar {
+    /// Count of init packets seen, the device needs both after boot
+    pub init: u8,
+    pub enabled: bool,
+    pub mode: Option<SlashMode>,
+    pub brightness: u8,
+    pub interval: u8,
+    pub show_on_boot: bool,
+    pub show_on_sleep: bool,
+    pub show_on_shutdown: bool,
+    pub show_on_low_battery: bool,
+    pub show_on_battery_saver: bool,
+    pub show_on_lid_closed: bool,
+    pub saves: usize,
+}
+
+impl SlashBar {
+    pub fn apply(&mut self, report: SlashReport) {
+        match report {
+            SlashReport::Init1 => self.init = 1,
+            SlashReport::Init2 => self.init = self.init.max(1) + 1,
+            SlashReport::Enable(enabled) => self.enabled = enabled,
+            SlashReport::Save => self.saves += 1,
+            SlashReport::ModeSelect => {}
+            SlashReport::Mode(mode) => self.mode = Some(mode),
+            SlashReport::Options {
+                enabled,
+                brightness,
+                interval,
+            } => {
+                self.enabled = enabled;
+                self.brightness = brightness;
+                self.interval = interval;
+            }
+            SlashReport::ShowOnBoot(on) => self.show_on_boot = on,
+            SlashReport::ShowOnSleep(on) => self.show_on_sleep = on,
+            SlashReport::ShowOnLowBattery(on) => self.show_on_low_battery = on,
+            SlashReport::ShowOnShutdown(on) => self.show_on_shutdown = on,
+            SlashReport::ShowOnBatterySaver(on) => self.show_on_battery_saver = on,
+            SlashReport::ShowOnLidClosed(on) => self.show_on_lid_closed = on,
+        }
+    }
+}
+
+impl Display for SlashBar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Seven segments, shaded by brightness
+        let segment = match (self.enabled, self.brightness) {
+            (false, _) | (_, 0) => ' ',
+            (_, 1..=85) => '░',
+            (_, 86..=170) => '▒',
+            _ => '▓',
+        };
+        let on = |b: bool| if b { "on" } else { "off" };
+        writeln!(
+            f,
+            "[{}] {} mode: {} brightness: {} interval: {}",
+            String::from(segment).repeat(7),
+            if self.enabled { "ENABLED" } else { "DISABLED" },
+            self.mode.map(|m| m.to_string()).unwrap_or("-".into()),
+            self.brightness,
+            self.interval,
+        )?;
+        write!(
+            f,
+            "  init: {}/2 saves: {} boot: {} sleep: {} shutdown: {} low battery: {} battery \
+             saver: {} lid closed: {}",
+            self.init,
+            self.saves,
+            on(self.show_on_boot),
+            on(self.show_on_sleep),
+            on(self.show_on_shutdown),
+            on(self.show_on_low_battery),
+            on(self.show_on_battery_saver),
+            on(self.show_on_lid_closed),
+        )
+    }
+}
+
+pub struct VirtSlash {
+    device: UHIDDevice<std::fs::File>,
+    slash_type: SlashType,
+    bar: SlashBar,
+}
+
+impl VirtSlash {
+    pub fn new(slash_type: SlashType) -> Self {
+        let mut rd_data = [
+            0x06, 0x31, 0xff, // Usage Page (Vendor Defined 0xFF31)
+            0x09, 0x76, // Usage (0x76)
+            0xa1, 0x01, // Collection (Application)
+            0x85, 0x00, //   Report ID, set below
+            0x19, 0x00, //   Usage Minimum (0x00)
+            0x2a, 0xff, 0x00, //   Usage Maximum (0xFF)
+            0x15, 0x00, //   Logical Minimum (0)
+            0x26, 0xff, 0x00, //   Logical Maximum (255)
+            0x75, 0x08, //   Report Size (8)
+            0x95, 0x1f, //   Report Count (31)
+            0x91,
+            0x00, /*   Output (Data,Array,Abs,No Wrap,Linear,Preferred State,No Null
+                   * Position,Non-volatile) */
+            0x95, 0x1f, //   Report Count (31)
+            0xb1,
+            0x00, /*   Feature (Data,Array,Abs,No Wrap,Linear,Preferred State,No Null
+                   * Position,Non-volatile) */
+            0xc0, // End Collection
+        ];
+        rd_data[8] = report_id(slash_type);
+
+        VirtSlash {
+            slash_type,
+            bar: SlashBar::default(),
+            device: UHIDDevice::create(CreateParams {
+                name: String::from("ROG_Virtual Slash"),
+                phys: String::from(""),
+                uniq: String::from(""),
+                bus: Bus::USB,
+                vendor: VENDOR_ID as u32,
+                product: slash_type.prod_id() as u32,
+                version: 0,
+                country: 0,
+                rd_data: rd_data.to_vec(),
+            })
+            .map_err(|err| error!("Could not create virtual device: {:?}", err))
+            .expect("Could not create virtual device"),
+        }
+    }
+
+    /// Block until the next packet arrives. Returns true if the bar changed.
+    pub fn read(&mut self) -> bool {
+        let data = match self.device.read() {
+            Ok(OutputEvent::Output { data }) => data,
+            Ok(OutputEvent::SetReport { id, data, .. }) => {
+                self.device.write_set_report_reply(id, 0).ok();
+                data
+            }
+            _ => return false,
+        };
+        match SlashReport::decode(self.slash_type, &data) {
+            Ok(report) => {
+                let old = self.bar.clone();
+                self.bar.apply(report);
+                old != self.bar
+            }
+            Err(e) => {
+                warn!("{e}");
+                false
+            }
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() <= 1 {
+        println!("Must supply arg, one of <GA403, GA605, GU605>");
+        return Ok(());
+    }
+    let slash_type = SlashType::from_str(&args[1])?;
+    if slash_type == SlashType::Unsupported {
+        println!(
+            "Unknown model {}, must be one of <GA403, GA605, GU605>",
+            args[1]
+        );
+        return Ok(());
+    }
+
+    let mut dev = VirtSlash::new(slash_type);
+    println!("Virtual Slash {slash_type:?} created, waiting for packets");
+    loop {
+        if dev.read() {
+            println!("{}", dev.bar);
+        }
+    }
+}